@@ -0,0 +1,142 @@
+use crate::simulation::{CoreState, Snapshot};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+// Autosaves kept when `--autosave-keep` isn't given
+pub const DEFAULT_KEEP: usize = 3;
+// Directory autosaves go to when `--autosave-dir` isn't given
+pub const DEFAULT_DIR: &str = "autosaves";
+
+// Saves the state every `interval` ticks into `dir` as `autosave-<tick>.ron`, deleting the
+// oldest once more than `keep` were written. The simulation thread only copies the state, a
+// writer thread encodes and writes it.
+pub struct Autosaver {
+    interval: u64,
+    // Tick of the last save, a frame without steps must not save the same tick twice
+    last_tick: Option<u64>,
+    sender: Option<Sender<Snapshot>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Autosaver {
+    // Creates `dir` if it is missing and starts the writer thread
+    pub fn start(dir: &Path, interval: u64, keep: usize) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|error| format!("{}: {error}", dir.display()))?;
+        // One save waiting on the one being written, any more and the disk can't keep up anyway
+        let (sender, receiver) = bounded::<Snapshot>(1);
+        let dir = dir.to_path_buf();
+        let writer = std::thread::Builder::new()
+            .name("autosave".to_string())
+            .spawn(move || {
+                let mut written = VecDeque::new();
+                for snapshot in receiver {
+                    match write(&dir, &snapshot) {
+                        Ok(path) => written.push_back(path),
+                        Err(error) => eprintln!("Failed to autosave: {error}"),
+                    }
+                    while written.len() > keep {
+                        if let Some(path) = written.pop_front() {
+                            let _ = fs::remove_file(path);
+                        }
+                    }
+                }
+            })
+            .map_err(|error| error.to_string())?;
+        Ok(Self {
+            interval: interval.max(1),
+            last_tick: None,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    // Hands the state to the writer if the current tick is due, call it after every step
+    pub fn after_step(&mut self, core_state: &CoreState) {
+        let tick = core_state.tick();
+        if !tick.is_multiple_of(self.interval) || self.last_tick == Some(tick) {
+            return;
+        }
+        self.last_tick = Some(tick);
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        // Skipping a save beats stalling the simulation until the disk catches up
+        if let Err(TrySendError::Full(_)) = sender.try_send(core_state.snapshot()) {
+            eprintln!(
+                "Skipped the autosave at tick {tick}, the previous one is still being written"
+            );
+        }
+    }
+
+    // Waits for the saves still being written
+    pub fn finish(mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+// Writes next to the final path and renames, a crash mid-write leaves the previous autosaves intact
+fn write(dir: &Path, snapshot: &Snapshot) -> Result<PathBuf, String> {
+    let path = dir.join(format!("autosave-{:010}.ron", snapshot.tick()));
+    let partial = path.with_extension("ron.partial");
+    let contents = snapshot.encode()?;
+    fs::write(&partial, contents)
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SimulationConfig;
+
+    #[test]
+    fn keeps_the_newest_saves_of_due_ticks() {
+        let dir = std::env::temp_dir().join("particle_evolution_autosave_test");
+        let _ = fs::remove_dir_all(&dir);
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 30,
+            ..SimulationConfig::default()
+        });
+        let mut autosaver = Autosaver::start(&dir, 10, 2).unwrap();
+        let mut expected = None;
+        for _ in 0..35 {
+            core_state.step(1.0 / 120.0);
+            // A slow disk may make it skip saves, waiting for the queue to drain here keeps all
+            while autosaver
+                .sender
+                .as_ref()
+                .is_some_and(|sender| !sender.is_empty())
+            {
+                std::thread::yield_now();
+            }
+            autosaver.after_step(&core_state);
+            if core_state.tick() == 30 {
+                expected = Some(core_state.encode().unwrap());
+                // Frames without a step don't save again
+                autosaver.after_step(&core_state);
+            }
+        }
+        autosaver.finish();
+
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["autosave-0000000020.ron", "autosave-0000000030.ron"]
+        );
+        let latest = dir.join("autosave-0000000030.ron");
+        assert_eq!(fs::read_to_string(&latest).ok(), expected);
+        assert_eq!(CoreState::load(&latest).unwrap().tick(), 30);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+// Steps a benchmark takes without `--ticks`
+pub const DEFAULT_TICKS: u64 = 1000;
+
+// Outcome of a benchmark run, printed as a single JSON line so results can be compared across
+// commits with any tool at hand
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchReport {
+    pub seed: u64,
+    pub particles: usize,
+    pub ticks: u64,
+    pub elapsed: Duration,
+    // `None` where the platform doesn't tell
+    pub peak_memory_bytes: Option<u64>,
+}
+
+impl BenchReport {
+    pub fn ticks_per_second(&self) -> f64 {
+        self.ticks as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    pub fn to_json(&self) -> String {
+        let peak_memory = self
+            .peak_memory_bytes
+            .map_or_else(|| "null".to_string(), |bytes| bytes.to_string());
+        format!(
+            "{{\"seed\":{},\"particles\":{},\"ticks\":{},\"total_seconds\":{:.6},\"ticks_per_second\":{:.3},\"peak_memory_bytes\":{}}}",
+            self.seed,
+            self.particles,
+            self.ticks,
+            self.elapsed.as_secs_f64(),
+            self.ticks_per_second(),
+            peak_memory
+        )
+    }
+}
+
+// High water mark of the resident set size, only Linux reports it
+pub fn peak_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kilobytes = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_is_a_single_json_line() {
+        let mut report = BenchReport {
+            seed: 7,
+            particles: 500,
+            ticks: 1000,
+            elapsed: Duration::from_millis(2500),
+            peak_memory_bytes: Some(4096),
+        };
+        assert_eq!(
+            report.to_json(),
+            "{\"seed\":7,\"particles\":500,\"ticks\":1000,\"total_seconds\":2.500000,\"ticks_per_second\":400.000,\"peak_memory_bytes\":4096}"
+        );
+
+        report.peak_memory_bytes = None;
+        assert!(report.to_json().ends_with("\"peak_memory_bytes\":null}"));
+        assert!(!report.to_json().contains('\n'));
+
+        if cfg!(target_os = "linux") {
+            assert!(peak_memory_bytes().is_some_and(|bytes| bytes > 0));
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::simulation::Particle;
+
+// Zooming is clamped so the transform can't collapse to zero or blow up to infinity
+const MIN_ZOOM: f32 = 0.01;
+const MAX_ZOOM: f32 = 100.0;
+// Share of the screen `CameraMode::FitAll` fills with the particles, leaves a border around them
+const FIT_MARGIN: f32 = 0.9;
+
+// What moves the camera besides the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    // Nothing, it stays where the user puts it
+    Manual,
+    // Stays centred on the mass weighted mean position of the particles, zooming is left to the user
+    FollowCenterOfMass,
+    // Centres and zooms to keep the bounding box of every particle in view
+    FitAll,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::Manual => CameraMode::FollowCenterOfMass,
+            CameraMode::FollowCenterOfMass => CameraMode::FitAll,
+            CameraMode::FitAll => CameraMode::Manual,
+        }
+    }
+}
+
+// View onto the world, `center` is the world position shown in the middle of the screen
+// and `zoom` the number of screen pixels per world unit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub center: [f32; 2],
+    pub zoom: f32,
+}
+
+impl Camera {
+    pub fn new(center: [f32; 2]) -> Self {
+        Self { center, zoom: 1.0 }
+    }
+
+    pub fn world_to_screen(&self, world: [f32; 2], screen_size: [f32; 2]) -> [f32; 2] {
+        [
+            (world[0] - self.center[0]) * self.zoom + screen_size[0] / 2.0,
+            (world[1] - self.center[1]) * self.zoom + screen_size[1] / 2.0,
+        ]
+    }
+
+    pub fn screen_to_world(&self, screen: [f32; 2], screen_size: [f32; 2]) -> [f32; 2] {
+        [
+            (screen[0] - screen_size[0] / 2.0) / self.zoom + self.center[0],
+            (screen[1] - screen_size[1] / 2.0) / self.zoom + self.center[1],
+        ]
+    }
+
+    // Centers the view on a world of `world_size` starting at the origin and zooms to show all
+    // of it. Zoom is uniform, so a window of another aspect ratio gets bars instead of stretching.
+    pub fn fit(&mut self, world_size: [f32; 2], screen_size: [f32; 2]) {
+        self.center = [world_size[0] / 2.0, world_size[1] / 2.0];
+        let zoom = (screen_size[0] / world_size[0]).min(screen_size[1] / world_size[1]);
+        self.zoom = if zoom.is_finite() {
+            zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+        } else {
+            1.0
+        };
+    }
+
+    // Moves the view along with a drag of `delta` screen pixels
+    pub fn pan(&mut self, delta: [f32; 2]) {
+        self.center[0] -= delta[0] / self.zoom;
+        self.center[1] -= delta[1] / self.zoom;
+    }
+
+    // Scales the zoom by `factor` while keeping the world point under `anchor` in place
+    pub fn zoom_around(&mut self, anchor: [f32; 2], factor: f32, screen_size: [f32; 2]) {
+        let anchored = self.screen_to_world(anchor, screen_size);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center = [
+            anchored[0] - (anchor[0] - screen_size[0] / 2.0) / self.zoom,
+            anchored[1] - (anchor[1] - screen_size[1] / 2.0) / self.zoom,
+        ];
+    }
+
+    // Where `mode` wants the view on `particles`, `None` when it leaves the camera alone
+    pub fn target(
+        &self,
+        mode: CameraMode,
+        particles: &[Particle],
+        screen_size: [f32; 2],
+    ) -> Option<Camera> {
+        if particles.is_empty() {
+            return None;
+        }
+        match mode {
+            CameraMode::Manual => None,
+            CameraMode::FollowCenterOfMass => {
+                let mut weighted = [0.0; 2];
+                let mut total_mass = 0.0;
+                for particle in particles {
+                    weighted[0] += particle.position[0] * particle.mass;
+                    weighted[1] += particle.position[1] * particle.mass;
+                    total_mass += particle.mass;
+                }
+                let center = [weighted[0] / total_mass, weighted[1] / total_mass];
+                center
+                    .iter()
+                    .all(|value| value.is_finite())
+                    .then_some(Camera {
+                        center,
+                        zoom: self.zoom,
+                    })
+            }
+            CameraMode::FitAll => {
+                let mut min = [f32::INFINITY; 2];
+                let mut max = [f32::NEG_INFINITY; 2];
+                for particle in particles {
+                    for axis in 0..2 {
+                        min[axis] = min[axis].min(particle.position[axis] - particle.radius);
+                        max[axis] = max[axis].max(particle.position[axis] + particle.radius);
+                    }
+                }
+                let extent = [max[0] - min[0], max[1] - min[1]];
+                let zoom =
+                    (screen_size[0] / extent[0]).min(screen_size[1] / extent[1]) * FIT_MARGIN;
+                Some(Camera {
+                    center: [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0],
+                    zoom: if zoom.is_finite() {
+                        zoom.clamp(MIN_ZOOM, MAX_ZOOM)
+                    } else {
+                        self.zoom
+                    },
+                })
+            }
+        }
+    }
+
+    // Moves `blend` of the way from 0.0 to 1.0 towards `target`. Zoom is blended by ratio, so
+    // zooming in takes as long as zooming out by the same factor.
+    pub fn approach(&mut self, target: Camera, blend: f32) {
+        let blend = blend.clamp(0.0, 1.0);
+        for axis in 0..2 {
+            self.center[axis] += (target.center[axis] - self.center[axis]) * blend;
+        }
+        self.zoom *= (target.zoom / self.zoom).powf(blend);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+
+    #[test]
+    fn tracking_modes_ease_towards_the_particles() {
+        let particle = |position: [f32; 2], mass: f32| {
+            let mut particle = Particle::new(0, 0, position, Genome::default(), 1.0);
+            (particle.mass, particle.radius) = (mass, 5.0);
+            particle
+        };
+        let particles = [particle([0.0, 0.0], 3.0), particle([100.0, 200.0], 1.0)];
+        let camera = Camera::new([500.0, 500.0]);
+        let screen_size = [400.0, 300.0];
+
+        assert_eq!(
+            camera.target(CameraMode::Manual, &particles, screen_size),
+            None
+        );
+        assert_eq!(
+            camera.target(CameraMode::FollowCenterOfMass, &particles, screen_size),
+            Some(Camera::new([25.0, 50.0]))
+        );
+        // 110x210 with the radii, the height decides
+        let fit = camera
+            .target(CameraMode::FitAll, &particles, screen_size)
+            .unwrap();
+        assert_eq!(fit.center, [50.0, 100.0]);
+        assert!((fit.zoom - 300.0 / 210.0 * FIT_MARGIN).abs() < 1e-5);
+        assert_eq!(camera.target(CameraMode::FitAll, &[], screen_size), None);
+
+        let mut eased = camera;
+        eased.approach(
+            Camera {
+                center: [0.0, 1000.0],
+                zoom: 4.0,
+            },
+            0.5,
+        );
+        assert_eq!(
+            eased,
+            Camera {
+                center: [250.0, 750.0],
+                zoom: 2.0
+            }
+        );
+        eased.approach(fit, 1.0);
+        assert_eq!(eased, fit);
+    }
+
+    #[test]
+    fn single_particles_are_centered_and_empty_worlds_leave_the_camera() {
+        let camera = Camera::new([500.0, 500.0]);
+        let screen_size = [400.0, 300.0];
+        for mode in [CameraMode::FollowCenterOfMass, CameraMode::FitAll] {
+            assert_eq!(camera.target(mode, &[], screen_size), None);
+        }
+
+        let mut particles = [Particle::new(0, 0, [30.0, 40.0], Genome::default(), 1.0)];
+        let follow = camera
+            .target(CameraMode::FollowCenterOfMass, &particles, screen_size)
+            .unwrap();
+        assert_eq!(follow, Camera::new([30.0, 40.0]));
+        // Its diameter fills the view, as far as the zoom is allowed to go
+        let fit = camera
+            .target(CameraMode::FitAll, &particles, screen_size)
+            .unwrap();
+        assert_eq!(fit.center, [30.0, 40.0]);
+        assert!((MIN_ZOOM..=MAX_ZOOM).contains(&fit.zoom), "{}", fit.zoom);
+
+        // Nothing to take a size or weight from, the camera centers but keeps its zoom, or
+        // stays put when there is no center
+        (particles[0].radius, particles[0].mass) = (0.0, 0.0);
+        let fit = camera
+            .target(CameraMode::FitAll, &particles, screen_size)
+            .unwrap();
+        assert_eq!(fit, Camera::new([30.0, 40.0]));
+        assert_eq!(
+            camera.target(CameraMode::FollowCenterOfMass, &particles, screen_size),
+            None
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::render::ParticleShape;
+use std::fmt::Display;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: particle_evolution [OPTIONS]
+       particle_evolution bench [OPTIONS]
+       particle_evolution sweep <DIR> [--ticks <COUNT>] [--summary-csv <PATH>]
+
+Options:
+    --config <PATH>       RON file with the simulation parameters, created from the defaults if
+                          missing [default: simulation.ron]
+    --load <PATH>         Resume a save written with F5 instead of generating a new world
+    --layout <PATH>       Start from the particles in a CSV file, one `x,y[,vx,vy[,species]]`
+                          per line, instead of random ones
+    --width <PIXELS>      Width of the window and the world [default: from the config]
+    --height <PIXELS>     Height of the window and the world [default: from the config]
+    --particles <COUNT>   Number of particles spawned at the start
+    --seed <SEED>         Seed everything random is derived from
+    --all-pairs           Check every pair of particles for collisions instead of using the
+                          grid, to validate that it finds the same ones
+    --fps <FPS>           Frames rendered per second while vsync is off, 0 for uncapped
+                          [default: 60]
+    --no-vsync            Present frames immediately and pace them with --fps, may tear
+    --uncapped            Render as fast as possible without vsync or --fps and show the frame
+                          rate in the overlay, for benchmarking. Keeps a CPU core busy
+    --trail-decay <DECAY> Share of the previous frame kept under the next one, from 0 for no
+                          trails up to just below 1 for long ones [default: 0]
+    --velocity-scale <SECS>
+                          Seconds of motion the velocity vectors toggled with V cover
+                          [default: 0.1]
+    --background <COLOR>  Background as RRGGBB or RRGGBBAA hex [default: 00000000]
+    --shape <SHAPE>       Draw particles as a circle, square or point, P cycles through them.
+                          Points stay fast with millions of particles [default: circle]
+    --density             Start out drawing a heatmap of particle density, H toggles it
+    --max-runtime <SECS>  Stop the simulation and close the window after this long
+    --pause-unfocused     Pause while the window is in the background, resume once it is back
+    --stats-csv <PATH>    Write population statistics for every tick to a CSV file
+    --record-gif <PATH>   Record the first rendered frames into an animated GIF
+    --frames <COUNT>      Frames recorded with --record-gif [default: 300]
+    --record-log <PATH>   Log the starting state and every input changing the world, for
+                          --replay
+    --replay <PATH>       Run a session logged with --record-log again, ignoring input until
+                          the log runs out
+    --autosave-interval <TICKS>
+                          Save the state every this many ticks, for recovering from crashes
+                          or stitching timelapses
+    --autosave-dir <PATH> Where autosaves go, as autosave-<tick>.ron [default: autosaves]
+    --autosave-keep <COUNT>
+                          Autosaves kept, older ones are deleted [default: 3]
+    --profile             Print where the time went in steps and frames on exit
+    --energy-check <PCT>  Log the total energy and warn once it drifts more than this many
+                          percent, for validating the integrator on closed systems
+    --headless            Run the simulation as fast as possible without opening a window
+    --ticks <COUNT>       Stop a headless run after this many steps
+    --save <PATH>         Write the final state of a headless run to a save file
+    --summary-csv <PATH>  Where a sweep writes its results [default: sweep.csv]
+    -h, --help            Print this message
+
+Options given on the command line take precedence over the config file, a replay starts from
+the logged world and config instead. A headless run ends once it reaches --ticks or
+--max-runtime, when the population dies out or when a replay without --ticks runs out of input.
+
+Exits with 69 when no window can be opened for lack of a display or graphics adapter, and
+with 101 when the simulation panics.
+
+`bench` runs headless from the default parameters unless --config is given, for 1000 ticks
+unless --ticks is given, and prints the timing as one JSON line.
+
+`sweep` runs every .ron config in DIR headless one after the other in the same process, each
+for 1000 ticks unless --ticks is given or until its population dies out, and writes a CSV row
+with the final statistics of each.";
+
+// Options given on the command line, `None` for everything left at its default
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub load: Option<PathBuf>,
+    pub layout: Option<PathBuf>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub particles: Option<usize>,
+    pub seed: Option<u64>,
+    pub all_pairs: bool,
+    pub fps: Option<u32>,
+    pub no_vsync: bool,
+    pub uncapped: bool,
+    pub trail_decay: Option<f32>,
+    pub velocity_scale: Option<f32>,
+    pub background: Option<[u8; 4]>,
+    pub shape: Option<ParticleShape>,
+    pub density: bool,
+    pub max_runtime: Option<Duration>,
+    pub pause_unfocused: bool,
+    pub stats_csv: Option<PathBuf>,
+    pub record_gif: Option<PathBuf>,
+    pub frames: Option<u32>,
+    pub record_log: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub autosave_interval: Option<u64>,
+    pub autosave_dir: Option<PathBuf>,
+    pub autosave_keep: Option<usize>,
+    pub profile: bool,
+    pub energy_check: Option<f32>,
+    pub headless: bool,
+    // Headless run reporting its timing, see `bench::BenchReport`
+    pub bench: bool,
+    // Directory of configs run back to back, see `sweep::run`
+    pub sweep: Option<PathBuf>,
+    pub summary_csv: Option<PathBuf>,
+    pub ticks: Option<u64>,
+    pub save: Option<PathBuf>,
+    pub help: bool,
+}
+
+impl Args {
+    // Parses the arguments following the program name, the error describes the offending one
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().peekable();
+        if args.next_if(|arg| arg == "bench").is_some() {
+            parsed.bench = true;
+        } else if let Some(arg) = args.next_if(|arg| arg == "sweep") {
+            parsed.sweep = Some(value(&arg, &mut args)?);
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => parsed.config = Some(value(&arg, &mut args)?),
+                "--load" => parsed.load = Some(value(&arg, &mut args)?),
+                "--layout" => parsed.layout = Some(value(&arg, &mut args)?),
+                "--width" => parsed.width = Some(value::<NonZeroU32>(&arg, &mut args)?.get()),
+                "--height" => parsed.height = Some(value::<NonZeroU32>(&arg, &mut args)?.get()),
+                "--particles" => parsed.particles = Some(value(&arg, &mut args)?),
+                "--seed" => parsed.seed = Some(value(&arg, &mut args)?),
+                "--all-pairs" => parsed.all_pairs = true,
+                "--fps" => parsed.fps = Some(value(&arg, &mut args)?),
+                "--no-vsync" => parsed.no_vsync = true,
+                "--uncapped" => parsed.uncapped = true,
+                "--trail-decay" => {
+                    let decay: f32 = value(&arg, &mut args)?;
+                    if !(0.0..1.0).contains(&decay) {
+                        return Err(format!("{arg} must be at least 0 and below 1"));
+                    }
+                    parsed.trail_decay = Some(decay);
+                }
+                "--velocity-scale" => {
+                    let scale: f32 = value(&arg, &mut args)?;
+                    if !(scale > 0.0 && scale.is_finite()) {
+                        return Err(format!("{arg} must be positive"));
+                    }
+                    parsed.velocity_scale = Some(scale);
+                }
+                "--shape" => {
+                    let shape: String = value(&arg, &mut args)?;
+                    parsed.shape = Some(match shape.as_str() {
+                        "circle" => ParticleShape::Circle,
+                        "square" => ParticleShape::Square,
+                        "point" => ParticleShape::Point,
+                        _ => return Err(format!("invalid value `{shape}` for {arg}")),
+                    });
+                }
+                "--density" => parsed.density = true,
+                "--background" => {
+                    let color: String = value(&arg, &mut args)?;
+                    parsed.background = Some(
+                        parse_color(&color)
+                            .ok_or_else(|| format!("invalid value `{color}` for {arg}"))?,
+                    );
+                }
+                "--max-runtime" => {
+                    let secs: f32 = value(&arg, &mut args)?;
+                    let max_runtime = Duration::try_from_secs_f32(secs)
+                        .map_err(|error| format!("invalid value for {arg}: {error}"))?;
+                    parsed.max_runtime = Some(max_runtime);
+                }
+                "--pause-unfocused" => parsed.pause_unfocused = true,
+                "--stats-csv" => parsed.stats_csv = Some(value(&arg, &mut args)?),
+                "--record-gif" => parsed.record_gif = Some(value(&arg, &mut args)?),
+                "--frames" => parsed.frames = Some(value::<NonZeroU32>(&arg, &mut args)?.get()),
+                "--record-log" => parsed.record_log = Some(value(&arg, &mut args)?),
+                "--replay" => parsed.replay = Some(value(&arg, &mut args)?),
+                "--autosave-interval" => {
+                    parsed.autosave_interval = Some(value::<NonZeroU64>(&arg, &mut args)?.get())
+                }
+                "--autosave-dir" => parsed.autosave_dir = Some(value(&arg, &mut args)?),
+                "--autosave-keep" => {
+                    parsed.autosave_keep = Some(value::<NonZeroUsize>(&arg, &mut args)?.get())
+                }
+                "--profile" => parsed.profile = true,
+                "--energy-check" => {
+                    let percent: f32 = value(&arg, &mut args)?;
+                    if percent.is_nan() || percent < 0.0 {
+                        return Err(format!("{arg} must not be negative"));
+                    }
+                    parsed.energy_check = Some(percent);
+                }
+                "--headless" => parsed.headless = true,
+                "--ticks" => parsed.ticks = Some(value(&arg, &mut args)?),
+                "--save" => parsed.save = Some(value(&arg, &mut args)?),
+                "--summary-csv" => parsed.summary_csv = Some(value(&arg, &mut args)?),
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown argument `{arg}`")),
+            }
+        }
+        if parsed.load.is_some() && parsed.layout.is_some() {
+            return Err("--load and --layout both provide the world, pick one".into());
+        }
+        if parsed.replay.is_some() && (parsed.load.is_some() || parsed.layout.is_some()) {
+            return Err(
+                "--replay brings its own world, it can't be used with --load or --layout".into(),
+            );
+        }
+        if parsed.autosave_interval.is_none()
+            && (parsed.autosave_dir.is_some() || parsed.autosave_keep.is_some())
+        {
+            return Err("--autosave-dir and --autosave-keep need --autosave-interval".into());
+        }
+        if parsed.uncapped && parsed.fps.is_some() {
+            return Err("--uncapped renders without a frame rate cap, drop --fps".into());
+        }
+        if parsed.replay.is_some() && parsed.record_log.is_some() {
+            return Err("--replay and --record-log can't be combined".into());
+        }
+        if parsed.record_gif.is_some() && (parsed.headless || parsed.bench) {
+            return Err(
+                "--record-gif needs rendered frames, it can't be used with --headless or bench"
+                    .into(),
+            );
+        }
+        // Every run of a sweep is configured by its file alone
+        if parsed.sweep.is_some() {
+            let sweep_only = Args {
+                sweep: parsed.sweep.clone(),
+                ticks: parsed.ticks,
+                summary_csv: parsed.summary_csv.clone(),
+                help: parsed.help,
+                ..Args::default()
+            };
+            if parsed != sweep_only {
+                return Err("sweep only takes --ticks and --summary-csv".into());
+            }
+        } else if parsed.summary_csv.is_some() {
+            return Err("--summary-csv is only written by sweep".into());
+        }
+        Ok(parsed)
+    }
+}
+
+// Parses the value following the flag `name`
+fn value<T>(name: &str, args: &mut impl Iterator<Item = String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = args
+        .next()
+        .ok_or_else(|| format!("missing value for {name}"))?;
+    value
+        .parse()
+        .map_err(|error| format!("invalid value `{value}` for {name}: {error}"))
+}
+
+// RRGGBB or RRGGBBAA in hex, opaque unless alpha is given
+fn parse_color(color: &str) -> Option<[u8; 4]> {
+    if !matches!(color.len(), 6 | 8) || !color.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut rgba = [255; 4];
+    for (channel, digits) in rgba.iter_mut().zip(color.as_bytes().chunks(2)) {
+        *channel = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+    Some(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_flags_and_rejects_invalid_values() {
+        let args = parse(&[
+            "--width",
+            "800",
+            "--seed",
+            "42",
+            "--all-pairs",
+            "--no-vsync",
+            "--density",
+            "--pause-unfocused",
+            "--max-runtime",
+            "1.5",
+        ])
+        .unwrap();
+        assert_eq!(
+            args,
+            Args {
+                width: Some(800),
+                seed: Some(42),
+                all_pairs: true,
+                no_vsync: true,
+                density: true,
+                pause_unfocused: true,
+                max_runtime: Some(Duration::from_millis(1500)),
+                ..Args::default()
+            }
+        );
+
+        assert!(parse(&["--width", "0"]).is_err());
+        assert!(parse(&["--fps"]).is_err());
+        assert_eq!(parse(&["--fps", "0"]).unwrap().fps, Some(0));
+        assert!(parse(&["--uncapped", "--no-vsync"]).unwrap().uncapped);
+        assert!(parse(&["--uncapped", "--fps", "30"]).is_err());
+        assert!(parse(&["--particles", "many"]).is_err());
+        assert!(parse(&["--max-runtime", "-1"]).is_err());
+        assert!(parse(&["--trail-decay", "1"]).is_err());
+        assert!(parse(&["--velocity-scale", "0"]).is_err());
+        assert_eq!(
+            parse(&["--shape", "point"]).unwrap().shape,
+            Some(ParticleShape::Point)
+        );
+        assert!(parse(&["--shape", "star"]).is_err());
+        assert!(parse(&["--energy-check", "-1"]).is_err());
+        assert!(parse(&["--load", "a.ron", "--layout", "b.csv"]).is_err());
+        assert!(parse(&["--replay", "a.log", "--load", "b.ron"]).is_err());
+        assert!(parse(&["--replay", "a.log", "--record-log", "b.log"]).is_err());
+        assert_eq!(
+            parse(&["--replay", "a.log", "--headless"]).unwrap().replay,
+            Some(PathBuf::from("a.log"))
+        );
+        assert!(parse(&["--headless", "--record-gif", "run.gif"]).is_err());
+        assert!(parse(&["--colour"]).is_err());
+        assert_eq!(
+            parse(&["--background", "1a2B3c"]).unwrap().background,
+            Some([0x1a, 0x2b, 0x3c, 255])
+        );
+        assert!(parse(&["--background", "1a2b3"]).is_err());
+        assert!(parse(&["--background", "+1a2b3c4"]).is_err());
+
+        let args = parse(&["bench", "--particles", "500", "--ticks", "100"]).unwrap();
+        assert!(args.bench && !args.headless);
+        assert_eq!((args.particles, args.ticks), (Some(500), Some(100)));
+        assert!(parse(&["--ticks", "100", "bench"]).is_err());
+        assert!(parse(&["bench", "--record-gif", "run.gif"]).is_err());
+        let args = parse(&["sweep", "configs", "--ticks", "500"]).unwrap();
+        assert_eq!(
+            (args.sweep, args.ticks),
+            (Some(PathBuf::from("configs")), Some(500))
+        );
+        assert!(parse(&["sweep"]).is_err());
+        assert!(parse(&["sweep", "configs", "--seed", "1"]).is_err());
+        assert!(parse(&["--summary-csv", "out.csv"]).is_err());
+        assert_eq!(
+            parse(&["--autosave-interval", "600"])
+                .unwrap()
+                .autosave_interval,
+            Some(600)
+        );
+        assert!(parse(&["--autosave-interval", "0"]).is_err());
+        assert!(parse(&["--autosave-keep", "5"]).is_err());
+    }
+}
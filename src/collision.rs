@@ -0,0 +1,207 @@
+use crate::simulation::{Particle, Rect};
+use crate::spatial::GridLayout;
+
+// Below this the centres count as coincident and the contact normal can't be derived from them
+const COINCIDENT_DISTANCE: f32 = 1e-6;
+
+// Finds the pairs of particles narrowphase has to resolve
+pub trait Broadphase {
+    // Fills `pairs` with every index pair `(i, j)`, `i < j`, that `overlapping` right now, in
+    // ascending order. Pairs only touching once earlier ones are resolved wait for the next step,
+    // so no implementation can find more than another.
+    fn candidate_pairs(&mut self, particles: &[Particle], pairs: &mut Vec<(usize, usize)>);
+
+    // Cells the particles were sorted into on the last call, `None` without a grid
+    fn layout(&self) -> Option<GridLayout> {
+        None
+    }
+}
+
+// Checks every pair, quadratic but trivially complete. The reference the grid is validated against.
+#[derive(Default)]
+pub struct AllPairs {
+    // World wrapping around at its edges, pairs then also touch across them
+    wrap: Option<Rect>,
+}
+
+impl AllPairs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn periodic(bounds: Rect) -> Self {
+        Self { wrap: Some(bounds) }
+    }
+}
+
+impl Broadphase for AllPairs {
+    fn candidate_pairs(&mut self, particles: &[Particle], pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        for i in 0..particles.len() {
+            pairs.extend(
+                (i + 1..particles.len())
+                    .filter(|&j| overlapping(&particles[i], &particles[j], self.wrap.as_ref()))
+                    .map(|j| (i, j)),
+            );
+        }
+    }
+}
+
+// Whether two particles overlap, measured to the closest image of `b` in a world wrapping at
+// `wrap`
+pub fn overlapping(a: &Particle, b: &Particle, wrap: Option<&Rect>) -> bool {
+    let mut delta = [b.position[0] - a.position[0], b.position[1] - a.position[1]];
+    if let Some(bounds) = wrap {
+        for (axis, delta) in delta.iter_mut().enumerate() {
+            let size = bounds.max[axis] - bounds.min[axis];
+            if *delta > size / 2.0 {
+                *delta -= size;
+            } else if *delta < -size / 2.0 {
+                *delta += size;
+            }
+        }
+    }
+    let min_distance = a.radius + b.radius;
+    delta[0] * delta[0] + delta[1] * delta[1] < min_distance * min_distance
+}
+
+// Separates two overlapping particles and exchanges momentum along the contact normal, keeping
+// `restitution` of their normal velocity relative to each other. Returns whether the pair was
+// actually in contact.
+pub fn resolve_collision(a: &mut Particle, b: &mut Particle, restitution: f32) -> bool {
+    let delta = [b.position[0] - a.position[0], b.position[1] - a.position[1]];
+    let distance_squared = delta[0] * delta[0] + delta[1] * delta[1];
+    let min_distance = a.radius + b.radius;
+    if distance_squared >= min_distance * min_distance {
+        return false;
+    }
+
+    let distance = distance_squared.sqrt();
+    let normal = if distance > COINCIDENT_DISTANCE {
+        [delta[0] / distance, delta[1] / distance]
+    } else {
+        // Coincident centres, push them apart along x so the outcome stays deterministic
+        [1.0, 0.0]
+    };
+
+    let inverse_mass_a = inverse_mass(a.mass);
+    let inverse_mass_b = inverse_mass(b.mass);
+    let inverse_mass_sum = inverse_mass_a + inverse_mass_b;
+    if inverse_mass_sum == 0.0 {
+        return true;
+    }
+
+    // Push the particles out of each other, the lighter one moves further
+    let overlap = min_distance - distance;
+    for (axis, n) in normal.iter().enumerate() {
+        a.position[axis] -= n * overlap * inverse_mass_a / inverse_mass_sum;
+        b.position[axis] += n * overlap * inverse_mass_b / inverse_mass_sum;
+    }
+
+    let relative_velocity = [b.velocity[0] - a.velocity[0], b.velocity[1] - a.velocity[1]];
+    let normal_velocity = relative_velocity[0] * normal[0] + relative_velocity[1] * normal[1];
+    // Already separating, resolving again would pull them back together
+    if normal_velocity >= 0.0 {
+        return true;
+    }
+
+    // Conserves momentum, and kinetic energy too at a restitution of 1.0
+    let impulse = -(1.0 + restitution) * normal_velocity / inverse_mass_sum;
+    for (axis, n) in normal.iter().enumerate() {
+        a.velocity[axis] -= impulse * inverse_mass_a * n;
+        b.velocity[axis] += impulse * inverse_mass_b * n;
+    }
+
+    true
+}
+
+// Mutable access to two distinct particles at once
+pub fn pair_mut(particles: &mut [Particle], i: usize, j: usize) -> (&mut Particle, &mut Particle) {
+    debug_assert!(i < j);
+    let (head, tail) = particles.split_at_mut(j);
+    (&mut head[i], &mut tail[0])
+}
+
+// Non-positive masses are treated as immovable
+fn inverse_mass(mass: f32) -> f32 {
+    if mass > 0.0 {
+        1.0 / mass
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+
+    fn particle(position: [f32; 2], velocity: [f32; 2], mass: f32) -> Particle {
+        let mut particle = Particle::new(0, 0, position, Genome::default(), 1.0);
+        (particle.velocity, particle.mass, particle.radius) = (velocity, mass, 2.0);
+        particle
+    }
+
+    // Total momentum and kinetic energy of a pair
+    fn momentum_and_energy(a: &Particle, b: &Particle) -> ([f32; 2], f32) {
+        let momentum = [0, 1].map(|axis| a.mass * a.velocity[axis] + b.mass * b.velocity[axis]);
+        let energy = [a, b]
+            .iter()
+            .map(|particle| {
+                0.5 * particle.mass * (particle.velocity[0].powi(2) + particle.velocity[1].powi(2))
+            })
+            .sum();
+        (momentum, energy)
+    }
+
+    #[test]
+    fn coincident_particles_separate_along_x() {
+        let (mut a, mut b) = (
+            particle([10.0, 5.0], [0.0, 0.0], 1.0),
+            particle([10.0, 5.0], [0.0, 0.0], 1.0),
+        );
+        assert!(resolve_collision(&mut a, &mut b, 1.0));
+        assert!(a
+            .position
+            .iter()
+            .chain(&b.position)
+            .all(|value| value.is_finite()));
+        // Pushed apart by the full overlap, half each, and the same every time
+        assert_eq!(a.position, [8.0, 5.0]);
+        assert_eq!(b.position, [12.0, 5.0]);
+    }
+
+    #[test]
+    fn impulses_conserve_momentum_and_energy() {
+        let (mut a, mut b) = (
+            particle([0.0, 0.0], [30.0, 5.0], 1.0),
+            particle([3.0, 1.0], [-10.0, 0.0], 3.0),
+        );
+        let (momentum, energy) = momentum_and_energy(&a, &b);
+
+        assert!(resolve_collision(&mut a, &mut b, 1.0));
+        // Approaching along the normal before, so the impulse turned them around
+        assert!(a.velocity[0] < 0.0 && b.velocity[0] > -10.0);
+        let (momentum_after, energy_after) = momentum_and_energy(&a, &b);
+        for axis in 0..2 {
+            assert!((momentum_after[axis] - momentum[axis]).abs() < 1e-3);
+        }
+        assert!((energy_after - energy).abs() < 1e-2, "{energy_after}");
+    }
+
+    #[test]
+    fn sticking_pairs_keep_their_momentum_but_lose_energy() {
+        let (mut a, mut b) = (
+            particle([0.0, 0.0], [30.0, 5.0], 1.0),
+            particle([3.0, 1.0], [-10.0, 0.0], 3.0),
+        );
+        let (momentum, energy) = momentum_and_energy(&a, &b);
+
+        assert!(resolve_collision(&mut a, &mut b, 0.0));
+        let (momentum_after, energy_after) = momentum_and_energy(&a, &b);
+        for axis in 0..2 {
+            assert!((momentum_after[axis] - momentum[axis]).abs() < 1e-3);
+        }
+        assert!(energy_after < energy - 1.0, "{energy_after}");
+    }
+}
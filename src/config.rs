@@ -0,0 +1,84 @@
+use crate::simulation::SimulationConfig;
+use ron::ser::PrettyConfig;
+use serde::Serialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+// Config file read when `--config` isn't given
+pub const DEFAULT_CONFIG_PATH: &str = "simulation.ron";
+
+// Reads the simulation config from the RON file at `path`. A missing file is created from the
+// defaults, so there is a template to edit next time.
+pub fn load_or_create(path: &Path) -> Result<SimulationConfig, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            parse(&contents).map_err(|error| format!("invalid config {}: {error}", path.display()))
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            let config = SimulationConfig::default();
+            // Running without a template is still better than not running at all
+            if let Err(error) = save(path, &config) {
+                eprintln!("Failed to write default config: {error}");
+            }
+            Ok(config)
+        }
+        Err(error) => Err(format!("failed to read config {}: {error}", path.display())),
+    }
+}
+
+// Same as `load_or_create` for a file that has to exist
+pub fn load(path: &Path) -> Result<SimulationConfig, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|error| format!("failed to read config {}: {error}", path.display()))?;
+    parse(&contents).map_err(|error| format!("invalid config {}: {error}", path.display()))
+}
+
+fn parse(contents: &str) -> Result<SimulationConfig, String> {
+    let config: SimulationConfig = ron::from_str(contents).map_err(|error| error.to_string())?;
+    config.validate()?;
+    Ok(config)
+}
+
+pub fn save(path: &Path, config: &SimulationConfig) -> Result<(), String> {
+    let contents = to_ron(config)?;
+    fs::write(path, contents).map_err(|error| format!("{}: {error}", path.display()))
+}
+
+// `config` the way a config file holds it
+pub fn to_ron(config: &impl Serialize) -> Result<String, String> {
+    ron::ser::to_string_pretty(config, PrettyConfig::default()).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips_and_missing_fields_keep_defaults() {
+        let config = SimulationConfig {
+            seed: 7,
+            restitution: 0.5,
+            ..SimulationConfig::default()
+        };
+        let contents = ron::ser::to_string_pretty(&config, PrettyConfig::default()).unwrap();
+        assert_eq!(
+            ron::from_str::<SimulationConfig>(&contents).unwrap(),
+            config
+        );
+
+        let partial: SimulationConfig = ron::from_str("(seed: 7, restitution: 0.5)").unwrap();
+        assert_eq!(partial, config);
+
+        let species: SimulationConfig =
+            ron::from_str("(interaction_matrix: [[0.5, -1.0], [1.0, 0.0]])").unwrap();
+        assert_eq!(species.species_count(), 2);
+        assert_eq!(species.interaction(0, 1), -1.0);
+
+        // Examples have to keep loading as the config grows
+        let fountain: SimulationConfig =
+            ron::from_str(include_str!("../examples/fountain.ron")).unwrap();
+        fountain.validate().unwrap();
+        assert_eq!(fountain.emitters.len(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::replay::Input;
+
+// Longest line the console takes, it has to fit a single row of a small window
+pub const MAX_LINE_LEN: usize = 64;
+// Most particles a single `spawn` adds, a typo shouldn't freeze the simulation
+const MAX_SPAWN: usize = 100_000;
+// Shown for `help`, in the characters the overlay font has
+pub const HELP: &str = "set <name> <values>, spawn <count>, pause, reset, help";
+
+// What a line typed into the console asks for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // Changes the world, so it goes through the input log like clicks and keys do
+    Input(Input),
+    TogglePause,
+    Help,
+}
+
+// Parses a console line such as `set gravity 0 20` or `spawn 100`, the error says what is wrong
+// with it
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Err("empty command, try help".into());
+    };
+    let arguments: Vec<_> = words.collect();
+    let command = match (command, arguments.as_slice()) {
+        ("set", [name, values @ ..]) if !values.is_empty() => {
+            let values = values
+                .iter()
+                .map(|value| {
+                    value
+                        .parse::<f32>()
+                        .map_err(|_| format!("{value} is not a number"))
+                })
+                .collect::<Result<_, _>>()?;
+            Command::Input(Input::Set {
+                name: name.to_string(),
+                values,
+            })
+        }
+        ("set", _) => return Err("usage: set <name> <values>".into()),
+        ("spawn", [count]) => match count.parse::<usize>() {
+            Ok(count) if count <= MAX_SPAWN => Command::Input(Input::SpawnRandom(count)),
+            Ok(_) => return Err(format!("spawn takes at most {MAX_SPAWN} at once")),
+            Err(_) => return Err(format!("{count} is not a particle count")),
+        },
+        ("spawn", _) => return Err("usage: spawn <count>".into()),
+        ("pause", []) => Command::TogglePause,
+        ("reset", []) => Command::Input(Input::Reset),
+        ("help", []) => Command::Help,
+        ("pause" | "reset" | "help", _) => return Err(format!("{command} takes no arguments")),
+        _ => return Err(format!("unknown command {command}, try help")),
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{CoreState, SimulationConfig};
+
+    #[test]
+    fn commands_parse_and_bad_ones_say_why() {
+        assert_eq!(
+            parse("  set gravity 0 -20.5 "),
+            Ok(Command::Input(Input::Set {
+                name: "gravity".into(),
+                values: vec![0.0, -20.5],
+            }))
+        );
+        assert_eq!(
+            parse("spawn 100"),
+            Ok(Command::Input(Input::SpawnRandom(100)))
+        );
+        assert_eq!(parse("pause"), Ok(Command::TogglePause));
+        for (line, error) in [
+            ("", "empty command"),
+            ("teleport 1 2", "unknown command teleport"),
+            ("set gravity", "usage: set"),
+            ("set gravity up", "up is not a number"),
+            ("spawn -3", "-3 is not a particle count"),
+            ("spawn 1000000", "spawn takes at most"),
+            ("reset now", "reset takes no arguments"),
+        ] {
+            let result = parse(line);
+            assert!(
+                result
+                    .as_ref()
+                    .is_err_and(|message| message.starts_with(error)),
+                "{line}: {result:?}"
+            );
+        }
+
+        // Parsed commands apply to the running world, values it can't take leave it as it was
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 10,
+            ..SimulationConfig::default()
+        });
+        Input::SpawnRandom(5).apply(&mut core_state);
+        assert_eq!(core_state.world().particles().len(), 15);
+        let [width, height] = core_state.config().bounds;
+        assert!(core_state.world().particles().iter().all(|particle| {
+            (0.0..=width).contains(&particle.position[0])
+                && (0.0..=height).contains(&particle.position[1])
+        }));
+        core_state.set_parameter("gravity", &[0.0, 20.0]).unwrap();
+        core_state.set_parameter("restitution", &[0.5]).unwrap();
+        assert_eq!(core_state.config().gravity, [0.0, 20.0]);
+        assert_eq!(core_state.config().restitution, 0.5);
+        assert!(core_state.set_parameter("gravity", &[1.0]).is_err());
+        assert!(core_state
+            .set_parameter("restitution", &[f32::NAN])
+            .is_err());
+        assert!(core_state.set_parameter("bounds", &[10.0, 10.0]).is_err());
+        assert_eq!(core_state.config().restitution, 0.5);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::rng::SimRng;
+use serde::{Deserialize, Serialize};
+
+// Line segment continuously spawning particles, e.g. along an edge of the world for flows and
+// fountains
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Emitter {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    // Particles spawned per second, at uniformly random points of the segment
+    pub rate: f32,
+    // Velocity every particle leaves with, emitters ignore `initial_velocity`
+    pub velocity: [f32; 2],
+    // Largest random change of each velocity axis, 0.0 emits them all at `velocity`
+    pub velocity_jitter: f32,
+}
+
+impl Emitter {
+    pub fn validate(&self) -> Result<(), String> {
+        let coordinates = [self.start, self.end, self.velocity].concat();
+        if !coordinates.iter().all(|value| value.is_finite()) {
+            return Err(format!(
+                "emitter from {:?} to {:?} needs finite positions and velocity",
+                self.start, self.end
+            ));
+        }
+        if !(self.rate >= 0.0 && self.rate.is_finite()) {
+            return Err(format!(
+                "emitter rate must not be negative, got {}",
+                self.rate
+            ));
+        }
+        if !(self.velocity_jitter >= 0.0 && self.velocity_jitter.is_finite()) {
+            return Err(format!(
+                "emitter velocity_jitter must not be negative, got {}",
+                self.velocity_jitter
+            ));
+        }
+        Ok(())
+    }
+
+    // Position and velocity of the next particle
+    pub fn sample(&self, rng: &mut SimRng) -> ([f32; 2], [f32; 2]) {
+        let t = rng.next_f32();
+        let position = [
+            self.start[0] + (self.end[0] - self.start[0]) * t,
+            self.start[1] + (self.end[1] - self.start[1]) * t,
+        ];
+        let jitter = self.velocity_jitter;
+        let velocity = [
+            self.velocity[0] + rng.range_f32(-jitter, jitter),
+            self.velocity[1] + rng.range_f32(-jitter, jitter),
+        ];
+        (position, velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{CoreState, SimulationConfig};
+
+    #[test]
+    fn emitters_spawn_along_their_segment_up_to_the_cap() {
+        let emitter = Emitter {
+            start: [100.0, 470.0],
+            end: [200.0, 470.0],
+            rate: 60.0,
+            velocity: [0.0, -100.0],
+            velocity_jitter: 10.0,
+        };
+        let config = SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            food_spawn_rate: 0.0,
+            max_particles: Some(50),
+            emitters: vec![emitter],
+            ..SimulationConfig::default()
+        };
+        let mut core_state = CoreState::new(config.clone());
+        // Half a second at 60 per second
+        for _ in 0..60 {
+            core_state.step(1.0 / 120.0);
+        }
+        let particles = core_state.world().particles();
+        assert_eq!(particles.len(), 30);
+        for particle in particles {
+            // Emitted on the segment moving upwards, collisions only push them around a little
+            assert!((95.0..=205.0).contains(&particle.position[0]));
+            assert!(particle.position[1] <= 470.0);
+            assert!(particle.velocity[1] < 0.0, "{:?}", particle.velocity);
+        }
+
+        // Emission pauses at the cap instead of making the culling kill older particles
+        for _ in 0..120 {
+            core_state.step(1.0 / 120.0);
+        }
+        assert_eq!(core_state.world().particles().len(), 50);
+        assert_eq!(core_state.world().particles()[0].id, 0);
+
+        // Saves carry the fractional particles owed, a resumed run emits like the original
+        let mut resumed = CoreState::decode(&core_state.encode().unwrap()).unwrap();
+        core_state.step(1.0 / 120.0);
+        resumed.step(1.0 / 120.0);
+        assert_eq!(resumed.world().particles(), core_state.world().particles());
+
+        let mut invalid = emitter;
+        invalid.rate = -1.0;
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,98 @@
+// Simulated ticks between two logged energy samples, one second at the fixed timestep
+const LOG_INTERVAL: u64 = 120;
+
+// Watches the total energy of a run for drift away from a reference. Only a closed system keeps
+// its energy, so births, deaths and spawns restart the reference instead of counting as drift.
+#[derive(Clone, Debug)]
+pub struct EnergyMonitor {
+    // Drift in percent of the reference beyond which a warning is printed
+    threshold_percent: f32,
+    // Energy and population the drift is measured against
+    reference: Option<(f64, usize)>,
+    // Warns once when crossing the threshold instead of every tick past it
+    drifting: bool,
+}
+
+impl EnergyMonitor {
+    pub fn new(threshold_percent: f32) -> Self {
+        Self {
+            threshold_percent,
+            reference: None,
+            drifting: false,
+        }
+    }
+
+    pub fn threshold_percent(&self) -> f32 {
+        self.threshold_percent
+    }
+
+    // Line worth printing about the `energy` of `particle_count` particles at `tick`, a warning
+    // whenever the drift crosses the threshold and a sample every `LOG_INTERVAL` ticks
+    pub fn observe(&mut self, tick: u64, particle_count: usize, energy: f64) -> Option<String> {
+        let reference = match self.reference {
+            Some((reference, count)) if count == particle_count => reference,
+            _ => {
+                self.reference = Some((energy, particle_count));
+                self.drifting = false;
+                energy
+            }
+        };
+        let drift = drift_percent(reference, energy);
+
+        let drifting = drift.abs() > self.threshold_percent as f64;
+        let crossed = drifting && !self.drifting;
+        self.drifting = drifting;
+        if crossed {
+            Some(format!(
+                "Warning: energy drifted {drift:+.2}% at tick {tick} ({energy:.3} vs {reference:.3}), \
+                 more than the {}% allowed",
+                self.threshold_percent
+            ))
+        } else if tick.is_multiple_of(LOG_INTERVAL) {
+            Some(format!(
+                "Energy at tick {tick}: {energy:.3} (drift {drift:+.3}%)"
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+// Relative change from `reference` in percent, measured against its magnitude so negative
+// totals (bound by gravity) drift in the intuitive direction
+fn drift_percent(reference: f64, energy: f64) -> f64 {
+    if reference == 0.0 {
+        return if energy == 0.0 { 0.0 } else { f64::INFINITY };
+    }
+    (energy - reference) / reference.abs() * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_once_per_crossing_and_rebases_on_population_changes() {
+        let mut monitor = EnergyMonitor::new(1.0);
+        assert!(monitor.observe(0, 10, 100.0).unwrap().starts_with("Energy"));
+        assert_eq!(monitor.observe(1, 10, 100.5), None);
+
+        let warning = monitor.observe(2, 10, 98.0).unwrap();
+        assert!(
+            warning.starts_with("Warning") && warning.contains("-2.00%"),
+            "{warning}"
+        );
+        assert_eq!(monitor.observe(3, 10, 97.0), None);
+        // Back within bounds re-arms the warning
+        assert_eq!(monitor.observe(4, 10, 100.0), None);
+        assert!(monitor.observe(5, 10, 102.0).is_some());
+
+        // A birth starts over from the new total
+        assert_eq!(monitor.observe(6, 11, 150.0), None);
+        assert_eq!(monitor.observe(7, 11, 150.5), None);
+        assert!(monitor
+            .observe(LOG_INTERVAL, 11, 150.0)
+            .unwrap()
+            .contains("+0.000%"));
+    }
+}
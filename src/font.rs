@@ -0,0 +1,120 @@
+// Glyphs are 8x8 pixels, one byte per row with the least significant bit on the left
+pub const GLYPH_SIZE: u32 = 8;
+
+// Glyph for `character`, lowercase letters share the uppercase ones and unknown characters are
+// blank. Bitmaps follow the public domain font8x8 by Daniel Hepper.
+fn glyph(character: char) -> [u8; 8] {
+    match character.to_ascii_uppercase() {
+        '0' => [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00],
+        '1' => [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00],
+        '2' => [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00],
+        '3' => [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00],
+        '4' => [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00],
+        '5' => [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00],
+        '6' => [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00],
+        '7' => [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00],
+        '8' => [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00],
+        '9' => [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00],
+        'A' => [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00],
+        'B' => [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00],
+        'C' => [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00],
+        'D' => [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00],
+        'E' => [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00],
+        'F' => [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00],
+        'G' => [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00],
+        'H' => [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00],
+        'I' => [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+        'J' => [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00],
+        'K' => [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00],
+        'L' => [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00],
+        'M' => [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00],
+        'N' => [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00],
+        'O' => [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00],
+        'P' => [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00],
+        'Q' => [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00],
+        'R' => [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00],
+        'S' => [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00],
+        'T' => [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],
+        'U' => [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00],
+        'V' => [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00],
+        'W' => [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00],
+        'X' => [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00],
+        'Y' => [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00],
+        'Z' => [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00],
+        '=' => [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00],
+        '/' => [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00],
+        '(' => [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00],
+        ')' => [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00],
+        '[' => [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00],
+        ']' => [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00],
+        '<' => [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00],
+        '>' => [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00],
+        '%' => [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00],
+        '#' => [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00],
+        '!' => [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00],
+        '?' => [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF],
+        '—' => [0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x00; 8],
+    }
+}
+
+// Width in pixels of `text` drawn in a single line
+pub fn text_width(text: &str) -> u32 {
+    text.chars().count() as u32 * GLYPH_SIZE
+}
+
+// Draws `text` with its top left corner at `origin`, clipped to the frame
+pub fn draw_text(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    origin: [i64; 2],
+    text: &str,
+    color: [u8; 4],
+) {
+    for (index, character) in text.chars().enumerate() {
+        let glyph_x = origin[0] + index as i64 * GLYPH_SIZE as i64;
+        for (row, bits) in glyph(character).into_iter().enumerate() {
+            let y = origin[1] + row as i64;
+            if y < 0 || y >= height as i64 {
+                continue;
+            }
+            for column in 0..GLYPH_SIZE as i64 {
+                let x = glyph_x + column;
+                if bits & (1 << column) == 0 || x < 0 || x >= width as i64 {
+                    continue;
+                }
+                let offset = (y as usize * width as usize + x as usize) * 4;
+                frame[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_glyph_pixels_and_clips_at_the_edges() {
+        let (width, height) = (12, 8);
+        let mut frame = vec![0; width * height * 4];
+        draw_text(&mut frame, 12, 8, [0, 0], "1", [255; 4]);
+        draw_text(&mut frame, 12, 8, [10, -4], "88", [255; 4]);
+
+        let lit = |x: usize, y: usize| frame[(y * width + x) * 4] == 255;
+        // Top row of the '1' is 0x0C, pixels 2 and 3
+        assert!(!lit(1, 0) && lit(2, 0) && lit(3, 0) && !lit(4, 0));
+        // Bottom row of the '1' is blank
+        assert!((0..8).all(|x| !lit(x, 7)));
+        // The clipped '8' only shows its lower half (rows 0x33, 0x33, 0x1E, blank) in the two
+        // rightmost columns
+        assert!(lit(10, 0) && lit(11, 0) && !lit(10, 2) && lit(11, 2) && !lit(11, 3));
+    }
+}
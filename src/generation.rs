@@ -0,0 +1,105 @@
+use crate::simulation::Particle;
+use serde::{Deserialize, Serialize};
+
+// Counts generations by population turnover. Generation 0 is the population a run starts with.
+// A generation ends on the tick the last particle alive at its start dies, everyone alive then
+// was born during it and makes up the next one. Particles spawned by hand count as born into
+// the generation they appear in, an extinct population stays in its generation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenerationTracker {
+    number: u64,
+    // Particles from this id on were born during the current generation, ids only ever grow
+    first_newborn_id: u64,
+    // Population mean energy summed over the ticks of the current generation
+    energy_sum: f64,
+    ticks: u64,
+    // Average over the whole of the last generation that ended
+    previous_mean_energy: Option<f32>,
+}
+
+impl GenerationTracker {
+    // Starts generation 0 with the particles whose ids are below `next_id`
+    pub fn new(next_id: u64) -> Self {
+        Self {
+            number: 0,
+            first_newborn_id: next_id,
+            energy_sum: 0.0,
+            ticks: 0,
+            previous_mean_energy: None,
+        }
+    }
+
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    // Per tick mean energy averaged over the current generation so far, `None` before its first
+    // tick
+    pub fn mean_energy(&self) -> Option<f32> {
+        (self.ticks > 0).then(|| (self.energy_sum / self.ticks as f64) as f32)
+    }
+
+    pub fn previous_mean_energy(&self) -> Option<f32> {
+        self.previous_mean_energy
+    }
+
+    // Takes the population at the end of a tick, in id order, and the id the next birth gets
+    pub fn observe(&mut self, particles: &[Particle], next_id: u64) {
+        let Some(oldest) = particles.first() else {
+            return;
+        };
+        if oldest.id >= self.first_newborn_id {
+            self.previous_mean_energy = self.mean_energy();
+            self.number += 1;
+            self.first_newborn_id = next_id;
+            self.energy_sum = 0.0;
+            self.ticks = 0;
+        }
+
+        let energy: f64 = particles
+            .iter()
+            .map(|particle| particle.energy as f64)
+            .sum();
+        self.energy_sum += energy / particles.len() as f64;
+        self.ticks += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+
+    fn population(ids_and_energies: &[(u64, f32)]) -> Vec<Particle> {
+        ids_and_energies
+            .iter()
+            .map(|&(id, energy)| Particle::new(id, 0, [0.0, 0.0], Genome::default(), energy))
+            .collect()
+    }
+
+    #[test]
+    fn generation_ends_once_its_founders_are_gone() {
+        let mut tracker = GenerationTracker::new(3);
+        tracker.observe(&population(&[(0, 10.0), (1, 20.0), (2, 30.0)]), 3);
+        // Births don't end it, they make up the next one
+        tracker.observe(&population(&[(1, 40.0), (2, 40.0), (3, 10.0)]), 4);
+        tracker.observe(&population(&[(2, 55.0), (3, 10.0), (4, 10.0)]), 5);
+        assert_eq!(tracker.number(), 0);
+        assert_eq!(tracker.mean_energy(), Some(25.0));
+        assert_eq!(tracker.previous_mean_energy(), None);
+
+        // Particle 2 was the last founder, everyone alive now founds generation 1
+        tracker.observe(&population(&[(3, 10.0), (4, 20.0), (5, 30.0)]), 6);
+        assert_eq!(tracker.number(), 1);
+        assert_eq!(tracker.mean_energy(), Some(20.0));
+        assert_eq!(tracker.previous_mean_energy(), Some(25.0));
+        tracker.observe(&population(&[(5, 30.0)]), 6);
+        assert_eq!(tracker.number(), 1);
+        tracker.observe(&population(&[(6, 30.0)]), 7);
+        assert_eq!(tracker.number(), 2);
+
+        // Extinction leaves everything as it was
+        tracker.observe(&[], 7);
+        assert_eq!((tracker.number(), tracker.mean_energy()), (2, Some(30.0)));
+    }
+}
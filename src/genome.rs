@@ -0,0 +1,59 @@
+use crate::rng::SimRng;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+const SPEED_FACTOR_RANGE: RangeInclusive<f32> = 0.1..=3.0;
+const SIZE_FACTOR_RANGE: RangeInclusive<f32> = 0.25..=3.0;
+
+// Standard deviation of the noise added to a trait when it mutates
+const SPEED_FACTOR_SIGMA: f32 = 0.1;
+const SIZE_FACTOR_SIGMA: f32 = 0.1;
+const COLOR_HUE_SIGMA: f32 = 15.0;
+
+// Heritable traits of a particle
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    // Multiplier on the maximum speed a particle can reach
+    pub speed_factor: f32,
+    // Multiplier on the particle radius
+    pub size_factor: f32,
+    // Hue in degrees, purely cosmetic for now
+    pub color_hue: f32,
+}
+
+impl Genome {
+    pub fn random(rng: &mut SimRng) -> Self {
+        Self {
+            speed_factor: rng.range_f32(0.5, 1.5),
+            size_factor: rng.range_f32(0.5, 1.5),
+            color_hue: rng.range_f32(0.0, 360.0),
+        }
+    }
+
+    // Perturbs every trait with gaussian noise, each with probability `rate`
+    pub fn mutate(&mut self, rng: &mut SimRng, rate: f32) {
+        if rng.next_f32() < rate {
+            self.speed_factor = (self.speed_factor + rng.next_gaussian() * SPEED_FACTOR_SIGMA)
+                .clamp(*SPEED_FACTOR_RANGE.start(), *SPEED_FACTOR_RANGE.end());
+        }
+        if rng.next_f32() < rate {
+            self.size_factor = (self.size_factor + rng.next_gaussian() * SIZE_FACTOR_SIGMA)
+                .clamp(*SIZE_FACTOR_RANGE.start(), *SIZE_FACTOR_RANGE.end());
+        }
+        if rng.next_f32() < rate {
+            // Hue lives on a circle, so wrap instead of clamping
+            self.color_hue =
+                (self.color_hue + rng.next_gaussian() * COLOR_HUE_SIGMA).rem_euclid(360.0);
+        }
+    }
+}
+
+impl Default for Genome {
+    fn default() -> Self {
+        Self {
+            speed_factor: 1.0,
+            size_factor: 1.0,
+            color_hue: 0.0,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::simulation::SimulationConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// One particle of a hand made starting arrangement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayoutParticle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub species: u8,
+}
+
+// Reads a layout from a CSV file with one particle per line as `x,y[,vx,vy[,species]]`.
+// Velocity and species default to zero, blank lines and lines starting with `#` are skipped.
+// Every particle has to start within the bounds of `config` and belong to one of its species.
+pub fn load(path: &Path, config: &SimulationConfig) -> Result<Vec<LayoutParticle>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    parse(&contents, config).map_err(|error| format!("{}:{error}", path.display()))
+}
+
+// Same as `load` for the contents of a file, errors start with the offending line number
+pub fn parse(contents: &str, config: &SimulationConfig) -> Result<Vec<LayoutParticle>, String> {
+    let mut layout = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let particle =
+            parse_row(line, config).map_err(|error| format!("{}: {error}", index + 1))?;
+        layout.push(particle);
+    }
+    Ok(layout)
+}
+
+fn parse_row(line: &str, config: &SimulationConfig) -> Result<LayoutParticle, String> {
+    let fields: Vec<_> = line.split(',').map(str::trim).collect();
+    if !matches!(fields.len(), 2 | 4 | 5) {
+        return Err(format!(
+            "expected x,y[,vx,vy[,species]] but found {} fields in `{line}`",
+            fields.len()
+        ));
+    }
+    let number = |index: usize| -> Result<f32, String> {
+        let Some(&field) = fields.get(index) else {
+            return Ok(0.0);
+        };
+        match field.parse::<f32>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(format!("`{field}` is not a finite number")),
+        }
+    };
+    let position = [number(0)?, number(1)?];
+    let velocity = [number(2)?, number(3)?];
+    let species = match fields.get(4) {
+        Some(field) => field
+            .parse::<u8>()
+            .map_err(|_| format!("`{field}` is not a species number"))?,
+        None => 0,
+    };
+
+    let [width, height] = config.bounds;
+    if !(0.0..=width).contains(&position[0]) || !(0.0..=height).contains(&position[1]) {
+        return Err(format!(
+            "position ({}, {}) is outside of the {width}x{height} world",
+            position[0], position[1]
+        ));
+    }
+    if species as usize >= config.species_count() {
+        return Err(format!(
+            "species {species} doesn't exist, the config has {}",
+            config.species_count()
+        ));
+    }
+
+    Ok(LayoutParticle {
+        position,
+        velocity,
+        species,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::CoreState;
+
+    #[test]
+    fn parses_rows_and_names_the_bad_one() {
+        let config = SimulationConfig {
+            interaction_matrix: vec![vec![0.0; 2]; 2],
+            ..SimulationConfig::default()
+        };
+        let contents = "# two clusters heading for each other\n\
+                        100, 240, 50, 0\n\
+                        \n\
+                        540,240,-50,0,1\n\
+                        320,0\n";
+        let layout = parse(contents, &config).unwrap();
+        assert_eq!(
+            layout,
+            [
+                LayoutParticle {
+                    position: [100.0, 240.0],
+                    velocity: [50.0, 0.0],
+                    species: 0,
+                },
+                LayoutParticle {
+                    position: [540.0, 240.0],
+                    velocity: [-50.0, 0.0],
+                    species: 1,
+                },
+                LayoutParticle {
+                    position: [320.0, 0.0],
+                    velocity: [0.0, 0.0],
+                    species: 0,
+                },
+            ]
+        );
+
+        let error = parse("1,1\n700,10\n", &config).unwrap_err();
+        assert!(error.starts_with("2: position (700, 10)"), "{error}");
+        let error = parse("1,1,0,0,2", &config).unwrap_err();
+        assert!(error.starts_with("1: species 2"), "{error}");
+        assert!(parse("1,1,0", &config).is_err());
+        assert!(parse("1,NaN", &config).is_err());
+
+        // Resets come back to the layout instead of a random world
+        let mut core_state = CoreState::from_layout(config, layout.clone());
+        core_state.step(1.0 / 120.0);
+        core_state.reset();
+        let particles = core_state.world().particles();
+        assert_eq!(particles.len(), 3);
+        for (particle, row) in particles.iter().zip(&layout) {
+            assert_eq!(
+                (particle.position, particle.velocity, particle.species),
+                (row.position, row.velocity, row.species)
+            );
+        }
+    }
+}
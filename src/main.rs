@@ -1,53 +1,554 @@
-use crossbeam_channel::{bounded, Receiver, Sender};
+mod autosave;
+mod bench;
+mod camera;
+mod cli;
+mod collision;
+mod config;
+mod console;
+mod emitter;
+mod energy;
+mod font;
+mod generation;
+mod genome;
+mod layout;
+mod nutrients;
+mod obstacle;
+mod pacing;
+mod profiler;
+mod quadtree;
+mod recorder;
+mod render;
+mod replay;
+mod rng;
+mod screenshot;
+mod simulation;
+// Alternative particle layout for throughput work, `CoreState` still steps `Vec<Particle>`
+#[allow(dead_code)]
+mod soa;
+mod spatial;
+mod stats;
+mod sweep;
+
+use autosave::Autosaver;
+use bench::BenchReport;
+use camera::Camera;
+use cli::Args;
+use console::Command;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use pacing::FrameLimiter;
 use parking_lot::Mutex;
-use pixels::{Error, Pixels, SurfaceTexture};
+use pixels::wgpu::{self, RequestAdapterOptions};
+use pixels::{Error, PixelsBuilder, SurfaceTexture};
+use recorder::GifRecorder;
+use render::{ParticleShape, RenderConfig, RenderMode, WinitRenderer, DEFAULT_FPS};
+use replay::{Input, InputLog, InputRecorder, Replay};
+use simulation::{Brush, CollisionDetection, CoreState, Particle, Rect, SimulationConfig};
+use stats::StatsWriter;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use winit::event::{Event, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{Fullscreen, WindowBuilder};
+
+// Physics always advances in increments of this many seconds
+const FIXED_TIMESTEP: f32 = 1.0 / 120.0;
+// Upper bound on physics steps per loop iteration at 1x speed, so a hitch can't snowball into a
+// spiral of death. Faster speed multipliers get proportionally more.
+const MAX_CATCH_UP_STEPS: u32 = 5;
+// Spawn brush a left drag starts out with, in world units and particles per second and unit of
+// area. Every wheel notch while painting scales the radius, or the density with Ctrl held.
+const DEFAULT_BRUSH_RADIUS: f32 = 20.0;
+const DEFAULT_BRUSH_DENSITY: f32 = 0.02;
+const BRUSH_STEP: f32 = 1.25;
+const BRUSH_RADIUS_RANGE: (f32, f32) = (2.0, 500.0);
+const BRUSH_DENSITY_RANGE: (f32, f32) = (0.001, 1.0);
+// Falling behind is reported at most this often, with the simulated time dropped since
+const LAG_WARNING_INTERVAL: Duration = Duration::from_secs(1);
+// Zoom factor applied per mouse wheel notch
+const ZOOM_STEP: f32 = 1.1;
+// Held WASD keys pan this many pixels per second, Q and E zoom by this factor per second. Both
+// are relative to the screen, so the world moves past as fast at any zoom.
+const KEY_PAN_SPEED: f32 = 600.0;
+const KEY_ZOOM_RATE: f32 = 2.0;
+// Longest frame keyboard movement is applied over, a hitch shouldn't throw the camera far off
+const MAX_KEY_MOVE_TIME: f32 = 0.1;
+// Touchpads report pixels instead of wheel notches, this many make up one notch
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+// Pixels a shift drag has to cover to select a region, anything shorter is a click inspecting
+const DRAG_THRESHOLD: f32 = 4.0;
+// Exit code when there is no display or graphics adapter to open a window with, sysexits'
+// EX_UNAVAILABLE. Scripts can tell it apart from the 1 of bad arguments and fall back to
+// `--headless`.
+const EXIT_NO_WINDOW: i32 = 69;
+// Exit code when the simulation thread panicked, the same a panic on the main thread exits with
+const EXIT_SIMULATION_PANIC: i32 = 101;
+// Where F5 saves the current state, resume it with `--load`
+const CHECKPOINT_PATH: &str = "checkpoint.ron";
+// Frames recorded by `--record-gif` without `--frames`, five seconds at 60 fps
+const DEFAULT_GIF_FRAMES: u32 = 300;
 
 // Communication Channels and Shared State
 struct SharedState {
-    draw_request: Arc<Mutex<Option<Vec<u8>>>>,
-    events: Arc<Mutex<Vec<WindowEvent>>>,
+    // Written by the main thread on mouse input, read by the simulation thread for rasterizing
+    camera: Arc<Mutex<Camera>>,
+}
+
+// Mouse, keyboard modifier and window state tracked across window events
+#[derive(Default)]
+struct InputState {
+    cursor_position: [f32; 2],
+    panning: bool,
+    gravity_well: bool,
+    // Shift turns a left click from spawning into inspecting, and a drag into selecting
+    shift: bool,
+    control: bool,
+    // Left button held down, particles are painted under the cursor
+    painting: bool,
+    brush_radius: f32,
+    brush_density: f32,
+    // Set when toggling fullscreen, the next resize fits the world into the new window
+    fit_on_resize: bool,
+    // Camera keys held down, W A S D Q E in that order
+    camera_keys: [bool; 6],
+    // When held camera keys last moved the camera
+    last_camera_move: Option<Instant>,
+    // Line typed into the console, `None` while it is closed
+    console: Option<String>,
+    // Screen position a shift drag started at, while the button is held
+    drag_start: Option<[f32; 2]>,
+    // Area the last shift drag selected in world coordinates, delete removes its particles
+    region: Option<Rect>,
+}
+
+impl InputState {
+    fn brush(&self, center: [f32; 2]) -> Brush {
+        Brush {
+            center,
+            radius: self.brush_radius,
+            density: self.brush_density,
+        }
+    }
+
+    // Types keyboard input into the console while it is open, `/` opens it and escape closes
+    // it. Returns whether the event went to the console, those never reach the key bindings.
+    fn type_into_console(
+        &mut self,
+        event: &WindowEvent,
+        main_to_sim_tx: &Sender<MainToSimulationMessage>,
+    ) -> bool {
+        let Some(line) = &mut self.console else {
+            if let WindowEvent::ReceivedCharacter('/') = event {
+                self.console = Some(String::new());
+                // Keys held while opening it would never see their release
+                self.camera_keys = [false; 6];
+                let _ =
+                    main_to_sim_tx.send(MainToSimulationMessage::ConsoleInput(Some(String::new())));
+                return true;
+            }
+            return false;
+        };
+        match event {
+            // Control characters arrive as keys below, on some platforms as characters too
+            WindowEvent::ReceivedCharacter(character)
+                if !character.is_control() && line.len() < console::MAX_LINE_LEN =>
+            {
+                line.push(*character);
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Back => {
+                    line.pop();
+                }
+                // Stays open for the next command, the outcome shows above the input
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                    let command = std::mem::take(line);
+                    let _ = main_to_sim_tx.send(MainToSimulationMessage::Command(command));
+                }
+                VirtualKeyCode::Escape => self.console = None,
+                _ => return true,
+            },
+            WindowEvent::ReceivedCharacter(_) | WindowEvent::KeyboardInput { .. } => return true,
+            _ => return false,
+        }
+        let _ = main_to_sim_tx.send(MainToSimulationMessage::ConsoleInput(self.console.clone()));
+        true
+    }
+
+    // Tracks camera keys, returns whether `key` is one of them
+    fn hold_camera_key(&mut self, key: VirtualKeyCode, pressed: bool) -> bool {
+        let index = match key {
+            VirtualKeyCode::W => 0,
+            VirtualKeyCode::A => 1,
+            VirtualKeyCode::S => 2,
+            VirtualKeyCode::D => 3,
+            VirtualKeyCode::Q => 4,
+            VirtualKeyCode::E => 5,
+            _ => return false,
+        };
+        self.camera_keys[index] = pressed;
+        true
+    }
+
+    // Moves the camera by the keys held since the last call, scaled by the time in between so
+    // holding a key glides at the same speed whatever the frame rate
+    fn move_camera(&mut self, camera: &Mutex<Camera>, screen_size: [f32; 2]) {
+        let now = Instant::now();
+        let elapsed = self.last_camera_move.map_or(0.0, |last| {
+            (now - last).as_secs_f32().min(MAX_KEY_MOVE_TIME)
+        });
+        self.last_camera_move = Some(now);
+
+        let axis = |negative: usize, positive: usize| {
+            self.camera_keys[positive] as i32 as f32 - self.camera_keys[negative] as i32 as f32
+        };
+        let [w, a, s, d, q, e] = [0, 1, 2, 3, 4, 5];
+        let pan = [axis(a, d), axis(w, s)];
+        let zoom = axis(q, e);
+        if pan == [0.0, 0.0] && zoom == 0.0 {
+            return;
+        }
+        let mut camera = camera.lock();
+        // Panning drags the world, moving the view right drags it left
+        camera.pan([
+            -pan[0] * KEY_PAN_SPEED * elapsed,
+            -pan[1] * KEY_PAN_SPEED * elapsed,
+        ]);
+        let center = [screen_size[0] / 2.0, screen_size[1] / 2.0];
+        camera.zoom_around(center, KEY_ZOOM_RATE.powf(zoom * elapsed), screen_size);
+    }
 }
 
 // Simulation Thread Message Types
 enum SimulationToMainMessage {
-    DrawRequest(Vec<u8>),
     Terminate,
 }
 
 // Main Thread Message Types
 enum MainToSimulationMessage {
-    Events(Vec<WindowEvent>),
+    Event(WindowEvent<'static>),
+    Resize { width: u32, height: u32 },
+    SpawnAt { world_pos: [f32; 2] },
+    Inspect { world_pos: [f32; 2] },
+    // Region to outline while it is selected
+    SelectRegion(Option<Rect>),
+    DeleteRegion(Rect),
+    GravityWell { active: bool, world_pos: [f32; 2] },
+    Paint { brush: Option<Brush> },
+    // Unit vector gravity should point along, in screen orientation
+    Gravity { direction: [f32; 2] },
+    // Console line typed so far, `None` once it closes
+    ConsoleInput(Option<String>),
+    // Line entered into the console
+    Command(String),
+    Terminate,
 }
 
 fn main() -> Result<(), Error> {
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) if args.help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(dir) = &args.sweep {
+        let ticks = args.ticks.unwrap_or(bench::DEFAULT_TICKS);
+        let summary_path = args
+            .summary_csv
+            .clone()
+            .unwrap_or_else(|| sweep::DEFAULT_SUMMARY_PATH.into());
+        match sweep::run(dir, ticks, FIXED_TIMESTEP, &summary_path) {
+            Ok(0) => println!("Wrote the summary to {}", summary_path.display()),
+            Ok(failed) => {
+                eprintln!(
+                    "Wrote the summary to {}, {failed} configs failed to load",
+                    summary_path.display()
+                );
+                std::process::exit(1);
+            }
+            Err(error) => {
+                eprintln!("error: {error}");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    let config_path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| config::DEFAULT_CONFIG_PATH.into());
+    // Benchmarks stay comparable across machines by ignoring a local config file
+    let mut simulation_config = if args.bench && args.config.is_none() {
+        SimulationConfig::default()
+    } else {
+        match config::load_or_create(&config_path) {
+            Ok(simulation_config) => simulation_config,
+            Err(error) => {
+                eprintln!("error: {error}");
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Command line flags override the file, the world follows the window size when one is given
+    if let Some(seed) = args.seed {
+        simulation_config.seed = seed;
+    }
+    if args.all_pairs {
+        simulation_config.collision_detection = CollisionDetection::AllPairs;
+    }
+    if let Some(particles) = args.particles {
+        simulation_config.particle_count = particles;
+    }
+    if let Some(max_runtime) = args.max_runtime {
+        simulation_config.max_runtime = Some(max_runtime);
+    }
+    if let Some(width) = args.width {
+        simulation_config.bounds[0] = width as f32;
+    }
+    if let Some(height) = args.height {
+        simulation_config.bounds[1] = height as f32;
+    }
+
+    // A save brings its own config, so the run continues exactly as it was. So does a replay.
+    let mut replay = None;
+    let mut core_state = match (&args.load, &args.layout, &args.replay) {
+        (Some(path), _, _) => match CoreState::load(path) {
+            Ok(core_state) => core_state,
+            Err(error) => {
+                eprintln!("error: failed to load save: {error}");
+                std::process::exit(1);
+            }
+        },
+        (_, Some(path), _) => match layout::load(path, &simulation_config) {
+            Ok(layout) => CoreState::from_layout(simulation_config, layout),
+            Err(error) => {
+                eprintln!("error: invalid layout {error}");
+                std::process::exit(1);
+            }
+        },
+        (_, _, Some(path)) => match Replay::load(path) {
+            Ok((core_state, log)) => {
+                replay = Some(log);
+                core_state
+            }
+            Err(error) => {
+                eprintln!("error: failed to load input log: {error}");
+                std::process::exit(1);
+            }
+        },
+        _ => CoreState::new(simulation_config),
+    };
+    core_state.set_profiling(args.profile);
+    core_state.set_energy_check(args.energy_check);
+    let bounds = core_state.config().bounds;
+
+    let input_log = match (replay, &args.record_log) {
+        (Some(replay), _) => InputLog::Replay(replay),
+        (None, Some(path)) => match InputRecorder::create(path, &core_state) {
+            Ok(recorder) => InputLog::Record(recorder),
+            Err(error) => {
+                eprintln!("error: failed to start input log: {error}");
+                std::process::exit(1);
+            }
+        },
+        (None, None) => InputLog::Off,
+    };
+
+    let stats_writer = args
+        .stats_csv
+        .as_ref()
+        .map(|path| match StatsWriter::create(path) {
+            Ok(stats_writer) => stats_writer,
+            Err(error) => {
+                eprintln!("error: failed to create {}: {error}", path.display());
+                std::process::exit(1);
+            }
+        });
+
+    let autosaver = args.autosave_interval.map(|interval| {
+        let dir = args
+            .autosave_dir
+            .clone()
+            .unwrap_or_else(|| autosave::DEFAULT_DIR.into());
+        let keep = args.autosave_keep.unwrap_or(autosave::DEFAULT_KEEP);
+        match Autosaver::start(&dir, interval, keep) {
+            Ok(autosaver) => autosaver,
+            Err(error) => {
+                eprintln!("error: failed to start autosaving: {error}");
+                std::process::exit(1);
+            }
+        }
+    });
+
+    // Never touches winit or pixels, so it runs without a display or GPU
+    if args.headless || args.bench {
+        let ticks = match args.ticks {
+            None if args.bench => Some(bench::DEFAULT_TICKS),
+            ticks => ticks,
+        };
+        run_headless(
+            core_state,
+            stats_writer,
+            autosaver,
+            input_log,
+            ticks,
+            args.save.as_deref(),
+            args.bench,
+        );
+        return Ok(());
+    }
+
+    // Without explicit flags the window shows the whole world at 1:1
+    let mut render_config = RenderConfig::builder()
+        .width(args.width.unwrap_or(bounds[0].max(1.0) as u32))
+        .height(args.height.unwrap_or(bounds[1].max(1.0) as u32))
+        // Zero frames per second lifts the cap, `--uncapped` lifts vsync along with it
+        .fps(if args.uncapped {
+            None
+        } else {
+            args.fps
+                .map_or(Some(DEFAULT_FPS), |fps| (fps > 0).then_some(fps))
+        })
+        .vsync(!args.no_vsync && !args.uncapped)
+        // The achieved frame rate is the point of running uncapped
+        .show_overlay(args.uncapped)
+        .trail_decay(args.trail_decay.unwrap_or(0.0))
+        .velocity_scale(
+            args.velocity_scale
+                .unwrap_or(RenderConfig::default().velocity_scale),
+        )
+        .render_mode(if args.density {
+            RenderMode::Density
+        } else {
+            RenderMode::Particles
+        })
+        .particle_shape(args.shape.unwrap_or(ParticleShape::Circle))
+        .background(
+            args.background
+                .unwrap_or(RenderConfig::default().background),
+        )
+        .build();
+
+    if render_config.uncapped() {
+        println!("Rendering uncapped, the simulation thread will keep a CPU core busy");
+    }
+
+    // winit panics instead of returning an error when there is no display to connect to, the
+    // message is kept for the explanation instead of going to the default hook
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let event_loop = std::panic::catch_unwind(EventLoop::new);
+    std::panic::set_hook(default_hook);
+    let event_loop = event_loop.unwrap_or_else(|panic| {
+        let reason = panic
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| panic.downcast_ref::<&str>().copied())
+            .unwrap_or("no display available");
+        exit_without_window(reason)
+    });
+    let window = WindowBuilder::new()
+        .with_inner_size(PhysicalSize::new(render_config.width, render_config.height))
+        .build(&event_loop)
+        .unwrap_or_else(|error| exit_without_window(&error.to_string()));
 
     // Create channels for thread communication
     let (sim_to_main_tx, sim_to_main_rx) = bounded(1);
-    let (main_to_sim_tx, main_to_sim_rx) = bounded(1);
+    // Unbounded so input is never dropped or blocks the event loop, the simulation drains it every tick
+    let (main_to_sim_tx, main_to_sim_rx) = unbounded();
 
     // Shared state for synchronization
     let shared_state = Arc::new(SharedState {
-        draw_request: Arc::new(Mutex::new(None)),
-        events: Arc::new(Mutex::new(Vec::new())),
+        camera: Arc::new(Mutex::new(Camera::new([bounds[0] / 2.0, bounds[1] / 2.0]))),
     });
 
-    // Spawn simulation thread
-    let shared_state_clone = Arc::clone(&shared_state);
-    let simulation_thread = std::thread::spawn(move || {
-        simulation_loop(shared_state_clone, sim_to_main_tx, main_to_sim_rx)
+    // Setup pixels renderer, it draws and presents from the simulation thread. Without a usable
+    // GPU the software adapter wgpu falls back to (if the platform has one) still beats no window.
+    let window_size = window.inner_size();
+    let build_pixels = |force_fallback_adapter| {
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        PixelsBuilder::new(render_config.width, render_config.height, surface_texture)
+            .enable_vsync(render_config.vsync)
+            .request_adapter_options(RequestAdapterOptions {
+                force_fallback_adapter,
+                power_preference: wgpu::util::power_preference_from_env().unwrap_or_default(),
+                compatible_surface: None,
+            })
+            .build()
+    };
+    let pixels = match build_pixels(false) {
+        Err(error @ (Error::AdapterNotFound | Error::DeviceNotFound(_))) => {
+            eprintln!("Warning: {error} Trying software rendering");
+            build_pixels(true)
+        }
+        result => result,
+    }
+    .unwrap_or_else(|error| exit_without_window(&error.to_string()));
+    let mut renderer = WinitRenderer::new(
+        pixels,
+        render_config.clone(),
+        Arc::clone(&shared_state.camera),
+    );
+    renderer.set_profiling(args.profile);
+    let gif_recorder = args.record_gif.as_ref().map(|path| {
+        let frames = args.frames.unwrap_or(DEFAULT_GIF_FRAMES);
+        let (width, height) = (render_config.width, render_config.height);
+        // Without a cap frames come as fast as they can, play them back at the usual rate
+        let fps = render_config.fps.unwrap_or(DEFAULT_FPS);
+        match GifRecorder::create(path, width, height, fps, frames) {
+            Ok(gif_recorder) => gif_recorder,
+            Err(error) => {
+                eprintln!("error: failed to start recording: {error}");
+                std::process::exit(1);
+            }
+        }
     });
 
-    // Setup pixels renderer
-    let window_size = window.inner_size();
-    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-    let mut pixels = Pixels::new(640, 480, surface_texture)?;
+    // Spawn simulation thread, named so its panic messages say where they come from
+    let simulation_thread = std::thread::Builder::new()
+        .name("simulation".to_string())
+        .spawn(move || {
+            simulation_loop(
+                core_state,
+                stats_writer,
+                autosaver,
+                renderer,
+                gif_recorder,
+                input_log,
+                args.pause_unfocused,
+                sim_to_main_tx,
+                main_to_sim_rx,
+            )
+        })
+        .unwrap_or_else(|error| {
+            eprintln!("error: failed to start the simulation thread: {error}");
+            std::process::exit(1);
+        });
+    let mut simulation_thread = Some(simulation_thread);
+
+    let mut input_state = InputState {
+        brush_radius: DEFAULT_BRUSH_RADIUS,
+        brush_density: DEFAULT_BRUSH_DENSITY,
+        ..InputState::default()
+    };
 
     // Main event loop
     event_loop.run(move |event, _, control_flow| {
@@ -56,46 +557,265 @@ fn main() -> Result<(), Error> {
 
         match event {
             Event::WindowEvent { event, .. } => {
-                // Collect events
-                {
-                    let mut events = shared_state.events.lock();
-                    events.push(event.clone());
+                // Handle window close
+                if let WindowEvent::CloseRequested = event {
+                    *control_flow = ControlFlow::Exit;
                 }
 
-                // Forward events to simulation thread
-                if let Err(_) = main_to_sim_tx.try_send(MainToSimulationMessage::Events(
-                    shared_state.events.lock().clone(),
-                )) {
-                    eprintln!("Failed to send events to simulation thread");
+                // The renderer follows the window size with both the surface and the buffer
+                if let WindowEvent::Resized(size) = event {
+                    // Minimizing reports a zero sized window, which the surface can't take
+                    if size.width > 0 && size.height > 0 {
+                        render_config.width = size.width;
+                        render_config.height = size.height;
+                        if std::mem::take(&mut input_state.fit_on_resize) {
+                            shared_state
+                                .camera
+                                .lock()
+                                .fit(bounds, [size.width as f32, size.height as f32]);
+                        }
+
+                        let _ = main_to_sim_tx.send(MainToSimulationMessage::Resize {
+                            width: size.width,
+                            height: size.height,
+                        });
+                    }
                 }
 
-                // Clear events after sending
-                shared_state.events.lock().clear();
+                if input_state.type_into_console(&event, &main_to_sim_tx) {
+                    return;
+                }
 
-                // Handle window close
+                // Camera controls, wheel zooms around the cursor and middle mouse drags, WASD pans
+                // and Q/E zoom while held. Clicks and arrow keys turn into simulation messages, F11
+                // toggles fullscreen. Shift+click inspects the particle under the cursor and a
+                // shift drag selects a region for delete to clear, a left drag paints particles
+                // with the wheel sizing the brush.
+                let screen_size = [render_config.width as f32, render_config.height as f32];
                 match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let position = [position.x as f32, position.y as f32];
+                        if input_state.panning {
+                            shared_state.camera.lock().pan([
+                                position[0] - input_state.cursor_position[0],
+                                position[1] - input_state.cursor_position[1],
+                            ]);
+                        }
+                        input_state.cursor_position = position;
+
+                        if let Some(start) = input_state.drag_start {
+                            let [dx, dy] = [position[0] - start[0], position[1] - start[1]];
+                            if dx.hypot(dy) >= DRAG_THRESHOLD {
+                                let camera = shared_state.camera.lock();
+                                let region = Rect::spanning(
+                                    camera.screen_to_world(start, screen_size),
+                                    camera.screen_to_world(position, screen_size),
+                                );
+                                input_state.region = Some(region.clone());
+                                let _ = main_to_sim_tx
+                                    .send(MainToSimulationMessage::SelectRegion(Some(region)));
+                            }
+                        }
+
+                        if input_state.gravity_well {
+                            let world_pos = shared_state
+                                .camera
+                                .lock()
+                                .screen_to_world(position, screen_size);
+                            let _ = main_to_sim_tx.send(MainToSimulationMessage::GravityWell {
+                                active: true,
+                                world_pos,
+                            });
+                        }
+                        if input_state.painting {
+                            let world_pos = shared_state
+                                .camera
+                                .lock()
+                                .screen_to_world(position, screen_size);
+                            let _ = main_to_sim_tx.send(MainToSimulationMessage::Paint {
+                                brush: Some(input_state.brush(world_pos)),
+                            });
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Middle,
+                        ..
+                    } => input_state.panning = state == ElementState::Pressed,
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        let world_pos = shared_state
+                            .camera
+                            .lock()
+                            .screen_to_world(input_state.cursor_position, screen_size);
+                        if input_state.shift {
+                            // Inspects on release unless it turns into a drag
+                            input_state.drag_start = Some(input_state.cursor_position);
+                            input_state.region = None;
+                        } else {
+                            // A click places one particle right away, holding on keeps painting
+                            input_state.painting = true;
+                            let _ =
+                                main_to_sim_tx.send(MainToSimulationMessage::SpawnAt { world_pos });
+                            let _ = main_to_sim_tx.send(MainToSimulationMessage::Paint {
+                                brush: Some(input_state.brush(world_pos)),
+                            });
+                        }
+                    }
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        if let Some(start) = input_state.drag_start.take() {
+                            if input_state.region.is_none() {
+                                let world_pos = shared_state
+                                    .camera
+                                    .lock()
+                                    .screen_to_world(start, screen_size);
+                                let _ = main_to_sim_tx
+                                    .send(MainToSimulationMessage::Inspect { world_pos });
+                                let _ = main_to_sim_tx
+                                    .send(MainToSimulationMessage::SelectRegion(None));
+                            }
+                        }
+                        input_state.painting = false;
+                        let _ = main_to_sim_tx.send(MainToSimulationMessage::Paint { brush: None });
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Right,
+                        ..
+                    } => {
+                        input_state.gravity_well = state == ElementState::Pressed;
+                        let world_pos = shared_state
+                            .camera
+                            .lock()
+                            .screen_to_world(input_state.cursor_position, screen_size);
+                        let _ = main_to_sim_tx.send(MainToSimulationMessage::GravityWell {
+                            active: input_state.gravity_well,
+                            world_pos,
+                        });
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } if input_state.hold_camera_key(key, state == ElementState::Pressed) => {}
+                    // Arrow keys point gravity towards that edge of the screen
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } => {
+                        let direction = match key {
+                            VirtualKeyCode::Down => Some([0.0, 1.0]),
+                            VirtualKeyCode::Up => Some([0.0, -1.0]),
+                            VirtualKeyCode::Left => Some([-1.0, 0.0]),
+                            VirtualKeyCode::Right => Some([1.0, 0.0]),
+                            _ => None,
+                        };
+                        if let Some(direction) = direction {
+                            let _ =
+                                main_to_sim_tx.send(MainToSimulationMessage::Gravity { direction });
+                        }
+                        if key == VirtualKeyCode::Delete {
+                            if let Some(region) = input_state.region.take() {
+                                let _ = main_to_sim_tx
+                                    .send(MainToSimulationMessage::DeleteRegion(region));
+                            }
+                        }
+
+                        // Borderless on the current monitor, the size change arrives as a resize
+                        if key == VirtualKeyCode::F11 {
+                            let fullscreen = match window.fullscreen() {
+                                Some(_) => None,
+                                None => Some(Fullscreen::Borderless(None)),
+                            };
+                            window.set_fullscreen(fullscreen);
+                            input_state.fit_on_resize = true;
+                        }
+                    }
+                    WindowEvent::ModifiersChanged(modifiers) => {
+                        input_state.shift = modifiers.shift();
+                        input_state.control = modifiers.ctrl();
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let notches = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(position) => {
+                                position.y as f32 / PIXELS_PER_SCROLL_LINE
+                            }
+                        };
+                        if input_state.painting {
+                            let factor = BRUSH_STEP.powf(notches);
+                            if input_state.control {
+                                let (min, max) = BRUSH_DENSITY_RANGE;
+                                input_state.brush_density =
+                                    (input_state.brush_density * factor).clamp(min, max);
+                            } else {
+                                let (min, max) = BRUSH_RADIUS_RANGE;
+                                input_state.brush_radius =
+                                    (input_state.brush_radius * factor).clamp(min, max);
+                            }
+                            let world_pos = shared_state
+                                .camera
+                                .lock()
+                                .screen_to_world(input_state.cursor_position, screen_size);
+                            let _ = main_to_sim_tx.send(MainToSimulationMessage::Paint {
+                                brush: Some(input_state.brush(world_pos)),
+                            });
+                        } else {
+                            shared_state.camera.lock().zoom_around(
+                                input_state.cursor_position,
+                                ZOOM_STEP.powf(notches),
+                                screen_size,
+                            );
+                        }
+                    }
                     _ => {}
                 }
+
+                // Forward every event exactly once, scale factor changes borrow from the event
+                // loop and can't be forwarded
+                if let Some(event) = event.to_static() {
+                    let _ = main_to_sim_tx.send(MainToSimulationMessage::Event(event));
+                }
             }
             Event::MainEventsCleared => {
-                // Check for draw requests from simulation thread
-                if let Ok(SimulationToMainMessage::DrawRequest(frame_data)) =
-                    sim_to_main_rx.try_recv()
-                {
-                    let mut pixels_frame = pixels.frame_mut();
-                    pixels_frame.copy_from_slice(&frame_data);
-
-                    if let Err(_) = pixels.render() {
-                        eprintln!("Failed to render frame");
+                let screen_size = [render_config.width as f32, render_config.height as f32];
+                input_state.move_camera(&shared_state.camera, screen_size);
+
+                // The simulation finished, nothing left to show. A panic drops the sender without
+                // a message, the window shouldn't stay open on its last frame either.
+                match sim_to_main_rx.try_recv() {
+                    Ok(SimulationToMainMessage::Terminate) | Err(TryRecvError::Disconnected) => {
                         *control_flow = ControlFlow::Exit;
                     }
+                    Err(TryRecvError::Empty) => {}
                 }
             }
             Event::LoopDestroyed => {
-                // Signal simulation thread to terminate
-                let _ = sim_to_main_tx.send(SimulationToMainMessage::Terminate);
-                let _ = simulation_thread.join();
+                // Signal simulation thread to terminate and wait for it to wind down
+                let _ = main_to_sim_tx.send(MainToSimulationMessage::Terminate);
+                if let Some(simulation_thread) = simulation_thread.take() {
+                    // The default hook already printed the panic message and where it happened
+                    if simulation_thread.join().is_err() {
+                        eprintln!("error: the simulation thread panicked, closing the window");
+                        std::process::exit(EXIT_SIMULATION_PANIC);
+                    }
+                }
             }
             _ => {}
         }
@@ -110,83 +830,450 @@ pub trait Renderer {
     fn render_world(&mut self, world: Self::RenderWorld);
 }
 
-pub struct RenderState {}
+// Steps the simulation back to back until a stop condition is met, then writes the final state.
+// A benchmark reports its timing as JSON instead of the human readable summary.
+fn run_headless(
+    mut core_state: CoreState,
+    mut stats_writer: Option<StatsWriter>,
+    mut autosaver: Option<Autosaver>,
+    mut input_log: InputLog,
+    ticks: Option<u64>,
+    save_path: Option<&Path>,
+    bench: bool,
+) {
+    let max_runtime = core_state.config().max_runtime;
+    let start_particles = core_state.world().particles().len();
+    let start_time = Instant::now();
+    let start_tick = core_state.tick();
+    record_stats(&mut stats_writer, &core_state);
+    // Resets in a replay take the tick back, so only the log can tell when it's over
+    let until_replayed = ticks.is_none() && input_log.replaying();
 
-pub struct CoreState {
-    world: CoreWorld,
-}
+    while ticks.is_none_or(|ticks| core_state.tick() - start_tick < ticks)
+        && max_runtime.is_none_or(|max_runtime| start_time.elapsed() < max_runtime)
+        // Emitters bring an empty world back to life, nothing else can headless
+        && (!core_state.world().particles().is_empty() || !core_state.config().emitters.is_empty())
+    {
+        input_log.before_step(&mut core_state);
+        if until_replayed && !input_log.replaying() {
+            break;
+        }
+        core_state.step(FIXED_TIMESTEP);
+        record_stats(&mut stats_writer, &core_state);
+        if let Some(autosaver) = &mut autosaver {
+            autosaver.after_step(&core_state);
+        }
+    }
+    input_log.finish(core_state.tick());
+    if let Some(autosaver) = autosaver {
+        autosaver.finish();
+    }
 
-pub struct CoreWorld {}
+    let elapsed = start_time.elapsed();
+    if bench {
+        let report = BenchReport {
+            seed: core_state.config().seed,
+            particles: start_particles,
+            ticks: core_state.tick() - start_tick,
+            elapsed,
+            peak_memory_bytes: bench::peak_memory_bytes(),
+        };
+        println!("{}", report.to_json());
+    } else {
+        println!(
+            "Headless run took {:.1} seconds. Ticks: {}, particles left: {}",
+            elapsed.as_secs_f32(),
+            core_state.tick() - start_tick,
+            core_state.world().particles().len()
+        );
+    }
+    print_profile(&core_state, None);
 
-pub struct WinitRenderData {}
+    let mut failed = false;
+    if let Some(Err(error)) = stats_writer.map(StatsWriter::finish) {
+        eprintln!("Failed to flush statistics: {error}");
+        failed = true;
+    }
+    if let Some(Err(error)) = save_path.map(|path| core_state.save(path)) {
+        eprintln!("Failed to write save: {error}");
+        failed = true;
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
 
+#[allow(clippy::too_many_arguments)]
 fn simulation_loop(
-    shared_state: Arc<SharedState>,
+    mut core_state: CoreState,
+    mut stats_writer: Option<StatsWriter>,
+    mut autosaver: Option<Autosaver>,
+    mut renderer: WinitRenderer,
+    mut gif_recorder: Option<GifRecorder>,
+    mut input_log: InputLog,
+    pause_unfocused: bool,
     sim_to_main_tx: Sender<SimulationToMainMessage>,
     main_to_sim_rx: Receiver<MainToSimulationMessage>,
 ) {
+    let max_runtime = core_state.config().max_runtime;
     let mut frame_count = 0;
     let start_time = Instant::now();
+    // Started right before the first tick so the first dt is tiny instead of arbitrary
+    let mut last_tick = Instant::now();
+    let mut accumulator = 0.0;
+    // Simulated seconds given up on since the last lag warning
+    let mut dropped_time = 0.0;
+    let mut last_lag_warning: Option<Instant> = None;
+    // Paused because the window went to the background, a pause from the keyboard stays on
+    let mut paused_unfocused = false;
+
+    // With vsync presenting already waits for the display, pacing on top of that would halve
+    // the frame rate whenever a frame runs long
+    let mut frame_limiter = FrameLimiter::new(if renderer.config().vsync {
+        None
+    } else {
+        renderer.config().fps
+    });
+
+    'simulation: loop {
+        // Replayed input first, so a replay ignores what the user does at the same tick
+        input_log.before_step(&mut core_state);
 
-    loop {
         // Check for messages from main thread
-        if let Ok(MainToSimulationMessage::Events(events)) = main_to_sim_rx.try_recv() {
-            // Process received events
-            for event in events {
-                match event {
-                    WindowEvent::KeyboardInput { .. } => {
-                        // Handle keyboard events
+        for message in main_to_sim_rx.try_iter() {
+            match message {
+                MainToSimulationMessage::Event(event) => match event {
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    } => match key {
+                        // Regenerates the world, frames are rasterized from a single state in
+                        // one go so none can mix both worlds
+                        VirtualKeyCode::R => input_log.submit(&mut core_state, Input::Reset),
+                        _ => handle_key_press(key, &mut core_state, &mut renderer),
+                    },
+                    WindowEvent::Focused(false) if pause_unfocused && !core_state.paused() => {
+                        core_state.toggle_pause();
+                        paused_unfocused = true;
+                    }
+                    // Unless the keyboard already resumed in the meantime
+                    WindowEvent::Focused(true)
+                        if std::mem::take(&mut paused_unfocused) && core_state.paused() =>
+                    {
+                        core_state.toggle_pause()
                     }
                     _ => {}
+                },
+                MainToSimulationMessage::ConsoleInput(input) => renderer.set_console_input(input),
+                MainToSimulationMessage::Command(line) => {
+                    let result = run_command(&line, &mut core_state, &mut input_log);
+                    renderer.show_console_result(result);
+                }
+                MainToSimulationMessage::Resize { width, height } => {
+                    if let Err(error) = renderer.resize(width, height) {
+                        eprintln!("Failed to resize pixels surface: {error}");
+                        break 'simulation;
+                    }
+                }
+                MainToSimulationMessage::SpawnAt { world_pos } => {
+                    input_log.submit(&mut core_state, Input::SpawnAt(world_pos))
                 }
+                MainToSimulationMessage::Inspect { world_pos } => {
+                    let selected = core_state.particle_at(world_pos);
+                    match selected.and_then(|id| core_state.world().particle(id)) {
+                        Some(particle) => println!("{}", describe_particle(particle)),
+                        None => {
+                            println!("No particle at ({:.1}, {:.1})", world_pos[0], world_pos[1])
+                        }
+                    }
+                    renderer.select(selected);
+                }
+                MainToSimulationMessage::SelectRegion(region) => renderer.select_region(region),
+                MainToSimulationMessage::DeleteRegion(region) => {
+                    let before = core_state.world().particles().len();
+                    input_log.submit(&mut core_state, Input::DeleteRegion(region));
+                    let deleted = before - core_state.world().particles().len();
+                    println!("Deleted {deleted} particles");
+                    renderer.select_region(None);
+                }
+                MainToSimulationMessage::GravityWell { active, world_pos } => input_log.submit(
+                    &mut core_state,
+                    Input::GravityWell(active.then_some(world_pos)),
+                ),
+                MainToSimulationMessage::Paint { brush } => {
+                    input_log.submit(&mut core_state, Input::Brush(brush))
+                }
+                MainToSimulationMessage::Gravity { direction } => {
+                    input_log.submit(&mut core_state, Input::Gravity(direction))
+                }
+                MainToSimulationMessage::Terminate => break 'simulation,
             }
         }
 
-        // Simulation update logic
-        let frame_data = simulate_frame(frame_count);
+        // Covers single steps taken from the keyboard
+        record_stats(&mut stats_writer, &core_state);
+        if let Some(autosaver) = &mut autosaver {
+            autosaver.after_step(&core_state);
+        }
+
+        // Simulation update logic, consume the elapsed (scaled) wall clock time in fixed steps
+        let now = Instant::now();
+        let speed_multiplier = core_state.speed_multiplier();
+        accumulator += now.duration_since(last_tick).as_secs_f32() * speed_multiplier;
+        last_tick = now;
+        // Time spent paused is gone, resuming doesn't catch up on it or report it as lag
+        if core_state.paused() {
+            accumulator = 0.0;
+        }
+
+        let max_steps = (MAX_CATCH_UP_STEPS as f32 * speed_multiplier.max(1.0)).ceil() as u32;
+        // Without frames to draw the simulation runs as fast as it can instead of keeping to the
+        // clock, the tick rate then shows what the physics alone costs
+        let flat_out = renderer.frozen() && !core_state.paused();
+        if flat_out {
+            accumulator = accumulator.max(max_steps as f32 * FIXED_TIMESTEP);
+        }
+        let mut steps = 0;
+        while accumulator >= FIXED_TIMESTEP && steps < max_steps {
+            input_log.before_step(&mut core_state);
+            core_state.step(FIXED_TIMESTEP);
+            record_stats(&mut stats_writer, &core_state);
+            if let Some(autosaver) = &mut autosaver {
+                autosaver.after_step(&core_state);
+            }
+            accumulator -= FIXED_TIMESTEP;
+            steps += 1;
+        }
+        // A short hitch is caught up on over the next iterations. Once the backlog grows past
+        // the allowed lag it is dropped, chasing it would only fall further behind.
+        let max_lag = core_state.config().max_lag * speed_multiplier.max(1.0);
+        if accumulator > max_lag.max(FIXED_TIMESTEP) {
+            dropped_time += accumulator - FIXED_TIMESTEP;
+            accumulator = FIXED_TIMESTEP;
+        }
+        if dropped_time > 0.0
+            && last_lag_warning.is_none_or(|warned| warned.elapsed() >= LAG_WARNING_INTERVAL)
+        {
+            eprintln!(
+                "Warning: simulation can't keep up, dropped {:.0} ms of simulated time",
+                dropped_time * 1000.0
+            );
+            dropped_time = 0.0;
+            last_lag_warning = Some(Instant::now());
+        }
 
-        // Send draw request to main thread
-        match sim_to_main_tx.try_send(SimulationToMainMessage::DrawRequest(frame_data)) {
-            Ok(_) => frame_count += 1,
-            Err(_) => {
-                // Skip frame if main thread is busy
-                eprintln!("Skipping frame due to busy main thread");
+        if renderer.frozen() {
+            renderer.render_frozen(&core_state);
+        } else {
+            let render_world = renderer.extract_render_world(&core_state);
+            renderer.render_world(render_world);
+            frame_count += 1;
+            if let Some(gif_recorder) = &mut gif_recorder {
+                gif_recorder.capture(renderer.frame());
             }
         }
+        if renderer.failed() {
+            break;
+        }
 
-        // Optional: Basic FPS control
-        std::thread::sleep(Duration::from_millis(16)); // ~60 FPS
+        // Either way we don't busy-spin while paused, unless the frame rate is uncapped
+        if !flat_out {
+            frame_limiter.wait();
+        }
 
-        // Optional: Exit condition or performance tracking
-        if start_time.elapsed() > Duration::from_secs(60) {
-            println!(
-                "Simulation ran for 60 seconds. Total frames: {}",
-                frame_count
-            );
+        if max_runtime.is_some_and(|max_runtime| start_time.elapsed() >= max_runtime) {
             break;
         }
     }
+
+    println!(
+        "Simulation ran for {:.1} seconds. Total frames: {}",
+        start_time.elapsed().as_secs_f32(),
+        frame_count
+    );
+    input_log.finish(core_state.tick());
+    if let Some(autosaver) = autosaver {
+        autosaver.finish();
+    }
+    print_profile(&core_state, Some(&renderer));
+    if let Some(Err(error)) = stats_writer.map(StatsWriter::finish) {
+        eprintln!("Failed to flush statistics: {error}");
+    }
+    // The encoder finalizes the file as soon as the last frame is in, this only waits for it
+    if let Some(gif_recorder) = gif_recorder {
+        let path = gif_recorder.path().display().to_string();
+        match gif_recorder.finish() {
+            Ok(()) => println!("Recorded {path}"),
+            Err(error) => eprintln!("Failed to record GIF: {error}"),
+        }
+    }
+    // Closes the window unless the main thread is the one shutting down anyway
+    let _ = sim_to_main_tx.try_send(SimulationToMainMessage::Terminate);
 }
 
-fn simulate_frame(frame_number: usize) -> Vec<u8> {
-    // Placeholder simulation logic
-    // In a real implementation, this would generate actual pixel data
-    let width = 640;
-    let height = 480;
-    let mut frame_data = vec![0; width * height * 4];
-    let asp = vec![] + "123";
-    let idk = &mut asp;
+// Breakdown of the time spent per phase, prints nothing unless profiling was enabled
+fn print_profile(core_state: &CoreState, renderer: Option<&WinitRenderer>) {
+    let reports = [
+        core_state.profiler().report("Simulation steps"),
+        renderer.and_then(|renderer| renderer.profiler().report("Rendered frames")),
+    ];
+    for report in reports.into_iter().flatten() {
+        println!("{report}");
+    }
+}
 
-    // Simple gradient or animation based on frame number
-    for y in 0..height {
-        for x in 0..width {
-            let index = (y * width + x) * 4;
-            frame_data[index] = (x % 256) as u8; // R
-            frame_data[index + 1] = (y % 256) as u8; // G
-            frame_data[index + 2] = (frame_number % 256) as u8; // B
-            frame_data[index + 3] = 255; // A
+fn exit_without_window(reason: &str) -> ! {
+    eprintln!(
+        "error: can't open a window: {reason}\n\
+         No display or graphics adapter is available here, --headless runs the simulation \
+         without one"
+    );
+    std::process::exit(EXIT_NO_WINDOW);
+}
+
+fn describe_particle(particle: &Particle) -> String {
+    let Particle {
+        id,
+        species,
+        position,
+        velocity,
+        mass,
+        radius,
+        genome,
+        energy,
+        age,
+        ..
+    } = particle;
+    format!(
+        "Particle {id} (species {species}): position ({:.1}, {:.1}), velocity ({:.1}, {:.1}), \
+         mass {mass:.2}, radius {radius:.2}, energy {energy:.1}, age {age:.1} s, genome: speed \
+         {:.2}, size {:.2}, hue {:.0}",
+        position[0],
+        position[1],
+        velocity[0],
+        velocity[1],
+        genome.speed_factor,
+        genome.size_factor,
+        genome.color_hue
+    )
+}
+
+// A failed write stops the recording instead of spamming the same error every tick
+fn record_stats(stats_writer: &mut Option<StatsWriter>, core_state: &CoreState) {
+    if let Some(Err(error)) = stats_writer
+        .as_mut()
+        .map(|stats_writer| stats_writer.record(core_state))
+    {
+        eprintln!("Failed to write statistics, stopping: {error}");
+        *stats_writer = None;
+    }
+}
+
+// Prints the live config in the format of a config file, ready to be saved as one. Render settings
+// aren't read from it and come after it as comments.
+fn print_config(core_state: &CoreState, renderer: &WinitRenderer) {
+    let (simulation, render) = match (
+        config::to_ron(&core_state.live_config()),
+        config::to_ron(renderer.config()),
+    ) {
+        (Ok(simulation), Ok(render)) => (simulation, render),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("Failed to print config: {error}");
+            return;
         }
+    };
+    println!("{simulation}");
+    println!(
+        "// Render settings, running at {}x speed{}",
+        core_state.speed_multiplier(),
+        if core_state.paused() { ", paused" } else { "" }
+    );
+    for line in render.lines() {
+        println!("// {line}");
     }
+}
 
-    frame_data
+// Applies a line typed into the console, returns what to show for it
+fn run_command(
+    line: &str,
+    core_state: &mut CoreState,
+    input_log: &mut InputLog,
+) -> Result<String, String> {
+    let command = console::parse(line)?;
+    if input_log.replaying() && matches!(command, Command::Input(_)) {
+        return Err("ignored while the replay runs".into());
+    }
+    match command {
+        Command::Input(input) => {
+            // Checked here as well, the log only holds what the world accepted
+            if let Input::Set { name, values } = &input {
+                core_state.live_config().set_parameter(name, values)?;
+            }
+            input_log.submit(core_state, input);
+            Ok(format!("ok: {}", line.trim()))
+        }
+        Command::TogglePause => {
+            core_state.toggle_pause();
+            Ok(if core_state.paused() {
+                "paused"
+            } else {
+                "running"
+            }
+            .into())
+        }
+        Command::Help => Ok(console::HELP.into()),
+    }
+}
+
+fn handle_key_press(key: VirtualKeyCode, core_state: &mut CoreState, renderer: &mut WinitRenderer) {
+    match key {
+        VirtualKeyCode::Space => core_state.toggle_pause(),
+        // Every OS key repeat is its own press, so holding the key steps once per repeat
+        VirtualKeyCode::Period => core_state.single_step(FIXED_TIMESTEP),
+        VirtualKeyCode::Plus | VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+            core_state.scale_speed(2.0)
+        }
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => core_state.scale_speed(0.5),
+        VirtualKeyCode::F5 => match core_state.save(Path::new(CHECKPOINT_PATH)) {
+            Ok(()) => println!("Saved checkpoint to {CHECKPOINT_PATH}"),
+            Err(error) => eprintln!("Failed to save checkpoint: {error}"),
+        },
+        VirtualKeyCode::Tab => renderer.cycle_color_mode(),
+        VirtualKeyCode::H => renderer.cycle_render_mode(),
+        VirtualKeyCode::P => renderer.cycle_particle_shape(),
+        VirtualKeyCode::F => println!("Camera mode: {:?}", renderer.cycle_camera_mode()),
+        VirtualKeyCode::F1 => renderer.toggle_overlay(),
+        VirtualKeyCode::F2 => {
+            if renderer.toggle_freeze() {
+                println!("Rendering frozen, the simulation runs as fast as it can");
+            } else {
+                println!("Rendering resumed");
+            }
+        }
+        VirtualKeyCode::M => renderer.toggle_minimap(),
+        VirtualKeyCode::V => renderer.toggle_velocity_vectors(),
+        VirtualKeyCode::B => renderer.toggle_bounds(),
+        VirtualKeyCode::G => renderer.toggle_grid(),
+        VirtualKeyCode::N => renderer.toggle_nutrients(),
+        VirtualKeyCode::C => print_config(core_state, renderer),
+        // The buffer always follows the window size so the image matches what is shown, the
+        // encode runs on its own thread to keep frames coming
+        VirtualKeyCode::F12 => {
+            let frame = renderer.frame().to_vec();
+            let (width, height) = (renderer.config().width, renderer.config().height);
+            std::thread::spawn(move || {
+                let path = screenshot::timestamped_path();
+                match screenshot::save_png(&path, width, height, &frame) {
+                    Ok(()) => println!("Saved screenshot to {}", path.display()),
+                    Err(error) => eprintln!("Failed to save screenshot: {error}"),
+                }
+            });
+        }
+        _ => {}
+    }
 }
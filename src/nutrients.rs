@@ -0,0 +1,165 @@
+use crate::simulation::{Particle, Rect};
+use serde::{Deserialize, Serialize};
+
+// Nutrients spread over the world in square cells, see `NutrientField`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NutrientConfig {
+    // Side of a cell in world units
+    pub cell_size: f32,
+    // Most nutrients a cell holds, every cell starts out full
+    pub cap: f32,
+    // Nutrients every cell gains per second until it is back at `cap`
+    pub regrowth_rate: f32,
+    // Nutrients a particle takes per second from the cell it is in, scaled by its `size_factor`.
+    // Each one becomes a unit of energy.
+    pub uptake_rate: f32,
+}
+
+impl NutrientConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.cell_size > 0.0 && self.cell_size.is_finite()) {
+            return Err(format!(
+                "nutrient cell_size must be positive, got {}",
+                self.cell_size
+            ));
+        }
+        for (name, value) in [
+            ("cap", self.cap),
+            ("regrowth_rate", self.regrowth_rate),
+            ("uptake_rate", self.uptake_rate),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("nutrient {name} must not be negative, got {value}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Concentration of nutrients per cell over the bounds of the world. Particles feed on the cell
+// under them and leave depleted trails behind, which fill up again at a steady rate.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NutrientField {
+    pub origin: [f32; 2],
+    pub cell_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    // Row by row from the top left
+    pub concentrations: Vec<f32>,
+}
+
+impl NutrientField {
+    // Covers `bounds` with full cells, the last row and column stick out past them if the size
+    // doesn't divide evenly
+    pub fn new(bounds: &Rect, config: &NutrientConfig) -> Self {
+        let cells = |axis: usize| {
+            (((bounds.max[axis] - bounds.min[axis]) / config.cell_size).ceil() as usize).max(1)
+        };
+        let (columns, rows) = (cells(0), cells(1));
+        Self {
+            origin: bounds.min,
+            cell_size: config.cell_size,
+            columns,
+            rows,
+            concentrations: vec![config.cap; columns * rows],
+        }
+    }
+
+    // Index into `concentrations` of the cell containing `position`, `None` outside the field
+    pub fn cell_at(&self, position: [f32; 2]) -> Option<usize> {
+        let column = ((position[0] - self.origin[0]) / self.cell_size).floor();
+        let row = ((position[1] - self.origin[1]) / self.cell_size).floor();
+        let inside = |cell: f32, count: usize| cell >= 0.0 && cell < count as f32;
+        (inside(column, self.columns) && inside(row, self.rows))
+            .then(|| row as usize * self.columns + column as usize)
+    }
+
+    // Lets every particle feed on its cell over `dt` in id order, so when several share a cell
+    // running low the older ones eat first. The field then regrows towards the cap.
+    pub fn step(&mut self, particles: &mut [Particle], config: &NutrientConfig, dt: f32) {
+        for particle in particles {
+            let Some(cell) = self.cell_at(particle.position) else {
+                continue;
+            };
+            let wanted = config.uptake_rate * particle.genome.size_factor * dt;
+            let taken = wanted.min(self.concentrations[cell]);
+            self.concentrations[cell] -= taken;
+            particle.energy += taken;
+        }
+        let regrowth = config.regrowth_rate * dt;
+        for concentration in &mut self.concentrations {
+            *concentration = (*concentration + regrowth).min(config.cap);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{CoreState, SimulationConfig};
+
+    #[test]
+    fn particles_deplete_their_cells_which_then_regrow() {
+        let nutrients = NutrientConfig {
+            cell_size: 40.0,
+            cap: 10.0,
+            regrowth_rate: 2.0,
+            uptake_rate: 30.0,
+        };
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            bounds: [100.0, 80.0],
+            gravity: [0.0, 0.0],
+            basal_metabolism: 0.0,
+            movement_cost: 0.0,
+            ambient_energy_gain: 0.0,
+            food_spawn_rate: 0.0,
+            reproduction_threshold: f32::INFINITY,
+            nutrients: Some(nutrients),
+            ..SimulationConfig::default()
+        });
+        let field = core_state.world().nutrients().unwrap();
+        // 100 by 80 in cells of 40 takes three columns and two rows
+        assert_eq!((field.columns, field.rows), (3, 2));
+        assert_eq!(field.cell_at([50.0, 70.0]), Some(4));
+        assert_eq!(field.cell_at([-1.0, 10.0]), None);
+        assert!(field.concentrations.iter().all(|&value| value == 10.0));
+
+        core_state.spawn_at([50.0, 20.0]);
+        let energy = core_state.world().particles()[0].energy;
+        for _ in 0..60 {
+            core_state.step(1.0 / 60.0);
+        }
+        // Even the smallest particle eats faster than the cell regrows, it ends up empty but
+        // for the last regrowth. The other cells stay full.
+        let field = core_state.world().nutrients().unwrap();
+        let cell = field.cell_at(core_state.world().particles()[0].position);
+        assert_eq!(cell, Some(1));
+        let eaten = 10.0 + 2.0 - field.concentrations[1];
+        let gained = core_state.world().particles()[0].energy - energy;
+        assert!((eaten - gained).abs() < 1e-3, "{eaten} {gained}");
+        assert!(field.concentrations[1] < 2.0 / 60.0 + 1e-4);
+        assert_eq!(field.concentrations[0], 10.0);
+
+        // Without anyone eating it is full again five seconds later, and stays at the cap
+        core_state.delete_region(&Rect {
+            min: [0.0, 0.0],
+            max: [100.0, 80.0],
+        });
+        for _ in 0..330 {
+            core_state.step(1.0 / 60.0);
+        }
+        let field = core_state.world().nutrients().unwrap();
+        assert!(field.concentrations.iter().all(|&value| value == 10.0));
+
+        // Saves carry the field over as it is
+        let resumed = CoreState::decode(&core_state.encode().unwrap()).unwrap();
+        assert_eq!(resumed.world().nutrients(), Some(field));
+
+        let invalid = NutrientConfig {
+            cell_size: 0.0,
+            ..nutrients
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
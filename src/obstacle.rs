@@ -0,0 +1,184 @@
+use crate::simulation::Particle;
+use serde::{Deserialize, Serialize};
+
+// Static shape particles bounce off, it never moves and isn't pushed around
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Obstacle {
+    Circle { center: [f32; 2], radius: f32 },
+    // Axis aligned box between two corners
+    Rect { min: [f32; 2], max: [f32; 2] },
+}
+
+impl Obstacle {
+    // Smallest circle around the whole shape, how it is bucketed in the spatial grid
+    pub fn bounding_circle(&self) -> ([f32; 2], f32) {
+        match *self {
+            Obstacle::Circle { center, radius } => (center, radius),
+            Obstacle::Rect { min, max } => {
+                let half = [(max[0] - min[0]) / 2.0, (max[1] - min[1]) / 2.0];
+                (
+                    [min[0] + half[0], min[1] + half[1]],
+                    (half[0] * half[0] + half[1] * half[1]).sqrt(),
+                )
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = |values: &[f32]| values.iter().all(|value| value.is_finite());
+        match *self {
+            Obstacle::Circle { center, radius } => {
+                if !finite(&[center[0], center[1], radius]) || radius <= 0.0 {
+                    return Err(format!(
+                        "obstacle circle needs a positive radius, got {radius}"
+                    ));
+                }
+            }
+            Obstacle::Rect { min, max } => {
+                if !finite(&[min[0], min[1], max[0], max[1]]) || min[0] > max[0] || min[1] > max[1]
+                {
+                    return Err(format!(
+                        "obstacle rect needs min {min:?} below max {max:?} on both axes"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Unit normal pointing away from the obstacle and how deep a circle at `position` with
+    // `radius` reaches into it, `None` while they don't touch
+    pub fn contact(&self, position: [f32; 2], radius: f32) -> Option<([f32; 2], f32)> {
+        match *self {
+            Obstacle::Circle {
+                center,
+                radius: obstacle_radius,
+            } => {
+                let delta = [position[0] - center[0], position[1] - center[1]];
+                let distance = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+                let depth = radius + obstacle_radius - distance;
+                if depth <= 0.0 {
+                    return None;
+                }
+                let normal = if distance > 0.0 {
+                    [delta[0] / distance, delta[1] / distance]
+                } else {
+                    // Dead centre, push out along x so the outcome stays deterministic
+                    [1.0, 0.0]
+                };
+                Some((normal, depth))
+            }
+            Obstacle::Rect { min, max } => {
+                let closest = [
+                    position[0].clamp(min[0], max[0]),
+                    position[1].clamp(min[1], max[1]),
+                ];
+                let delta = [position[0] - closest[0], position[1] - closest[1]];
+                let distance = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+                if distance > 0.0 {
+                    let depth = radius - distance;
+                    return (depth > 0.0)
+                        .then(|| ([delta[0] / distance, delta[1] / distance], depth));
+                }
+
+                // The centre is inside, leave through the nearest face
+                let faces = [
+                    (position[0] - min[0], [-1.0, 0.0]),
+                    (max[0] - position[0], [1.0, 0.0]),
+                    (position[1] - min[1], [0.0, -1.0]),
+                    (max[1] - position[1], [0.0, 1.0]),
+                ];
+                let (inset, normal) = faces
+                    .into_iter()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap_or((0.0, [1.0, 0.0]));
+                Some((normal, inset + radius))
+            }
+        }
+    }
+}
+
+// Pushes the particle out of the obstacle and reflects the velocity moving into it, keeping the
+// share of it set by `restitution`. Returns whether they were touching.
+pub fn collide_with_obstacle(
+    particle: &mut Particle,
+    obstacle: &Obstacle,
+    restitution: f32,
+) -> bool {
+    let Some((normal, depth)) = obstacle.contact(particle.position, particle.radius) else {
+        return false;
+    };
+    particle.position[0] += normal[0] * depth;
+    particle.position[1] += normal[1] * depth;
+
+    let normal_velocity = particle.velocity[0] * normal[0] + particle.velocity[1] * normal[1];
+    // Only flip velocity heading into the obstacle, so a particle can't get stuck inside
+    if normal_velocity < 0.0 {
+        let change = -(1.0 + restitution) * normal_velocity;
+        particle.velocity[0] += change * normal[0];
+        particle.velocity[1] += change * normal[1];
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::LayoutParticle;
+    use crate::simulation::{CoreState, SimulationConfig};
+
+    #[test]
+    fn particle_bounces_off_a_static_box() {
+        let obstacle = Obstacle::Rect {
+            min: [300.0, 200.0],
+            max: [340.0, 280.0],
+        };
+        let config = SimulationConfig {
+            gravity: [0.0, 0.0],
+            restitution: 0.5,
+            food_spawn_rate: 0.0,
+            obstacles: vec![obstacle],
+            ..SimulationConfig::default()
+        };
+        let layout = vec![LayoutParticle {
+            position: [250.0, 240.0],
+            velocity: [100.0, 0.0],
+            species: 0,
+        }];
+        let mut core_state = CoreState::from_layout(config, layout);
+        for _ in 0..120 {
+            core_state.step(1.0 / 120.0);
+            let particle = &core_state.world().particles()[0];
+            assert!(obstacle
+                .contact(particle.position, particle.radius)
+                .is_none());
+        }
+
+        // Straight back at half the speed, the box didn't go anywhere
+        let particle = &core_state.world().particles()[0];
+        assert!(particle.position[0] < 300.0 - particle.radius);
+        assert!(
+            (particle.velocity[0] + 50.0).abs() < 1e-3,
+            "{:?}",
+            particle.velocity
+        );
+        assert_eq!(particle.velocity[1], 0.0);
+        assert_eq!(core_state.world().obstacles(), [obstacle]);
+
+        // A centre inside leaves through the nearest face, circles push out radially
+        let (normal, depth) = obstacle.contact([338.0, 240.0], 1.0).unwrap();
+        assert_eq!((normal, depth), ([1.0, 0.0], 3.0));
+        let circle = Obstacle::Circle {
+            center: [0.0, 0.0],
+            radius: 10.0,
+        };
+        let (normal, depth) = circle.contact([0.0, 12.0], 3.0).unwrap();
+        assert_eq!((normal, depth), ([0.0, 1.0], 1.0));
+        assert!(Obstacle::Circle {
+            center: [0.0, 0.0],
+            radius: 0.0
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+// Paces frames against a fixed schedule of deadlines instead of sleeping a frame's worth after
+// each one. Work time and sleeps running long are taken out of the next wait, so the rate holds
+// on average instead of drifting below the target.
+pub struct FrameLimiter {
+    // `None` renders as fast as it can
+    interval: Option<Duration>,
+    deadline: Instant,
+}
+
+impl FrameLimiter {
+    // Targets `fps` frames per second, uncapped for `None`
+    pub fn new(fps: Option<u32>) -> Self {
+        Self {
+            interval: fps.map(|fps| Duration::from_secs(1) / fps.max(1)),
+            deadline: Instant::now(),
+        }
+    }
+
+    // Blocks until the next frame is due, call it once at the end of every frame
+    pub fn wait(&mut self) {
+        let delay = self.delay(Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    // How long to wait at `now` for the next deadline, and moves on to the one after it
+    pub fn delay(&mut self, now: Instant) -> Duration {
+        let Some(interval) = self.interval else {
+            return Duration::ZERO;
+        };
+        self.deadline += interval;
+        // More than a frame behind, a burst of frames wouldn't get the lost ones back
+        if self.deadline + interval < now {
+            self.deadline = now;
+        }
+        self.deadline.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_keep_to_their_deadlines() {
+        let mut limiter = FrameLimiter::new(Some(100));
+        let start = limiter.deadline;
+        let ms = Duration::from_millis;
+
+        // 4 ms of work leaves 6 of the 10 ms frame, a sleep overshooting by 2 ms is made up for
+        assert_eq!(limiter.delay(start + ms(4)), ms(6));
+        assert_eq!(limiter.delay(start + ms(12) + ms(4)), ms(4));
+        assert_eq!(limiter.delay(start + ms(20) + ms(4)), ms(6));
+        // A frame running a little long is caught up on right away
+        assert_eq!(limiter.delay(start + ms(43)), ms(0));
+        assert_eq!(limiter.delay(start + ms(45)), ms(5));
+        // One stalling for several frames starts the schedule over
+        assert_eq!(limiter.delay(start + ms(200)), ms(0));
+        assert_eq!(limiter.delay(start + ms(204)), ms(6));
+
+        let mut uncapped = FrameLimiter::new(None);
+        assert_eq!(uncapped.delay(start), Duration::ZERO);
+    }
+}
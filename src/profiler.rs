@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+// Accumulates wall clock time per named span. A disabled profiler never reads the clock, so
+// instrumented code only pays for a branch.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    enabled: bool,
+    // Total time and number of calls per span, in the order they were first recorded
+    spans: RefCell<Vec<(&'static str, Duration, u64)>>,
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // Beginning of a span, hand it to `record` when the span ends
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    // Adds the time since `start` to the span `name`
+    pub fn record(&self, name: &'static str, start: Option<Instant>) {
+        let Some(start) = start else {
+            return;
+        };
+        let elapsed = start.elapsed();
+        let mut spans = self.spans.borrow_mut();
+        match spans.iter_mut().find(|(span, ..)| *span == name) {
+            Some((_, total, calls)) => {
+                *total += elapsed;
+                *calls += 1;
+            }
+            None => spans.push((name, elapsed, 1)),
+        }
+    }
+
+    // Table of every span with its total, average per call and share of the overall time,
+    // `None` if nothing was recorded
+    pub fn report(&self, title: &str) -> Option<String> {
+        let spans = self.spans.borrow();
+        if spans.is_empty() {
+            return None;
+        }
+
+        let overall: Duration = spans.iter().map(|(_, total, _)| *total).sum();
+        let name_width = spans.iter().map(|(name, ..)| name.len()).max().unwrap_or(0);
+        let mut report = format!("{title} ({:.1} ms total)", overall.as_secs_f64() * 1e3);
+        for (name, total, calls) in spans.iter() {
+            let _ = write!(
+                report,
+                "\n  {name:<name_width$}  {:>10.1} ms  {:>8.3} ms/call  {:>5.1}%",
+                total.as_secs_f64() * 1e3,
+                total.as_secs_f64() * 1e3 / *calls as f64,
+                total.as_secs_f64() / overall.as_secs_f64().max(f64::MIN_POSITIVE) * 100.0
+            );
+        }
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_spans_only_while_enabled() {
+        let disabled = Profiler::new(false);
+        let start = disabled.start();
+        assert!(start.is_none());
+        disabled.record("step", start);
+        assert_eq!(disabled.report("Step"), None);
+
+        let profiler = Profiler::new(true);
+        for name in ["forces", "integration", "forces"] {
+            profiler.record(name, profiler.start());
+        }
+        let report = profiler.report("Step").unwrap();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("Step"));
+        assert!(lines[1].trim_start().starts_with("forces"));
+        assert!(lines[2].trim_start().starts_with("integration"));
+        assert_eq!(profiler.spans.borrow()[0].2, 2);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::simulation::Particle;
+
+// Deep enough for any sane distribution, stops coincident particles from splitting forever
+const MAX_DEPTH: u32 = 24;
+
+// Barnes-Hut quadtree, nodes far enough away are treated as a single body at their centre of mass
+#[derive(Default)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    // Particle indices arranged so every node covers a contiguous range
+    order: Vec<usize>,
+}
+
+struct Node {
+    // Edge length of the square this node covers
+    size: f32,
+    mass: f32,
+    center_of_mass: [f32; 2],
+    // Range into `order` of the particles below this node
+    start: usize,
+    end: usize,
+    // Index of the first of four consecutive children, `None` for leaves
+    children: Option<usize>,
+}
+
+impl QuadTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rebuilds the tree from the current particle positions
+    pub fn rebuild(&mut self, particles: &[Particle]) {
+        self.nodes.clear();
+        self.order.clear();
+        self.order.extend(0..particles.len());
+        if particles.is_empty() {
+            return;
+        }
+
+        let mut min = [f32::INFINITY; 2];
+        let mut max = [f32::NEG_INFINITY; 2];
+        for particle in particles {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(particle.position[axis]);
+                max[axis] = max[axis].max(particle.position[axis]);
+            }
+        }
+        let size = (max[0] - min[0]).max(max[1] - min[1]).max(f32::EPSILON);
+
+        self.nodes.push(Node::empty(size, 0, particles.len()));
+        self.build(0, particles, min, 0);
+    }
+
+    // Gravitational acceleration on `index` caused by all other particles.
+    // Smaller `theta` is more accurate, 0 degenerates to the exact all-pairs sum.
+    pub fn acceleration(
+        &self,
+        particles: &[Particle],
+        index: usize,
+        gravitational_constant: f32,
+        theta: f32,
+        softening: f32,
+    ) -> [f32; 2] {
+        let mut acceleration = [0.0, 0.0];
+        if self.nodes.is_empty() {
+            return acceleration;
+        }
+
+        let position = particles[index].position;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.mass <= 0.0 {
+                continue;
+            }
+
+            match node.children {
+                Some(first_child) => {
+                    let distance = distance(position, node.center_of_mass);
+                    if distance > 0.0 && node.size / distance < theta {
+                        accumulate(
+                            &mut acceleration,
+                            position,
+                            node.center_of_mass,
+                            node.mass,
+                            softening,
+                        );
+                    } else {
+                        stack.extend(first_child..first_child + 4);
+                    }
+                }
+                None => {
+                    for &other in &self.order[node.start..node.end] {
+                        if other != index {
+                            let particle = &particles[other];
+                            accumulate(
+                                &mut acceleration,
+                                position,
+                                particle.position,
+                                particle.mass,
+                                softening,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        [
+            acceleration[0] * gravitational_constant,
+            acceleration[1] * gravitational_constant,
+        ]
+    }
+
+    fn build(&mut self, node_index: usize, particles: &[Particle], min: [f32; 2], depth: u32) {
+        let Node {
+            size, start, end, ..
+        } = self.nodes[node_index];
+
+        if end - start > 1 && depth < MAX_DEPTH {
+            let half = size / 2.0;
+            let quadrant = |particle: &Particle| {
+                let right = particle.position[0] >= min[0] + half;
+                let bottom = particle.position[1] >= min[1] + half;
+                right as usize + 2 * bottom as usize
+            };
+            // Stable sort keeps the layout deterministic for a given particle order
+            self.order[start..end].sort_by_key(|&index| quadrant(&particles[index]));
+
+            let first_child = self.nodes.len();
+            let mut child_start = start;
+            for child in 0..4 {
+                let child_end = child_start
+                    + self.order[child_start..end]
+                        .iter()
+                        .take_while(|&&index| quadrant(&particles[index]) == child)
+                        .count();
+                self.nodes.push(Node::empty(half, child_start, child_end));
+                child_start = child_end;
+            }
+            self.nodes[node_index].children = Some(first_child);
+
+            for child in 0..4 {
+                let child_min = [
+                    min[0] + half * (child % 2) as f32,
+                    min[1] + half * (child / 2) as f32,
+                ];
+                self.build(first_child + child, particles, child_min, depth + 1);
+            }
+        }
+
+        let mut mass = 0.0;
+        let mut weighted_position = [0.0, 0.0];
+        for &index in &self.order[start..end] {
+            let particle = &particles[index];
+            mass += particle.mass;
+            weighted_position[0] += particle.position[0] * particle.mass;
+            weighted_position[1] += particle.position[1] * particle.mass;
+        }
+
+        let node = &mut self.nodes[node_index];
+        node.mass = mass;
+        if mass > 0.0 {
+            node.center_of_mass = [weighted_position[0] / mass, weighted_position[1] / mass];
+        }
+    }
+}
+
+impl Node {
+    fn empty(size: f32, start: usize, end: usize) -> Self {
+        Self {
+            size,
+            mass: 0.0,
+            center_of_mass: [0.0, 0.0],
+            start,
+            end,
+            children: None,
+        }
+    }
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+}
+
+// Adds `mass / (r² + softening²)` towards `source`, skipping bodies sitting exactly on top of
+// `position`. Softening caps the pull of close bodies, which otherwise grows without bound.
+fn accumulate(
+    acceleration: &mut [f32; 2],
+    position: [f32; 2],
+    source: [f32; 2],
+    mass: f32,
+    softening: f32,
+) {
+    let delta = [source[0] - position[0], source[1] - position[1]];
+    let distance_squared = delta[0] * delta[0] + delta[1] * delta[1];
+    if distance_squared == 0.0 {
+        return;
+    }
+
+    let softened = distance_squared + softening * softening;
+    let scale = mass / (softened * softened.sqrt());
+    acceleration[0] += delta[0] * scale;
+    acceleration[1] += delta[1] * scale;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genome::Genome;
+    use crate::rng::SimRng;
+
+    #[test]
+    fn barnes_hut_matches_exact_forces() {
+        let mut rng = SimRng::new(3);
+        let particles: Vec<_> = (0..200)
+            .map(|_| Particle {
+                id: 0,
+                species: 0,
+                position: [rng.range_f32(0.0, 500.0), rng.range_f32(0.0, 500.0)],
+                velocity: [0.0, 0.0],
+                acceleration: [0.0, 0.0],
+                lineage_hue: 0.0,
+                mass: rng.range_f32(0.5, 2.0),
+                radius: 1.0,
+                genome: Genome::default(),
+                energy: 0.0,
+                age: 0.0,
+            })
+            .collect();
+
+        let mut tree = QuadTree::new();
+        tree.rebuild(&particles);
+
+        let mut error = 0.0;
+        let mut total = 0.0;
+        for (index, particle) in particles.iter().enumerate() {
+            let mut exact = [0.0, 0.0];
+            for (other_index, other) in particles.iter().enumerate() {
+                if other_index != index {
+                    accumulate(
+                        &mut exact,
+                        particle.position,
+                        other.position,
+                        other.mass,
+                        0.0,
+                    );
+                }
+            }
+            let approximate = tree.acceleration(&particles, index, 1.0, 0.5, 0.0);
+
+            // Compare forces rather than accelerations, so heavier particles weigh in more
+            let force_error = distance(exact, approximate) * particle.mass;
+            error += force_error;
+            total += distance([0.0, 0.0], exact) * particle.mass;
+        }
+
+        assert!(error / total < 0.02, "relative error {}", error / total);
+    }
+}
@@ -0,0 +1,172 @@
+use crossbeam_channel::{unbounded, Sender};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+// Fixed palette of 6 red, 7 green and 6 blue levels, 252 of the 256 colors a GIF allows. Green
+// gets the extra level because the eye is most sensitive to it.
+const RED_LEVELS: u32 = 6;
+const GREEN_LEVELS: u32 = 7;
+const BLUE_LEVELS: u32 = 6;
+
+// Records the first frames of a run into an animated GIF. Frames are quantized and encoded on a
+// background thread, so capturing only costs a copy.
+pub struct GifRecorder {
+    path: PathBuf,
+    frame_len: usize,
+    remaining: u32,
+    frames: Option<Sender<Vec<u8>>>,
+    encoder: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl GifRecorder {
+    // Creates `path` for `frames` frames of `width` x `height` pixels shown at `fps`
+    pub fn create(
+        path: &Path,
+        width: u32,
+        height: u32,
+        fps: u32,
+        frames: u32,
+    ) -> Result<Self, String> {
+        let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(format!("{width}x{height} is too large for a GIF"));
+        };
+        let file = File::create(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        let mut encoder =
+            gif::Encoder::new(BufWriter::new(file), gif_width, gif_height, &palette())
+                .and_then(|mut encoder| encoder.set_repeat(gif::Repeat::Infinite).map(|_| encoder))
+                .map_err(|error| format!("{}: {error}", path.display()))?;
+        // GIF delays count hundredths of a second, browsers treat anything below 2 as slow
+        let delay = (100.0 / fps.max(1) as f32).round().max(2.0) as u16;
+
+        let (sender, receiver) = unbounded::<Vec<u8>>();
+        let display_path = path.display().to_string();
+        let thread = std::thread::spawn(move || {
+            let describe = |error: &dyn std::fmt::Display| format!("{display_path}: {error}");
+            for rgba in receiver {
+                let frame = gif::Frame {
+                    width: gif_width,
+                    height: gif_height,
+                    delay,
+                    buffer: Cow::Owned(quantize(&rgba)),
+                    ..gif::Frame::default()
+                };
+                encoder
+                    .write_frame(&frame)
+                    .map_err(|error| describe(&error))?;
+            }
+            // Writes the trailer, only then is the file a complete GIF
+            encoder
+                .into_inner()
+                .and_then(|mut writer| writer.flush())
+                .map_err(|error| describe(&error))
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            frame_len: width as usize * height as usize * 4,
+            remaining: frames,
+            frames: Some(sender),
+            encoder: Some(thread),
+        })
+    }
+
+    // Queues a copy of `rgba` unless all frames are recorded. A frame of a different size ends
+    // the recording, a GIF can't change its size midway.
+    pub fn capture(&mut self, rgba: &[u8]) {
+        if self.remaining == 0 {
+            return;
+        }
+        if rgba.len() != self.frame_len {
+            eprintln!("Window resized, stopping the GIF recording early");
+            self.remaining = 0;
+        } else if let Some(frames) = &self.frames {
+            // Only fails once the encoder gave up, `finish` reports why
+            let _ = frames.send(rgba.to_vec());
+            self.remaining -= 1;
+        }
+        if self.remaining == 0 {
+            self.frames = None;
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Encodes the queued frames and finalizes the file, also after stopping before all frames
+    // were captured
+    pub fn finish(mut self) -> Result<(), String> {
+        self.frames = None;
+        match self.encoder.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(format!("{}: encoder panicked", self.path.display())),
+            None => Ok(()),
+        }
+    }
+}
+
+// RGB triples of the fixed palette, indexed like `palette_index`
+fn palette() -> Vec<u8> {
+    let level = |index: u32, levels: u32| (index * 255 / (levels - 1)) as u8;
+    let mut palette = Vec::with_capacity((RED_LEVELS * GREEN_LEVELS * BLUE_LEVELS * 3) as usize);
+    for red in 0..RED_LEVELS {
+        for green in 0..GREEN_LEVELS {
+            for blue in 0..BLUE_LEVELS {
+                palette.extend([
+                    level(red, RED_LEVELS),
+                    level(green, GREEN_LEVELS),
+                    level(blue, BLUE_LEVELS),
+                ]);
+            }
+        }
+    }
+    palette
+}
+
+// Nearest palette entry, every channel rounds to its closest level on its own
+fn palette_index(rgba: &[u8]) -> u8 {
+    let level = |channel: u8, levels: u32| (channel as u32 * (levels - 1) + 127) / 255;
+    (level(rgba[0], RED_LEVELS) * GREEN_LEVELS * BLUE_LEVELS
+        + level(rgba[1], GREEN_LEVELS) * BLUE_LEVELS
+        + level(rgba[2], BLUE_LEVELS)) as u8
+}
+
+fn quantize(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4).map(palette_index).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_gif_has_the_requested_frames_and_close_colors() {
+        let path = std::env::temp_dir().join("particle_evolution_recording_test.gif");
+        let mut recorder = GifRecorder::create(&path, 2, 1, 50, 2).unwrap();
+        let frame = [255, 255, 255, 255, 60, 200, 80, 255];
+        for _ in 0..3 {
+            recorder.capture(&frame);
+        }
+        assert_eq!(recorder.remaining, 0);
+        recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        let mut frames = 0;
+        while let Some(decoded) = decoder.read_next_frame().unwrap() {
+            frames += 1;
+            assert_eq!(decoded.delay, 2);
+            assert_eq!(&decoded.buffer[..4], [255; 4]);
+            // Food green ends up on the nearest palette entry
+            for (decoded, original) in decoded.buffer[4..7].iter().zip(&frame[4..7]) {
+                assert!(decoded.abs_diff(*original) <= 26, "{decoded} vs {original}");
+            }
+        }
+        assert_eq!(frames, 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,2667 @@
+use crate::camera::{Camera, CameraMode};
+use crate::font::{self, GLYPH_SIZE};
+use crate::nutrients::NutrientField;
+use crate::obstacle::Obstacle;
+use crate::profiler::Profiler;
+use crate::simulation::{id_hue, CoreState, CoreWorld, Particle, Rect, FOOD_RADIUS};
+use crate::spatial::GridLayout;
+use crate::Renderer;
+use parking_lot::Mutex;
+use pixels::{Pixels, TextureError};
+use serde::Serialize;
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const PARTICLE_COLOR: [u8; 4] = [255, 255, 255, 255];
+const FOOD_COLOR: [u8; 4] = [60, 200, 80, 255];
+const OBSTACLE_COLOR: [u8; 4] = [110, 110, 130, 255];
+// Nutrient cells at the cap, empty ones show the background
+const NUTRIENT_COLOR: [u8; 4] = [30, 90, 40, 255];
+const BOUNDS_COLOR: [u8; 4] = [255, 80, 80, 255];
+const GRID_COLOR: [u8; 4] = [50, 70, 110, 255];
+// Time for a tracking camera to cover about two thirds of the way to its target, smooths out
+// jitter from particles moving frame to frame
+const CAMERA_FOLLOW_TIME: f32 = 0.25;
+// Grid cells narrower than this many pixels on screen aren't drawn, their lines would fill it
+const MIN_SCREEN_GRID_CELL: f32 = 4.0;
+// Frame rate the simulation thread paces itself to without vsync, unless told otherwise
+pub const DEFAULT_FPS: u32 = 60;
+// Smallest radius in pixels anything is drawn with, however far the camera zooms out
+const MIN_SCREEN_RADIUS: f32 = 1.0;
+
+const OVERLAY_TEXT_COLOR: [u8; 4] = [255, 255, 255, 255];
+const CONSOLE_ERROR_COLOR: [u8; 4] = [255, 90, 90, 255];
+// Opacity of the dark box behind the overlay text, keeps it readable on bright frames
+const OVERLAY_BACKGROUND_ALPHA: u32 = 160;
+const OVERLAY_MARGIN: u32 = 4;
+const OVERLAY_PADDING: u32 = 4;
+const OVERLAY_LINE_HEIGHT: u32 = GLYPH_SIZE + 2;
+// Rates shown in the overlay are averaged over windows this long
+const RATE_WINDOW: Duration = Duration::from_millis(500);
+// While rendering is frozen the last frame is presented this often, only to keep the rates in
+// the overlay current
+const FROZEN_REFRESH: Duration = Duration::from_millis(250);
+// Gradient in the color legend, bottom left corner
+const LEGEND_BAR_WIDTH: u32 = 96;
+const LEGEND_BAR_HEIGHT: u32 = 6;
+// Largest size of the minimap in the top right corner, it keeps the aspect ratio of the world
+const MINIMAP_MAX_SIZE: [u32; 2] = [128, 128];
+const VIEWPORT_COLOR: [u8; 4] = [255, 220, 0, 255];
+// Ring around the inspected particle, this many pixels outside of it
+const SELECTION_COLOR: [u8; 4] = [255, 220, 0, 255];
+const SELECTION_GAP: f32 = 3.0;
+// Degrees between the hues generated for species the palette has no color for
+const GOLDEN_ANGLE: f32 = 137.507_77;
+// Share of `max_lifetime` a particle has lived when it starts to fade out
+const AGE_FADE_START: f32 = 0.5;
+// Side in pixels of the square cells the density mode counts particles in
+const DENSITY_CELL_SIZE: u32 = 4;
+
+// Which particle attribute decides its color
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ColorMode {
+    Uniform,
+    // Speed mapped from blue (slowest in frame) to red (fastest in frame)
+    Velocity,
+    // Brightness relative to the most energetic particle in frame
+    Energy,
+    // Hue taken straight from the genome
+    Genome,
+    // Fixed color per species from `SimulationConfig::species_colors`
+    Species,
+    // Stable pseudo-random color per particle from a hash of its id, for following individuals
+    Individual,
+    // Like `Individual` for founders, children take on a color close to their parent's so
+    // families stand out
+    Lineage,
+}
+
+impl ColorMode {
+    // Name of the value colors encode, `None` when they don't encode any
+    pub fn legend_title(self) -> Option<&'static str> {
+        match self {
+            ColorMode::Uniform => None,
+            ColorMode::Velocity => Some("SPEED"),
+            ColorMode::Energy => Some("ENERGY"),
+            ColorMode::Genome => Some("HUE"),
+            ColorMode::Species | ColorMode::Individual | ColorMode::Lineage => None,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ColorMode::Uniform => ColorMode::Velocity,
+            ColorMode::Velocity => ColorMode::Energy,
+            ColorMode::Energy => ColorMode::Genome,
+            ColorMode::Genome => ColorMode::Species,
+            ColorMode::Species => ColorMode::Individual,
+            ColorMode::Individual => ColorMode::Lineage,
+            ColorMode::Lineage => ColorMode::Uniform,
+        }
+    }
+}
+
+// What a frame shows of the world
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum RenderMode {
+    // Every particle and pellet as a circle
+    Particles,
+    // Particles counted per cell and colored from black to white hot, relative to the busiest
+    // cell in frame. Reads better than dots once swarms get large.
+    Density,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Particles => RenderMode::Density,
+            RenderMode::Density => RenderMode::Particles,
+        }
+    }
+}
+
+// How `RenderMode::Particles` draws every particle, food is always drawn as circles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ParticleShape {
+    // Anti-aliased disc
+    Circle,
+    // Square as wide as the disc would be, no blending at the edges
+    Square,
+    // Only the pixel under the centre at any zoom, keeps millions of particles interactive
+    Point,
+}
+
+impl ParticleShape {
+    pub fn next(self) -> Self {
+        match self {
+            ParticleShape::Circle => ParticleShape::Square,
+            ParticleShape::Square => ParticleShape::Point,
+            ParticleShape::Point => ParticleShape::Circle,
+        }
+    }
+}
+
+// Resolution of the frame buffer, shared by the pixels surface and the simulation thread
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RenderConfig {
+    pub width: u32,
+    pub height: u32,
+    // Frames the simulation thread rasterizes per second, only enforced while vsync is off.
+    // `None` renders as fast as it can.
+    pub fps: Option<u32>,
+    // Present in step with the display refresh. No tearing and no manual pacing, but the frame
+    // rate follows the monitor instead of `fps`. Off presents immediately and paces with `fps`.
+    pub vsync: bool,
+    pub render_mode: RenderMode,
+    pub particle_shape: ParticleShape,
+    pub color_mode: ColorMode,
+    // Share of the previous frame kept under the new one, 0.0 clears every frame and values
+    // close to 1.0 leave long trails behind moving particles
+    pub trail_decay: f32,
+    // RGBA every frame is cleared to, or trails fade towards
+    pub background: [u8; 4],
+    // Frame rate, tick rate and particle count in the top left corner
+    pub show_overlay: bool,
+    // Overview of the whole world with the visible part outlined, in the top right corner
+    pub show_minimap: bool,
+    // A line from every particle along its velocity, for seeing how things move
+    pub show_velocity_vectors: bool,
+    // Seconds of motion a velocity vector covers, its length in world units per unit of speed
+    pub velocity_scale: f32,
+    // Outline of the world bounds
+    pub show_bounds: bool,
+    // Cell lines of the collision grid, for debugging the broadphase
+    pub show_grid: bool,
+    // Nutrient field as a heatmap under everything else, in place of the background
+    pub show_nutrients: bool,
+}
+
+impl RenderConfig {
+    pub fn builder() -> RenderConfigBuilder {
+        RenderConfigBuilder::default()
+    }
+
+    pub fn screen_size(&self) -> [f32; 2] {
+        [self.width as f32, self.height as f32]
+    }
+
+    // Neither vsync nor the frame limiter hold frames back, e.g. for `--uncapped`
+    pub fn uncapped(&self) -> bool {
+        !self.vsync && self.fps.is_none()
+    }
+
+    // Length of an RGBA frame buffer at this resolution
+    pub fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            fps: Some(DEFAULT_FPS),
+            vsync: true,
+            render_mode: RenderMode::Particles,
+            particle_shape: ParticleShape::Circle,
+            color_mode: ColorMode::Uniform,
+            trail_decay: 0.0,
+            background: [0, 0, 0, 0],
+            show_overlay: false,
+            show_minimap: false,
+            show_velocity_vectors: false,
+            velocity_scale: 0.1,
+            show_bounds: false,
+            show_grid: false,
+            show_nutrients: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RenderConfigBuilder {
+    config: RenderConfig,
+}
+
+impl RenderConfigBuilder {
+    pub fn width(mut self, width: u32) -> Self {
+        self.config.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: u32) -> Self {
+        self.config.height = height;
+        self
+    }
+
+    pub fn fps(mut self, fps: Option<u32>) -> Self {
+        self.config.fps = fps;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.config.vsync = vsync;
+        self
+    }
+
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.config.render_mode = render_mode;
+        self
+    }
+
+    pub fn particle_shape(mut self, particle_shape: ParticleShape) -> Self {
+        self.config.particle_shape = particle_shape;
+        self
+    }
+
+    pub fn trail_decay(mut self, trail_decay: f32) -> Self {
+        self.config.trail_decay = trail_decay;
+        self
+    }
+
+    pub fn background(mut self, background: [u8; 4]) -> Self {
+        self.config.background = background;
+        self
+    }
+
+    pub fn show_overlay(mut self, show_overlay: bool) -> Self {
+        self.config.show_overlay = show_overlay;
+        self
+    }
+
+    pub fn velocity_scale(mut self, velocity_scale: f32) -> Self {
+        self.config.velocity_scale = velocity_scale;
+        self
+    }
+
+    pub fn build(self) -> RenderConfig {
+        self.config
+    }
+}
+
+// Everything needed to draw one frame, detached from the simulation so rasterizing never
+// has to look at `CoreState`. Kept as flat arrays of plain values, a snapshot costs a fraction
+// of copying the particles themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderWorld {
+    pub positions: Vec<[f32; 2]>,
+    pub velocities: Vec<[f32; 2]>,
+    pub radii: Vec<f32>,
+    // RGBA packed into one `u32` per particle, see `pack_rgba`
+    pub colors: Vec<u32>,
+    pub food: Vec<[f32; 2]>,
+    pub obstacles: Vec<Obstacle>,
+    pub color_mode: ColorMode,
+    // Values mapped to the two ends of the colormap this frame, labels the legend
+    pub color_range: (f32, f32),
+    pub bounds: Rect,
+    pub camera: Camera,
+    pub tick: u64,
+    // Position and radius of the inspected particle while it is alive
+    pub selected: Option<([f32; 2], f32)>,
+    // Current generation and its mean energy so far, see `GenerationTracker`
+    pub generation: (u64, Option<f32>),
+    // Cells of the collision grid, `None` when it isn't drawn or there is none
+    pub grid: Option<GridLayout>,
+    // Area dragged out for deletion
+    pub region: Option<Rect>,
+    // Nutrient field with every cell as a share of the cap, `None` when it isn't drawn
+    pub nutrients: Option<NutrientField>,
+}
+
+impl RenderWorld {
+    // Colors are resolved here, they may depend on the whole population (e.g. the speed range).
+    // `palette` holds the species colors, see `SimulationConfig::species_colors`.
+    pub fn extract(
+        world: &CoreWorld,
+        tick: u64,
+        color_mode: ColorMode,
+        palette: &[[u8; 3]],
+        camera: Camera,
+    ) -> Self {
+        let mut render_world = Self {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            radii: Vec::new(),
+            colors: Vec::new(),
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode,
+            color_range: (0.0, 0.0),
+            bounds: world.bounds().clone(),
+            camera,
+            tick,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        render_world.refill(world, tick, color_mode, palette, camera);
+        render_world
+    }
+
+    // Blends the colors of `particles`, the ones this was extracted from, into `background` as
+    // they near `max_lifetime`. They are fully faded out right when they die of old age.
+    pub fn fade_by_age(&mut self, particles: &[Particle], max_lifetime: f32, background: [u8; 4]) {
+        let fade_time = max_lifetime * (1.0 - AGE_FADE_START);
+        for (color, particle) in self.colors.iter_mut().zip(particles) {
+            let opacity = ((max_lifetime - particle.age) / fade_time).clamp(0.0, 1.0);
+            if opacity == 1.0 {
+                continue;
+            }
+            let mut rgba = color.to_le_bytes();
+            for (channel, &back) in rgba.iter_mut().zip(&background).take(3) {
+                *channel = (back as f32 + (*channel as f32 - back as f32) * opacity).round() as u8;
+            }
+            *color = pack_rgba(rgba);
+        }
+    }
+
+    // Same as `extract`, but reuses the buffers of this snapshot so a steady state doesn't allocate
+    pub fn refill(
+        &mut self,
+        world: &CoreWorld,
+        tick: u64,
+        color_mode: ColorMode,
+        palette: &[[u8; 3]],
+        camera: Camera,
+    ) {
+        let particles = world.particles();
+        let color_range = match color_mode {
+            ColorMode::Uniform => (0.0, 0.0),
+            ColorMode::Velocity => value_range(particles.iter().map(speed)),
+            ColorMode::Energy => (
+                0.0,
+                value_range(particles.iter().map(|particle| particle.energy)).1,
+            ),
+            ColorMode::Genome | ColorMode::Individual | ColorMode::Lineage => (0.0, 360.0),
+            ColorMode::Species => (0.0, 0.0),
+        };
+
+        self.positions.clear();
+        self.positions
+            .extend(particles.iter().map(|particle| particle.position));
+        self.velocities.clear();
+        self.velocities
+            .extend(particles.iter().map(|particle| particle.velocity));
+        self.radii.clear();
+        self.radii
+            .extend(particles.iter().map(|particle| particle.radius));
+        self.colors.clear();
+        self.colors.extend(particles.iter().map(|particle| {
+            let color = match color_mode {
+                ColorMode::Species => species_color(palette, particle.species),
+                _ => particle_color(particle, color_mode, color_range),
+            };
+            pack_rgba(color)
+        }));
+        self.food.clear();
+        self.food
+            .extend(world.food().iter().map(|pellet| pellet.position));
+        self.obstacles.clear();
+        self.obstacles.extend_from_slice(world.obstacles());
+        self.color_mode = color_mode;
+        self.color_range = color_range;
+        self.bounds = world.bounds().clone();
+        self.camera = camera;
+        self.tick = tick;
+    }
+}
+
+// Fades `frame` by the trail decay and draws the obstacles and food, then the particles on top
+// so they stay visible while sitting on a pellet
+pub fn rasterize(world: &RenderWorld, frame: &mut [u8], config: &RenderConfig) {
+    debug_assert_eq!(frame.len(), config.frame_len());
+    fade(frame, config.trail_decay, config.background);
+    if let Some(field) = &world.nutrients {
+        draw_nutrients(field, frame, config, world.camera);
+    }
+    draw_obstacles(world, frame, config);
+
+    let screen_size = config.screen_size();
+    // Sizes are in world units like positions, zoomed out far they still show up as a dot
+    let screen_radius = |radius: f32| (radius * world.camera.zoom).max(MIN_SCREEN_RADIUS);
+    for &position in &world.food {
+        fill_circle(
+            frame,
+            config.width,
+            config.height,
+            world.camera.world_to_screen(position, screen_size),
+            screen_radius(FOOD_RADIUS),
+            FOOD_COLOR,
+        );
+    }
+    for ((&position, &radius), &color) in
+        world.positions.iter().zip(&world.radii).zip(&world.colors)
+    {
+        let center = world.camera.world_to_screen(position, screen_size);
+        let color = color.to_le_bytes();
+        match config.particle_shape {
+            ParticleShape::Circle => fill_circle(
+                frame,
+                config.width,
+                config.height,
+                center,
+                screen_radius(radius),
+                color,
+            ),
+            ParticleShape::Square => {
+                let half = screen_radius(radius);
+                fill_rect(
+                    frame,
+                    config.width,
+                    config.height,
+                    [center[0] - half, center[1] - half],
+                    [center[0] + half, center[1] + half],
+                    color,
+                );
+            }
+            ParticleShape::Point => plot(frame, config.width, config.height, center, color),
+        }
+    }
+}
+
+// Fades `frame` like `rasterize` and draws the nutrients and obstacles, then the particle count of
+// every cell on top, from black for a single particle to white for the busiest cell in frame.
+// Cells fit the frame from its top left corner. `density` holds the counts and keeps its capacity
+// between frames.
+pub fn rasterize_density(
+    world: &RenderWorld,
+    frame: &mut [u8],
+    config: &RenderConfig,
+    density: &mut Vec<f32>,
+) {
+    debug_assert_eq!(frame.len(), config.frame_len());
+    fade(frame, config.trail_decay, config.background);
+    if let Some(field) = &world.nutrients {
+        draw_nutrients(field, frame, config, world.camera);
+    }
+    draw_obstacles(world, frame, config);
+
+    let columns = config.width.div_ceil(DENSITY_CELL_SIZE) as usize;
+    let rows = config.height.div_ceil(DENSITY_CELL_SIZE) as usize;
+    density.clear();
+    density.resize(columns * rows, 0.0);
+    let screen_size = config.screen_size();
+    for &position in &world.positions {
+        let [x, y] = world.camera.world_to_screen(position, screen_size);
+        if (0.0..screen_size[0]).contains(&x) && (0.0..screen_size[1]).contains(&y) {
+            let cell = [x as u32 / DENSITY_CELL_SIZE, y as u32 / DENSITY_CELL_SIZE];
+            density[cell[1] as usize * columns + cell[0] as usize] += 1.0;
+        }
+    }
+
+    let max = density.iter().copied().fold(0.0, f32::max);
+    if max == 0.0 {
+        return;
+    }
+    let width = config.width as usize;
+    for (index, &count) in density.iter().enumerate() {
+        if count == 0.0 {
+            continue;
+        }
+        let color = heat(count / max);
+        let (column, row) = (index % columns, index / columns);
+        let x_range = column * DENSITY_CELL_SIZE as usize
+            ..((column + 1) * DENSITY_CELL_SIZE as usize).min(width);
+        let y_range = row * DENSITY_CELL_SIZE as usize
+            ..((row + 1) * DENSITY_CELL_SIZE as usize).min(config.height as usize);
+        for y in y_range {
+            let row_pixels =
+                &mut frame[(y * width + x_range.start) * 4..(y * width + x_range.end) * 4];
+            for pixel in row_pixels.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+// Every cell blended from the background to `NUTRIENT_COLOR` by its share of the cap. Trails
+// left on top of the field are painted over, they only show outside of it.
+fn draw_nutrients(field: &NutrientField, frame: &mut [u8], config: &RenderConfig, camera: Camera) {
+    let screen_size = config.screen_size();
+    for row in 0..field.rows {
+        for column in 0..field.columns {
+            let share = field.concentrations[row * field.columns + column].clamp(0.0, 1.0);
+            let min = [
+                field.origin[0] + column as f32 * field.cell_size,
+                field.origin[1] + row as f32 * field.cell_size,
+            ];
+            let max = [min[0] + field.cell_size, min[1] + field.cell_size];
+            let mut color = config.background;
+            for (channel, &full) in color.iter_mut().zip(&NUTRIENT_COLOR) {
+                *channel =
+                    (*channel as f32 + (full as f32 - *channel as f32) * share).round() as u8;
+            }
+            fill_rect(
+                frame,
+                config.width,
+                config.height,
+                camera.world_to_screen(min, screen_size),
+                camera.world_to_screen(max, screen_size),
+                color,
+            );
+        }
+    }
+}
+
+fn draw_obstacles(world: &RenderWorld, frame: &mut [u8], config: &RenderConfig) {
+    let screen_size = config.screen_size();
+    for obstacle in &world.obstacles {
+        match *obstacle {
+            Obstacle::Circle { center, radius } => fill_circle(
+                frame,
+                config.width,
+                config.height,
+                world.camera.world_to_screen(center, screen_size),
+                (radius * world.camera.zoom).max(MIN_SCREEN_RADIUS),
+                OBSTACLE_COLOR,
+            ),
+            Obstacle::Rect { min, max } => fill_rect(
+                frame,
+                config.width,
+                config.height,
+                world.camera.world_to_screen(min, screen_size),
+                world.camera.world_to_screen(max, screen_size),
+                OBSTACLE_COLOR,
+            ),
+        }
+    }
+}
+
+// Scales every channel toward the `background` by `decay`. Fixed point with a factor below 256
+// and rounding towards the background, so each channel that differs gets at least one step
+// closer per frame and trails always fade out completely instead of leaving ghost pixels.
+pub fn fade(frame: &mut [u8], decay: f32, background: [u8; 4]) {
+    let factor = (decay.clamp(0.0, 1.0) * 256.0).min(255.0) as u32;
+    for pixel in frame.chunks_exact_mut(4) {
+        if factor == 0 {
+            pixel.copy_from_slice(&background);
+            continue;
+        }
+        for (channel, &target) in pixel.iter_mut().zip(&background) {
+            let (value, target) = (*channel as u32, target as u32);
+            *channel = if value >= target {
+                target + (((value - target) * factor) >> 8)
+            } else {
+                target - (((target - value) * factor) >> 8)
+            } as u8;
+        }
+    }
+}
+
+// Average for the overlay, a dash while there is nothing to average
+pub fn format_mean(mean: Option<f32>) -> String {
+    mean.map_or_else(|| "—".to_string(), |mean| format!("{mean:.1}"))
+}
+
+// Draws `lines` of text over a translucent dark box in the top left corner
+pub fn draw_overlay(frame: &mut [u8], config: &RenderConfig, lines: &[String]) {
+    let text_width = lines
+        .iter()
+        .map(|line| font::text_width(line))
+        .max()
+        .unwrap_or(0);
+    let box_width = text_width + 2 * OVERLAY_PADDING;
+    let box_height = lines.len() as u32 * OVERLAY_LINE_HEIGHT + 2 * OVERLAY_PADDING;
+    darken_box(
+        frame,
+        config,
+        [OVERLAY_MARGIN, OVERLAY_MARGIN],
+        [box_width, box_height],
+    );
+
+    let text_origin = (OVERLAY_MARGIN + OVERLAY_PADDING) as i64;
+    for (index, line) in lines.iter().enumerate() {
+        font::draw_text(
+            frame,
+            config.width,
+            config.height,
+            [
+                text_origin,
+                text_origin + (index as u32 * OVERLAY_LINE_HEIGHT) as i64,
+            ],
+            line,
+            OVERLAY_TEXT_COLOR,
+        );
+    }
+}
+
+// Gradient of the current colormap labelled with this frame's range, in the bottom left corner.
+// Draws nothing for colors that don't encode a value.
+pub fn draw_legend(
+    frame: &mut [u8],
+    config: &RenderConfig,
+    color_mode: ColorMode,
+    (min, max): (f32, f32),
+) {
+    let Some(title) = color_mode.legend_title() else {
+        return;
+    };
+    let (min_label, max_label) = (legend_label(min), legend_label(max));
+    let labels_width = font::text_width(&min_label) + GLYPH_SIZE + font::text_width(&max_label);
+    let content_width = LEGEND_BAR_WIDTH
+        .max(font::text_width(title))
+        .max(labels_width);
+    let box_width = content_width + 2 * OVERLAY_PADDING;
+    let box_height = 2 * OVERLAY_LINE_HEIGHT + LEGEND_BAR_HEIGHT + 2 + 2 * OVERLAY_PADDING;
+    // Signed so a frame shorter than the legend clips its top instead of underflowing
+    let top = config.height as i64 - (OVERLAY_MARGIN + box_height) as i64;
+    darken_box(
+        frame,
+        config,
+        [OVERLAY_MARGIN, top.max(0) as u32],
+        [box_width, (box_height as i64 + top.min(0)) as u32],
+    );
+
+    let left = (OVERLAY_MARGIN + OVERLAY_PADDING) as i64;
+    let title_y = top + OVERLAY_PADDING as i64;
+    let bar_y = title_y + OVERLAY_LINE_HEIGHT as i64;
+    let labels_y = bar_y + LEGEND_BAR_HEIGHT as i64 + 2;
+    let (width, height) = (config.width as i64, config.height as i64);
+    for column in 0..LEGEND_BAR_WIDTH as i64 {
+        let x = left + column;
+        if x >= width {
+            break;
+        }
+        let color = colormap(color_mode, column as f32 / (LEGEND_BAR_WIDTH - 1) as f32);
+        for y in (bar_y..bar_y + LEGEND_BAR_HEIGHT as i64).filter(|y| (0..height).contains(y)) {
+            let offset = (y * width + x) as usize * 4;
+            frame[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+
+    let max_x =
+        left + LEGEND_BAR_WIDTH.max(labels_width) as i64 - font::text_width(&max_label) as i64;
+    for (origin, text) in [
+        ([left, title_y], title),
+        ([left, labels_y], min_label.as_str()),
+        ([max_x, labels_y], max_label.as_str()),
+    ] {
+        font::draw_text(
+            frame,
+            config.width,
+            config.height,
+            origin,
+            text,
+            OVERLAY_TEXT_COLOR,
+        );
+    }
+}
+
+// Console along the bottom edge, the input line with a cursor and the outcome of the last
+// command above it, errors in red
+pub fn draw_console(
+    frame: &mut [u8],
+    config: &RenderConfig,
+    input: &str,
+    result: Option<&Result<String, String>>,
+) {
+    let lines = result.map_or(1, |_| 2);
+    let box_height = lines * OVERLAY_LINE_HEIGHT + 2 * OVERLAY_PADDING;
+    let Some(top) = config.height.checked_sub(OVERLAY_MARGIN + box_height) else {
+        return;
+    };
+    let box_width = config.width.saturating_sub(2 * OVERLAY_MARGIN);
+    darken_box(
+        frame,
+        config,
+        [OVERLAY_MARGIN, top],
+        [box_width, box_height],
+    );
+
+    let x = (OVERLAY_MARGIN + OVERLAY_PADDING) as i64;
+    let mut y = (top + OVERLAY_PADDING) as i64;
+    if let Some(result) = result {
+        let (text, color) = match result {
+            Ok(text) => (text, OVERLAY_TEXT_COLOR),
+            Err(text) => (text, CONSOLE_ERROR_COLOR),
+        };
+        font::draw_text(frame, config.width, config.height, [x, y], text, color);
+        y += OVERLAY_LINE_HEIGHT as i64;
+    }
+    font::draw_text(
+        frame,
+        config.width,
+        config.height,
+        [x, y],
+        &format!("/{input}_"),
+        OVERLAY_TEXT_COLOR,
+    );
+}
+
+// Outlines the selected particle with a ring that stays visible at any zoom
+pub fn draw_selection(frame: &mut [u8], config: &RenderConfig, world: &RenderWorld) {
+    let Some((position, radius)) = world.selected else {
+        return;
+    };
+    let center = world.camera.world_to_screen(position, config.screen_size());
+    let ring = (radius * world.camera.zoom).max(MIN_SCREEN_RADIUS) + SELECTION_GAP;
+    let (width, height) = (config.width as i64, config.height as i64);
+    // Saturating casts, a selection far off-screen yields empty ranges
+    let x_range = ((center[0] - ring - 1.0).floor() as i64).max(0)
+        ..=((center[0] + ring + 1.0).ceil() as i64).min(width - 1);
+    for y in ((center[1] - ring - 1.0).floor() as i64).max(0)
+        ..=((center[1] + ring + 1.0).ceil() as i64).min(height - 1)
+    {
+        for x in x_range.clone() {
+            let dx = x as f32 + 0.5 - center[0];
+            let dy = y as f32 + 0.5 - center[1];
+            if ((dx * dx + dy * dy).sqrt() - ring).abs() < 0.75 {
+                let offset = ((y * width + x) * 4) as usize;
+                frame[offset..offset + 4].copy_from_slice(&SELECTION_COLOR);
+            }
+        }
+    }
+}
+
+// Collision grid and world bounds outline as far as `config` enables them, the grid comes from
+// `RenderWorld::grid`. The region selected for deletion is always outlined.
+pub fn draw_debug_lines(frame: &mut [u8], config: &RenderConfig, world: &RenderWorld) {
+    let screen_size = config.screen_size();
+    let mut line = |start: [f32; 2], end: [f32; 2], color: [u8; 4]| {
+        draw_line(
+            frame,
+            config.width,
+            config.height,
+            world.camera.world_to_screen(start, screen_size),
+            world.camera.world_to_screen(end, screen_size),
+            color,
+        );
+    };
+
+    if let Some(grid) = world.grid.filter(|grid| {
+        grid.cell_size[0].min(grid.cell_size[1]) * world.camera.zoom >= MIN_SCREEN_GRID_CELL
+    }) {
+        let corner = |column: usize, row: usize| {
+            [
+                grid.origin[0] + column as f32 * grid.cell_size[0],
+                grid.origin[1] + row as f32 * grid.cell_size[1],
+            ]
+        };
+        for column in 0..=grid.columns {
+            line(corner(column, 0), corner(column, grid.rows), GRID_COLOR);
+        }
+        for row in 0..=grid.rows {
+            line(corner(0, row), corner(grid.columns, row), GRID_COLOR);
+        }
+    }
+
+    if config.show_bounds {
+        let Rect { min, max } = world.bounds;
+        let corners = [min, [max[0], min[1]], max, [min[0], max[1]], min];
+        for edge in corners.windows(2) {
+            line(edge[0], edge[1], BOUNDS_COLOR);
+        }
+    }
+
+    if let Some(Rect { min, max }) = world.region {
+        let corners = [min, [max[0], min[1]], max, [min[0], max[1]], min];
+        for edge in corners.windows(2) {
+            line(edge[0], edge[1], SELECTION_COLOR);
+        }
+    }
+}
+
+// A line from the centre of every particle in the direction it moves, `velocity_scale` seconds
+// of motion long, in the particle's color
+pub fn draw_velocity_vectors(frame: &mut [u8], config: &RenderConfig, world: &RenderWorld) {
+    let screen_size = config.screen_size();
+    for ((&position, &velocity), &color) in world
+        .positions
+        .iter()
+        .zip(&world.velocities)
+        .zip(&world.colors)
+    {
+        let tip = [
+            position[0] + velocity[0] * config.velocity_scale,
+            position[1] + velocity[1] * config.velocity_scale,
+        ];
+        draw_line(
+            frame,
+            config.width,
+            config.height,
+            world.camera.world_to_screen(position, screen_size),
+            world.camera.world_to_screen(tip, screen_size),
+            color.to_le_bytes(),
+        );
+    }
+}
+
+// Every particle as a single pixel on a scaled down map of the world, with the part the camera
+// shows outlined. Skipped when the frame is too small to fit it.
+pub fn draw_minimap(frame: &mut [u8], config: &RenderConfig, world: &RenderWorld) {
+    let world_size = [
+        world.bounds.max[0] - world.bounds.min[0],
+        world.bounds.max[1] - world.bounds.min[1],
+    ];
+    if world_size[0] <= 0.0 || world_size[1] <= 0.0 {
+        return;
+    }
+    let scale = (MINIMAP_MAX_SIZE[0] as f32 / world_size[0])
+        .min(MINIMAP_MAX_SIZE[1] as f32 / world_size[1]);
+    let size = [
+        ((world_size[0] * scale) as u32).max(1),
+        ((world_size[1] * scale) as u32).max(1),
+    ];
+    if size[0] + 2 * OVERLAY_MARGIN > config.width || size[1] + 2 * OVERLAY_MARGIN > config.height {
+        return;
+    }
+    let origin = [config.width - OVERLAY_MARGIN - size[0], OVERLAY_MARGIN];
+    darken_box(frame, config, origin, size);
+
+    // Minimap pixel of a world position, `None` outside the map
+    let to_map = |position: [f32; 2]| {
+        let x = ((position[0] - world.bounds.min[0]) * scale).floor();
+        let y = ((position[1] - world.bounds.min[1]) * scale).floor();
+        (x >= 0.0 && y >= 0.0 && x < size[0] as f32 && y < size[1] as f32)
+            .then_some([x as u32, y as u32])
+    };
+    let mut plot = |[x, y]: [u32; 2], color: [u8; 4]| {
+        let offset = (((origin[1] + y) * config.width + origin[0] + x) * 4) as usize;
+        frame[offset..offset + 4].copy_from_slice(&color);
+    };
+
+    let stride = minimap_stride(world.positions.len(), size);
+    for (position, color) in world.positions.iter().zip(&world.colors).step_by(stride) {
+        if let Some(pixel) = to_map(*position) {
+            plot(pixel, color.to_le_bytes());
+        }
+    }
+
+    // Viewport outline, clipped to the map when the camera looks past the edge of the world
+    let screen_size = config.screen_size();
+    let top_left = world.camera.screen_to_world([0.0, 0.0], screen_size);
+    let bottom_right = world.camera.screen_to_world(screen_size, screen_size);
+    let map_coordinate = |value: f32, axis: usize| {
+        ((value - world.bounds.min[axis]) * scale)
+            .floor()
+            .clamp(0.0, (size[axis] - 1) as f32) as u32
+    };
+    let (left, right) = (
+        map_coordinate(top_left[0], 0),
+        map_coordinate(bottom_right[0], 0),
+    );
+    let (top, bottom) = (
+        map_coordinate(top_left[1], 1),
+        map_coordinate(bottom_right[1], 1),
+    );
+    for x in left..=right {
+        plot([x, top], VIEWPORT_COLOR);
+        plot([x, bottom], VIEWPORT_COLOR);
+    }
+    for y in top..=bottom {
+        plot([left, y], VIEWPORT_COLOR);
+        plot([right, y], VIEWPORT_COLOR);
+    }
+}
+
+// Every how many particles one is drawn on a minimap of `size`. Beyond one per pixel they
+// can't be told apart, so drawing all of them would only cost time.
+fn minimap_stride(particles: usize, size: [u32; 2]) -> usize {
+    particles
+        .div_ceil(size[0] as usize * size[1] as usize)
+        .max(1)
+}
+
+// Small values keep a decimal, the legend would show 0 to 1 for slow particles otherwise
+fn legend_label(value: f32) -> String {
+    if value.abs() < 10.0 {
+        format!("{value:.1}")
+    } else {
+        format!("{value:.0}")
+    }
+}
+
+// Darkens the RGB of a `size` box at `origin` behind text, clipped to the frame
+fn darken_box(frame: &mut [u8], config: &RenderConfig, origin: [u32; 2], size: [u32; 2]) {
+    let x_end = (origin[0] + size[0]).min(config.width);
+    let y_end = (origin[1] + size[1]).min(config.height);
+    for y in origin[1].min(y_end)..y_end {
+        let row = (y * config.width) as usize;
+        let start = (row + origin[0].min(x_end) as usize) * 4;
+        let end = (row + x_end as usize) * 4;
+        for pixel in frame[start..end].chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as u32 * (255 - OVERLAY_BACKGROUND_ALPHA) / 255) as u8;
+            }
+        }
+    }
+}
+
+// Frames and ticks per second, averaged over windows of `RATE_WINDOW`
+struct RateMeter {
+    window_start: Instant,
+    frames: u32,
+    ticks: u64,
+    last_tick: u64,
+    fps: f32,
+    tps: f32,
+}
+
+impl RateMeter {
+    fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            frames: 0,
+            ticks: 0,
+            last_tick: 0,
+            fps: 0.0,
+            tps: 0.0,
+        }
+    }
+
+    // Counts a frame showing the world at `tick`
+    fn record(&mut self, tick: u64) {
+        self.frames += 1;
+        self.record_ticks(tick);
+    }
+
+    // Counts the ticks up to `tick` without a frame, while rendering is frozen
+    fn record_ticks(&mut self, tick: u64) {
+        // Resetting the world starts over at tick 0, which isn't negative progress
+        self.ticks += tick.saturating_sub(self.last_tick);
+        self.last_tick = tick;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            let secs = elapsed.as_secs_f32();
+            self.fps = self.frames as f32 / secs;
+            self.tps = self.ticks as f32 / secs;
+            self.window_start = Instant::now();
+            self.frames = 0;
+            self.ticks = 0;
+        }
+    }
+}
+
+// Byte order matches the frame buffer, so `to_le_bytes` gives the color back
+fn pack_rgba(color: [u8; 4]) -> u32 {
+    u32::from_le_bytes(color)
+}
+
+// Draws into the pixels frame buffer and presents it, lives on the simulation thread
+pub struct WinitRenderer {
+    pixels: Pixels,
+    config: RenderConfig,
+    // Written by the main thread on mouse input
+    camera: Arc<Mutex<Camera>>,
+    camera_mode: CameraMode,
+    // When a tracking camera last moved, `None` while it is manual
+    last_follow: Cell<Option<Instant>>,
+    rate_meter: RateMeter,
+    // Snapshot from the previous frame, refilled by the next extraction instead of reallocated
+    spare_world: Cell<Option<RenderWorld>>,
+    // While trails are on the world is drawn here and copied into the frame, so the overlay
+    // from the previous frame isn't faded into the next one
+    trail_canvas: Vec<u8>,
+    // Particle counts per cell in density mode, see `rasterize_density`
+    density: Vec<f32>,
+    // Time spent in each phase of a frame, disabled unless asked for
+    profiler: Profiler,
+    // Id of the particle picked for inspection
+    selected: Option<u64>,
+    // Area dragged out with shift, in world coordinates
+    region: Option<Rect>,
+    // Last frame before the overlay while rendering is frozen, see `toggle_freeze`
+    frozen: Option<Vec<u8>>,
+    // The next frame is kept and rendering freezes after it
+    freeze_pending: bool,
+    // When the frozen frame was last presented
+    last_frozen_present: Option<Instant>,
+    // Line typed into the console while it is open, and the outcome of the last command
+    console_input: Option<String>,
+    console_result: Option<Result<String, String>>,
+    failed: bool,
+}
+
+impl WinitRenderer {
+    pub fn new(pixels: Pixels, config: RenderConfig, camera: Arc<Mutex<Camera>>) -> Self {
+        Self {
+            pixels,
+            config,
+            camera,
+            camera_mode: CameraMode::Manual,
+            last_follow: Cell::new(None),
+            rate_meter: RateMeter::new(),
+            spare_world: Cell::new(None),
+            trail_canvas: Vec::new(),
+            density: Vec::new(),
+            profiler: Profiler::default(),
+            selected: None,
+            region: None,
+            frozen: None,
+            freeze_pending: false,
+            last_frozen_present: None,
+            console_input: None,
+            console_result: None,
+            failed: false,
+        }
+    }
+
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+
+    pub fn cycle_color_mode(&mut self) {
+        self.config.color_mode = self.config.color_mode.next();
+    }
+
+    pub fn cycle_render_mode(&mut self) {
+        self.config.render_mode = self.config.render_mode.next();
+    }
+
+    pub fn cycle_particle_shape(&mut self) {
+        self.config.particle_shape = self.config.particle_shape.next();
+    }
+
+    pub fn cycle_camera_mode(&mut self) -> CameraMode {
+        self.camera_mode = self.camera_mode.next();
+        self.last_follow.set(None);
+        self.camera_mode
+    }
+
+    pub fn toggle_overlay(&mut self) {
+        self.config.show_overlay = !self.config.show_overlay;
+    }
+
+    pub fn toggle_minimap(&mut self) {
+        self.config.show_minimap = !self.config.show_minimap;
+    }
+
+    pub fn toggle_velocity_vectors(&mut self) {
+        self.config.show_velocity_vectors = !self.config.show_velocity_vectors;
+    }
+
+    pub fn toggle_bounds(&mut self) {
+        self.config.show_bounds = !self.config.show_bounds;
+    }
+
+    pub fn toggle_grid(&mut self) {
+        self.config.show_grid = !self.config.show_grid;
+    }
+
+    pub fn toggle_nutrients(&mut self) {
+        self.config.show_nutrients = !self.config.show_nutrients;
+    }
+
+    // Stops drawing the world after the next frame, which stays on screen while the simulation
+    // keeps going, or resumes drawing. Returns whether rendering is frozen from now on.
+    pub fn toggle_freeze(&mut self) -> bool {
+        let freezing = self.frozen.is_none() && !self.freeze_pending;
+        self.frozen = None;
+        self.freeze_pending = freezing;
+        self.last_frozen_present = None;
+        freezing
+    }
+
+    // Whether frames come from `render_frozen` instead of the world
+    pub fn frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
+    // Shows the frozen frame again with a fresh overlay, every `FROZEN_REFRESH` at most. Nothing
+    // is extracted from `core_state`, it only provides the numbers of the overlay.
+    pub fn render_frozen(&mut self, core_state: &CoreState) {
+        self.rate_meter.record_ticks(core_state.tick());
+        if self
+            .last_frozen_present
+            .is_some_and(|last| last.elapsed() < FROZEN_REFRESH)
+        {
+            return;
+        }
+        self.last_frozen_present = Some(Instant::now());
+        let Some(frozen) = &self.frozen else {
+            return;
+        };
+        self.pixels.frame_mut().copy_from_slice(frozen);
+        let particles = core_state.world().particles().len();
+        let generation = core_state.generation();
+        self.draw_interface(particles, (generation.number(), generation.mean_energy()));
+        if let Err(error) = self.pixels.render() {
+            eprintln!("Failed to render frame: {error}");
+            self.failed = true;
+        }
+    }
+
+    // Overlay and console over the frame, the parts that change while rendering is frozen
+    fn draw_interface(&mut self, particles: usize, generation: (u64, Option<f32>)) {
+        if self.config.show_overlay {
+            let lines = overlay_lines(
+                &self.config,
+                &self.rate_meter,
+                self.frozen.is_some(),
+                particles,
+                generation,
+            );
+            draw_overlay(self.pixels.frame_mut(), &self.config, &lines);
+        }
+        if let Some(input) = &self.console_input {
+            draw_console(
+                self.pixels.frame_mut(),
+                &self.config,
+                input,
+                self.console_result.as_ref(),
+            );
+        }
+    }
+
+    // Shows the console with `input` typed so far, `None` closes it
+    pub fn set_console_input(&mut self, input: Option<String>) {
+        if input.is_none() {
+            self.console_result = None;
+        }
+        self.console_input = input;
+    }
+
+    // Shown above the console input until the next command
+    pub fn show_console_result(&mut self, result: Result<String, String>) {
+        self.console_result = Some(result);
+    }
+
+    // Highlights the particle with `id` for as long as it lives, `None` clears the selection
+    pub fn select(&mut self, id: Option<u64>) {
+        self.selected = id;
+    }
+
+    // Outlines `region` until it is replaced, `None` clears it
+    pub fn select_region(&mut self, region: Option<Rect>) {
+        self.region = region;
+    }
+
+    // Follows the window size with both the surface and the buffer
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), TextureError> {
+        self.pixels.resize_surface(width, height)?;
+        self.pixels.resize_buffer(width, height)?;
+        self.config.width = width;
+        self.config.height = height;
+        // The frozen frame no longer fits, the next one is kept in its place
+        if self.frozen.take().is_some() {
+            self.freeze_pending = true;
+        }
+        Ok(())
+    }
+
+    // RGBA contents of the frame on screen, sized like `config`
+    pub fn frame(&self) -> &[u8] {
+        self.pixels.frame()
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.set_enabled(enabled);
+    }
+
+    // Eases `camera` towards where the camera mode wants it, by the time since the last frame
+    fn follow(&self, camera: &mut Camera, particles: &[Particle]) {
+        let Some(target) = camera.target(self.camera_mode, particles, self.config.screen_size())
+        else {
+            return;
+        };
+        let now = Instant::now();
+        let elapsed = self
+            .last_follow
+            .replace(Some(now))
+            .map_or(0.0, |last| (now - last).as_secs_f32());
+        camera.approach(target, 1.0 - (-elapsed / CAMERA_FOLLOW_TIME).exp());
+    }
+
+    // Whether presenting a frame failed, the surface is unusable from then on
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+}
+
+impl Renderer for WinitRenderer {
+    type RenderWorld = RenderWorld;
+
+    fn extract_render_world(&self, cr_state: &CoreState) -> RenderWorld {
+        let start = self.profiler.start();
+        let camera = {
+            let mut camera = self.camera.lock();
+            self.follow(&mut camera, cr_state.world().particles());
+            *camera
+        };
+        let mut world = match self.spare_world.take() {
+            Some(mut world) => {
+                world.refill(
+                    cr_state.world(),
+                    cr_state.tick(),
+                    self.config.color_mode,
+                    &cr_state.config().species_colors,
+                    camera,
+                );
+                world
+            }
+            None => RenderWorld::extract(
+                cr_state.world(),
+                cr_state.tick(),
+                self.config.color_mode,
+                &cr_state.config().species_colors,
+                camera,
+            ),
+        };
+        world.selected = self
+            .selected
+            .and_then(|id| cr_state.world().particle(id))
+            .map(|particle| (particle.position, particle.radius));
+        let generation = cr_state.generation();
+        world.generation = (generation.number(), generation.mean_energy());
+        world.grid = self
+            .config
+            .show_grid
+            .then(|| cr_state.collision_grid())
+            .flatten();
+        world.region = self.region.clone();
+        match (
+            cr_state.world().nutrients(),
+            &cr_state.config().nutrients,
+            self.config.show_nutrients,
+        ) {
+            (Some(field), Some(nutrients), true) => {
+                // Reuses the cells of the last frame
+                let shares = world.nutrients.get_or_insert_with(NutrientField::default);
+                shares.clone_from(field);
+                for share in &mut shares.concentrations {
+                    *share = if nutrients.cap > 0.0 {
+                        *share / nutrients.cap
+                    } else {
+                        0.0
+                    };
+                }
+            }
+            _ => world.nutrients = None,
+        }
+        if let Some(max_lifetime) = cr_state.config().max_lifetime {
+            world.fade_by_age(
+                cr_state.world().particles(),
+                max_lifetime,
+                self.config.background,
+            );
+        }
+        self.profiler.record("extract", start);
+        world
+    }
+
+    fn render_world(&mut self, world: RenderWorld) {
+        self.rate_meter.record(world.tick);
+        let start = self.profiler.start();
+        if self.config.trail_decay > 0.0 {
+            // A resize leaves nothing worth fading
+            if self.trail_canvas.len() != self.config.frame_len() {
+                self.trail_canvas = self
+                    .config
+                    .background
+                    .repeat(self.config.width as usize * self.config.height as usize);
+            }
+            rasterize_in_mode(
+                &world,
+                &mut self.trail_canvas,
+                &self.config,
+                &mut self.density,
+            );
+            self.profiler.record("rasterize", start);
+            let start = self.profiler.start();
+            self.pixels.frame_mut().copy_from_slice(&self.trail_canvas);
+            self.profiler.record("trail copy", start);
+        } else {
+            rasterize_in_mode(
+                &world,
+                self.pixels.frame_mut(),
+                &self.config,
+                &mut self.density,
+            );
+            self.profiler.record("rasterize", start);
+        }
+        // Drawn over the trails, a moving selection shouldn't smear
+        draw_debug_lines(self.pixels.frame_mut(), &self.config, &world);
+        if self.config.show_velocity_vectors {
+            draw_velocity_vectors(self.pixels.frame_mut(), &self.config, &world);
+        }
+        draw_selection(self.pixels.frame_mut(), &self.config, &world);
+        // Particle colors aren't drawn in density mode, neither is their legend
+        if self.config.render_mode == RenderMode::Particles {
+            draw_legend(
+                self.pixels.frame_mut(),
+                &self.config,
+                world.color_mode,
+                world.color_range,
+            );
+        }
+        if self.config.show_minimap {
+            draw_minimap(self.pixels.frame_mut(), &self.config, &world);
+        }
+        if std::mem::take(&mut self.freeze_pending) {
+            self.frozen = Some(self.pixels.frame().to_vec());
+        }
+        self.draw_interface(world.positions.len(), world.generation);
+        *self.spare_world.get_mut() = Some(world);
+
+        // Uploads the buffer to the GPU and presents it, includes waiting for vsync
+        let start = self.profiler.start();
+        if let Err(error) = self.pixels.render() {
+            eprintln!("Failed to render frame: {error}");
+            self.failed = true;
+        }
+        self.profiler.record("present", start);
+    }
+}
+
+// Text of the overlay, frozen rendering shows in place of the frame rate
+fn overlay_lines(
+    config: &RenderConfig,
+    rate_meter: &RateMeter,
+    frozen: bool,
+    particles: usize,
+    generation: (u64, Option<f32>),
+) -> [String; 5] {
+    [
+        if frozen {
+            "RENDERING FROZEN".to_string()
+        } else if config.uncapped() {
+            format!("FPS {:.0} UNCAPPED", rate_meter.fps)
+        } else {
+            format!("FPS {:.0}", rate_meter.fps)
+        },
+        format!("TPS {:.0}", rate_meter.tps),
+        format!("PARTICLES {particles}"),
+        format!("GENERATION {}", generation.0),
+        format!("GEN ENERGY {}", format_mean(generation.1)),
+    ]
+}
+
+fn rasterize_in_mode(
+    world: &RenderWorld,
+    frame: &mut [u8],
+    config: &RenderConfig,
+    density: &mut Vec<f32>,
+) {
+    match config.render_mode {
+        RenderMode::Particles => rasterize(world, frame, config),
+        RenderMode::Density => rasterize_density(world, frame, config, density),
+    }
+}
+
+// Places the particle within `range` of the value its color encodes
+fn particle_color(particle: &Particle, color_mode: ColorMode, (min, max): (f32, f32)) -> [u8; 4] {
+    let value = match color_mode {
+        ColorMode::Uniform => 0.0,
+        ColorMode::Velocity => speed(particle),
+        ColorMode::Energy => particle.energy,
+        ColorMode::Genome => particle.genome.color_hue,
+        ColorMode::Species => 0.0,
+        ColorMode::Individual => id_hue(particle.id),
+        ColorMode::Lineage => particle.lineage_hue,
+    };
+    let t = if max > min {
+        (value - min) / (max - min)
+    } else {
+        0.0
+    };
+    colormap(color_mode, t)
+}
+
+// Color of the position t in [0, 1] along the colormap of `color_mode`
+fn colormap(color_mode: ColorMode, t: f32) -> [u8; 4] {
+    match color_mode {
+        ColorMode::Uniform => PARTICLE_COLOR,
+        ColorMode::Velocity => blue_to_red(t),
+        ColorMode::Energy => {
+            let brightness = (t.clamp(0.0, 1.0) * 255.0) as u8;
+            [brightness, brightness, brightness, 255]
+        }
+        ColorMode::Genome | ColorMode::Individual | ColorMode::Lineage => {
+            hsv_to_rgba(t * 360.0, 0.8, 1.0)
+        }
+        ColorMode::Species => PARTICLE_COLOR,
+    }
+}
+
+// Color of `species` from the palette, species past its end step around the hue circle by the
+// golden angle from a fixed start so they stay apart from each other and the same every run
+fn species_color(palette: &[[u8; 3]], species: u8) -> [u8; 4] {
+    match palette.get(species as usize) {
+        Some(&[r, g, b]) => [r, g, b, 255],
+        None => {
+            let extra = species as usize - palette.len();
+            hsv_to_rgba(15.0 + extra as f32 * GOLDEN_ANGLE, 0.65, 0.95)
+        }
+    }
+}
+
+fn speed(particle: &Particle) -> f32 {
+    (particle.velocity[0].powi(2) + particle.velocity[1].powi(2)).sqrt()
+}
+
+// (min, max) of the values, (0, 0) when there are none
+fn value_range(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    values
+        .fold(None, |range: Option<(f32, f32)>, value| match range {
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+            None => Some((value, value)),
+        })
+        .unwrap_or((0.0, 0.0))
+}
+
+// Black body colormap over t in [0, 1], through red and yellow to white
+fn heat(t: f32) -> [u8; 4] {
+    let t = t.clamp(0.0, 1.0) * 3.0;
+    [
+        (t.min(1.0) * 255.0) as u8,
+        ((t - 1.0).clamp(0.0, 1.0) * 255.0) as u8,
+        ((t - 2.0).clamp(0.0, 1.0) * 255.0) as u8,
+        255,
+    ]
+}
+
+// Linear blue to red colormap over t in [0, 1]
+fn blue_to_red(t: f32) -> [u8; 4] {
+    let t = t.clamp(0.0, 1.0);
+    [(t * 255.0) as u8, 0, ((1.0 - t) * 255.0) as u8, 255]
+}
+
+// Hue in degrees, saturation and value in [0, 1]
+fn hsv_to_rgba(hue: f32, saturation: f32, value: f32) -> [u8; 4] {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [
+        ((r + m) * 255.0) as u8,
+        ((g + m) * 255.0) as u8,
+        ((b + m) * 255.0) as u8,
+        255,
+    ]
+}
+
+// Bresenham line between the pixels containing `start` and `end`, both ends included. The
+// segment is clipped to the frame first, lines reaching far off-screen only walk the pixels
+// they cover on it.
+pub fn draw_line(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    start: [f32; 2],
+    end: [f32; 2],
+    color: [u8; 4],
+) {
+    let Some((start, end)) = clip_line(start, end, [width as f32, height as f32]) else {
+        return;
+    };
+    let (width, height) = (width as i64, height as i64);
+    // Clipping can land exactly on the far edge, which belongs to the pixel past it
+    let pixel = |point: [f32; 2]| {
+        [
+            (point[0].floor() as i64).clamp(0, width - 1),
+            (point[1].floor() as i64).clamp(0, height - 1),
+        ]
+    };
+    let ([mut x, mut y], [end_x, end_y]) = (pixel(start), pixel(end));
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+    loop {
+        let offset = ((y * width + x) * 4) as usize;
+        frame[offset..offset + 4].copy_from_slice(&color);
+        if x == end_x && y == end_y {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+// Part of the segment from `start` to `end` within `[0, size]` on both axes (Liang-Barsky),
+// `None` when it misses the frame or isn't finite
+fn clip_line(start: [f32; 2], end: [f32; 2], size: [f32; 2]) -> Option<([f32; 2], [f32; 2])> {
+    if !start.iter().chain(&end).all(|value| value.is_finite()) {
+        return None;
+    }
+    let delta = [end[0] - start[0], end[1] - start[1]];
+    let (mut enter, mut exit) = (0.0f32, 1.0f32);
+    for axis in 0..2 {
+        for (direction, distance) in [
+            (-delta[axis], start[axis]),
+            (delta[axis], size[axis] - start[axis]),
+        ] {
+            if direction == 0.0 {
+                if distance < 0.0 {
+                    return None;
+                }
+            } else {
+                let t = distance / direction;
+                if direction < 0.0 {
+                    enter = enter.max(t);
+                } else {
+                    exit = exit.min(t);
+                }
+            }
+        }
+    }
+    if enter > exit {
+        return None;
+    }
+    let at = |t: f32| [start[0] + delta[0] * t, start[1] + delta[1] * t];
+    Some((at(enter), at(exit)))
+}
+
+// Sets every pixel whose centre lies within the screen space box from `min` to `max`. Float to
+// int casts saturate, boxes reaching far off-screen are clipped like any other.
+pub fn fill_rect(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    min: [f32; 2],
+    max: [f32; 2],
+    color: [u8; 4],
+) {
+    let columns = (min[0] - 0.5).ceil().max(0.0) as usize
+        ..((max[0] - 0.5).floor() + 1.0).min(width as f32).max(0.0) as usize;
+    let rows = (min[1] - 0.5).ceil().max(0.0) as usize
+        ..((max[1] - 0.5).floor() + 1.0).min(height as f32).max(0.0) as usize;
+    for row in rows {
+        let offset = row * width as usize;
+        for column in columns.clone() {
+            let pixel = (offset + column) * 4;
+            frame[pixel..pixel + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+// Sets the pixel containing `position`, if it is on screen
+pub fn plot(frame: &mut [u8], width: u32, height: u32, position: [f32; 2], color: [u8; 4]) {
+    let [x, y] = position.map(f32::floor);
+    if x >= 0.0 && y >= 0.0 && x < width as f32 && y < height as f32 {
+        let pixel = (y as usize * width as usize + x as usize) * 4;
+        frame[pixel..pixel + 4].copy_from_slice(&color);
+    }
+}
+
+// Circles wider than this estimate edge coverage from the squared distance instead of taking a
+// square root per edge pixel. The error shrinks with the radius and is invisible by then.
+const EXACT_COVERAGE_MAX_RADIUS: f32 = 4.0;
+
+// Anti-aliased scanline fill. Pixels whose centre is at least half a pixel inside the circle are
+// overwritten, the ones on the boundary are blended by how much of them the circle covers.
+// Rows and spans are clipped to the frame first, so zooming far into a circle costs no more than
+// the pixels it covers on screen.
+pub fn fill_circle(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    center: [f32; 2],
+    radius: f32,
+    color: [u8; 4],
+) {
+    if radius <= 0.0 {
+        return;
+    }
+    let (width, height) = (width as i64, height as i64);
+    let outer = radius + 0.5;
+    let inner = radius - 0.5;
+    // Float to int casts saturate, so far off-screen circles simply produce empty ranges and
+    // no zoom level can overflow the index math below
+    let y_min = ((center[1] - outer - 0.5).ceil() as i64).max(0);
+    let y_max = ((center[1] + outer - 0.5).floor() as i64).min(height - 1);
+
+    for y in y_min..=y_max {
+        let dy = y as f32 + 0.5 - center[1];
+        let outer_span_squared = outer * outer - dy * dy;
+        if outer_span_squared < 0.0 {
+            continue;
+        }
+
+        let outer_span = outer_span_squared.sqrt();
+        let x_min = ((center[0] - outer_span - 0.5).ceil() as i64).max(0);
+        let x_max = ((center[0] + outer_span - 0.5).floor() as i64).min(width - 1);
+        if x_min > x_max {
+            continue;
+        }
+
+        // Fully covered middle of the row, empty when the row only grazes the edge
+        let inner_span_squared = inner * inner - dy * dy;
+        let (inner_min, inner_max) = if inner > 0.0 && inner_span_squared >= 0.0 {
+            let inner_span = inner_span_squared.sqrt();
+            (
+                ((center[0] - inner_span - 0.5).ceil() as i64).max(x_min),
+                ((center[0] + inner_span - 0.5).floor() as i64).min(x_max),
+            )
+        } else {
+            (x_max + 1, x_max)
+        };
+
+        let row = (y * width) as usize;
+        let pixel_offset = |x: i64| (row + x as usize) * 4;
+        for x in (x_min..inner_min.min(x_max + 1)).chain((inner_max + 1).max(x_min)..=x_max) {
+            let dx = x as f32 + 0.5 - center[0];
+            let distance_squared = dx * dx + dy * dy;
+            let coverage = if radius > EXACT_COVERAGE_MAX_RADIUS {
+                0.5 - (distance_squared - radius * radius) / (2.0 * radius)
+            } else {
+                outer - distance_squared.sqrt()
+            };
+            let offset = pixel_offset(x);
+            blend(
+                &mut frame[offset..offset + 4],
+                color,
+                coverage.clamp(0.0, 1.0),
+            );
+        }
+        if inner_min <= inner_max {
+            let start = pixel_offset(inner_min);
+            let end = pixel_offset(inner_max + 1);
+            for pixel in frame[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+// Mixes `color` over `pixel`, `coverage` of 1.0 replaces it entirely
+fn blend(pixel: &mut [u8], color: [u8; 4], coverage: f32) {
+    let weight = (coverage * 255.0).round() as u32;
+    for (channel, &source) in pixel.iter_mut().zip(&color) {
+        *channel = ((source as u32 * weight + *channel as u32 * (255 - weight) + 127) / 255) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SimRng;
+    use crate::simulation::SimulationConfig;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::time::Instant;
+
+    // Counts allocations per thread, so tests running in parallel don't see each other's
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn rasterized_world_fills_full_rgba_buffer() {
+        let config = RenderConfig::builder().width(320).height(200).build();
+        let core_state = CoreState::new(SimulationConfig {
+            particle_count: 100,
+            bounds: [320.0, 200.0],
+            obstacles: vec![Obstacle::Rect {
+                min: [-10.0, 190.0],
+                max: [20.0, 210.0],
+            }],
+            ..SimulationConfig::default()
+        });
+        let world = RenderWorld::extract(
+            core_state.world(),
+            core_state.tick(),
+            config.color_mode,
+            &[],
+            Camera::new([160.0, 100.0]),
+        );
+        assert_eq!(world.positions.len(), 100);
+
+        let mut frame = vec![255; config.frame_len()];
+        rasterize(&world, &mut frame, &config);
+
+        assert_eq!(frame.len(), 320 * 200 * 4);
+        assert!(frame.chunks_exact(4).any(|pixel| pixel == [0, 0, 0, 0]));
+        assert!(frame.chunks_exact(4).any(|pixel| pixel == PARTICLE_COLOR));
+        // Obstacles are clipped to the frame, the bottom left corner is covered by one
+        let corner = (199 * 320) * 4;
+        assert_eq!(frame[corner..corner + 4], OBSTACLE_COLOR);
+        let beside = (189 * 320 + 25) * 4;
+        assert_ne!(frame[beside..beside + 4], OBSTACLE_COLOR);
+    }
+
+    #[test]
+    fn empty_world_renders_the_background() {
+        let background = [20, 30, 60, 255];
+        for trail_decay in [0.0, 0.9] {
+            let config = RenderConfig::builder()
+                .width(32)
+                .height(16)
+                .trail_decay(trail_decay)
+                .background(background)
+                .build();
+            let core_state = CoreState::new(SimulationConfig {
+                particle_count: 0,
+                food_spawn_rate: 0.0,
+                ..SimulationConfig::default()
+            });
+            let world = RenderWorld::extract(
+                core_state.world(),
+                0,
+                config.color_mode,
+                &[],
+                Camera::new([0.0, 0.0]),
+            );
+            // Trails start out from the background, like the first frame after a reset
+            let mut frame: Vec<_> = background.repeat(32 * 16);
+            if trail_decay == 0.0 {
+                frame.fill(255);
+            }
+            rasterize(&world, &mut frame, &config);
+            assert!(frame.chunks_exact(4).all(|pixel| pixel == background));
+        }
+    }
+
+    #[test]
+    fn lone_particles_are_drawn_centered_in_every_mode() {
+        let background = [20, 30, 60, 255];
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        });
+        core_state.step(1.0 / 60.0);
+        for count in [0, 1] {
+            if count == 1 {
+                core_state.spawn_at([300.0, 200.0]);
+                core_state.step(1.0 / 60.0);
+            }
+            let particles = core_state.world().particles();
+            let shapes = [
+                ParticleShape::Circle,
+                ParticleShape::Square,
+                ParticleShape::Point,
+            ];
+            let color_modes = [
+                ColorMode::Uniform,
+                ColorMode::Velocity,
+                ColorMode::Energy,
+                ColorMode::Genome,
+                ColorMode::Species,
+                ColorMode::Individual,
+                ColorMode::Lineage,
+            ];
+            for render_mode in [RenderMode::Particles, RenderMode::Density] {
+                for (shape, color_mode) in shapes
+                    .into_iter()
+                    .flat_map(|shape| color_modes.map(|color_mode| (shape, color_mode)))
+                {
+                    let config = RenderConfig::builder()
+                        .width(32)
+                        .height(16)
+                        .render_mode(render_mode)
+                        .particle_shape(shape)
+                        .background(background)
+                        .build();
+                    let camera = Camera::new([0.0, 0.0])
+                        .target(CameraMode::FitAll, particles, config.screen_size())
+                        .unwrap_or(Camera::new([0.0, 0.0]));
+                    let world = RenderWorld::extract(
+                        core_state.world(),
+                        core_state.tick(),
+                        color_mode,
+                        &[],
+                        camera,
+                    );
+                    assert!(world.color_range.0.is_finite() && world.color_range.1.is_finite());
+                    let mut frame = vec![0; config.frame_len()];
+                    match render_mode {
+                        RenderMode::Particles => rasterize(&world, &mut frame, &config),
+                        RenderMode::Density => {
+                            rasterize_density(&world, &mut frame, &config, &mut Vec::new())
+                        }
+                    }
+                    // Nothing but the background without particles, the lone one right in the
+                    // middle of the view otherwise
+                    let center = &frame[(8 * 32 + 16) * 4..(8 * 32 + 17) * 4];
+                    if count == 0 {
+                        assert!(frame.chunks_exact(4).all(|pixel| pixel == background));
+                    } else {
+                        assert_ne!(
+                            center, background,
+                            "{render_mode:?} {shape:?} {color_mode:?}"
+                        );
+                    }
+                    draw_legend(&mut frame, &config, world.color_mode, world.color_range);
+                    draw_minimap(&mut frame, &config, &world);
+                }
+            }
+        }
+
+        assert_eq!(format_mean(None), "—");
+        assert_eq!(format_mean(Some(12.345)), "12.3");
+        assert_ne!(font::text_width("—"), 0);
+    }
+
+    #[test]
+    fn circle_edges_blend_with_the_background() {
+        let (width, height) = (16, 16);
+        let mut frame = vec![0; width * height * 4];
+        fill_circle(&mut frame, 16, 16, [8.0, 8.0], 3.0, [255; 4]);
+        let red = |x: usize, y: usize| frame[(y * width + x) * 4];
+
+        // Centre is solid, the boundary partially covered and the outside untouched
+        assert_eq!(red(7, 7), 255);
+        assert!((1..255).contains(&red(10, 7)));
+        assert!((1..255).contains(&red(7, 10)));
+        assert_eq!(red(12, 7), 0);
+        assert_eq!(red(0, 0), 0);
+
+        // The approximation on large circles stays within a few levels of the exact coverage
+        let radius = 40.0_f32;
+        let mut frame = vec![0; 100 * 100 * 4];
+        fill_circle(&mut frame, 100, 100, [50.0, 50.0], radius, [255; 4]);
+        for (index, pixel) in frame.chunks_exact(4).enumerate() {
+            let (x, y) = ((index % 100) as f32 + 0.5, (index / 100) as f32 + 0.5);
+            let distance = ((x - 50.0).powi(2) + (y - 50.0).powi(2)).sqrt();
+            let exact = ((radius + 0.5 - distance).clamp(0.0, 1.0) * 255.0).round();
+            assert!((pixel[0] as f32 - exact).abs() <= 2.0, "{x} {y}");
+        }
+    }
+
+    #[test]
+    fn overlay_darkens_bright_frames_and_clips_to_small_ones() {
+        let config = RenderConfig::builder().width(64).height(32).build();
+        let mut frame = vec![255; config.frame_len()];
+        draw_overlay(&mut frame, &config, &["FPS 60".to_string()]);
+
+        let pixel = |x: usize, y: usize| &frame[(y * 64 + x) * 4..(y * 64 + x) * 4 + 4];
+        // Box behind the text is darkened, the text itself and the rest stay white
+        assert_eq!(
+            pixel(OVERLAY_MARGIN as usize, OVERLAY_MARGIN as usize),
+            [95, 95, 95, 255]
+        );
+        assert_eq!(pixel(63, 31), [255; 4]);
+        assert!(frame
+            .chunks_exact(4)
+            .any(|pixel| pixel == OVERLAY_TEXT_COLOR));
+
+        let tiny = RenderConfig::builder().width(3).height(2).build();
+        let mut frame = vec![0; tiny.frame_len()];
+        draw_overlay(&mut frame, &tiny, &["PARTICLES 1000".to_string()]);
+    }
+
+    #[test]
+    fn frozen_rendering_still_measures_the_tick_rate() {
+        let mut rate_meter = RateMeter::new();
+        rate_meter.record(0);
+        // Ticks go on without frames, the window then closes with only the one frame in it
+        rate_meter.record_ticks(300);
+        rate_meter.window_start -= RATE_WINDOW;
+        rate_meter.record_ticks(600);
+        assert!(rate_meter.tps > 0.0);
+        assert!((rate_meter.tps / rate_meter.fps - 600.0).abs() < 1e-3);
+
+        let config = RenderConfig::default();
+        let lines = overlay_lines(&config, &rate_meter, true, 3, (1, None));
+        assert_eq!(lines[0], "RENDERING FROZEN");
+        assert_eq!(lines[1], format!("TPS {:.0}", rate_meter.tps));
+        assert_eq!(lines[2], "PARTICLES 3");
+        assert_eq!(lines[4], "GEN ENERGY —");
+        let live = overlay_lines(&config, &rate_meter, false, 3, (1, Some(2.0)));
+        assert_eq!(live[0], format!("FPS {:.0}", rate_meter.fps));
+    }
+
+    #[test]
+    fn legend_shows_the_gradient_of_the_current_mode() {
+        let config = RenderConfig::builder().width(200).height(100).build();
+        let mut frame = vec![0; config.frame_len()];
+        draw_legend(&mut frame, &config, ColorMode::Uniform, (0.0, 0.0));
+        assert!(frame.iter().all(|&channel| channel == 0));
+
+        draw_legend(&mut frame, &config, ColorMode::Velocity, (2.5, 180.0));
+        let pixel = |x: u32, y: u32| {
+            let offset = ((y * 200 + x) * 4) as usize;
+            &frame[offset..offset + 4]
+        };
+        let bar_y = 100 - OVERLAY_MARGIN - OVERLAY_PADDING - OVERLAY_LINE_HEIGHT - 2 - 1;
+        let bar_x = OVERLAY_MARGIN + OVERLAY_PADDING;
+        assert_eq!(pixel(bar_x, bar_y), blue_to_red(0.0));
+        assert_eq!(pixel(bar_x + LEGEND_BAR_WIDTH - 1, bar_y), blue_to_red(1.0));
+        // Title and labels are drawn, nothing outside the corner is touched
+        assert!(frame
+            .chunks_exact(4)
+            .any(|pixel| pixel == OVERLAY_TEXT_COLOR));
+        assert_eq!(pixel(199, 0), [0; 4]);
+        assert_eq!(
+            (legend_label(2.5), legend_label(180.0)),
+            ("2.5".into(), "180".into())
+        );
+
+        let tiny = RenderConfig::builder().width(8).height(4).build();
+        let mut frame = vec![0; tiny.frame_len()];
+        draw_legend(&mut frame, &tiny, ColorMode::Genome, (0.0, 360.0));
+    }
+
+    #[test]
+    fn minimap_shows_particles_and_the_viewport() {
+        let config = RenderConfig::builder().width(320).height(240).build();
+        let mut camera = Camera::new([500.0, 250.0]);
+        camera.zoom = 4.0;
+        let world = RenderWorld {
+            positions: vec![[0.0, 0.0], [999.0, 499.0]],
+            velocities: vec![[0.0, 0.0]; 2],
+            radii: vec![1.0; 2],
+            colors: vec![pack_rgba([255, 0, 0, 255]); 2],
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [1000.0, 500.0],
+            },
+            camera,
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        let mut frame = vec![0; config.frame_len()];
+        draw_minimap(&mut frame, &config, &world);
+
+        // 1000x500 world on a 128x64 map in the top right corner
+        let origin = [320 - OVERLAY_MARGIN - 128, OVERLAY_MARGIN];
+        let pixel = |x: u32, y: u32| {
+            let offset = (((origin[1] + y) * 320 + origin[0] + x) * 4) as usize;
+            &frame[offset..offset + 4]
+        };
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(127, 63), [255, 0, 0, 255]);
+        // The camera shows 80x60 world units around the centre, about 10x8 map pixels
+        assert_eq!(pixel(58, 28), VIEWPORT_COLOR);
+        assert_eq!(pixel(69, 35), VIEWPORT_COLOR);
+        assert_eq!(pixel(64, 32), [0; 4]);
+
+        assert_eq!(minimap_stride(1_000_000, [128, 64]), 123);
+        assert_eq!(minimap_stride(10, [128, 64]), 1);
+    }
+
+    #[test]
+    fn particles_are_sized_in_world_units() {
+        let config = RenderConfig::builder().width(64).height(64).build();
+        let lit = |zoom: f32| {
+            let mut camera = Camera::new([0.0, 0.0]);
+            camera.zoom = zoom;
+            let world = RenderWorld {
+                positions: vec![[0.0, 0.0]],
+                velocities: vec![[0.0, 0.0]],
+                radii: vec![4.0],
+                colors: vec![pack_rgba([255; 4])],
+                food: Vec::new(),
+                obstacles: Vec::new(),
+                color_mode: ColorMode::Uniform,
+                color_range: (0.0, 0.0),
+                bounds: Rect {
+                    min: [-32.0, -32.0],
+                    max: [32.0, 32.0],
+                },
+                camera,
+                tick: 0,
+                selected: None,
+                generation: (0, None),
+                grid: None,
+                region: None,
+                nutrients: None,
+            };
+            let mut frame = vec![0; config.frame_len()];
+            rasterize(&world, &mut frame, &config);
+            frame.chunks_exact(4).filter(|pixel| pixel[0] > 0).count()
+        };
+
+        // Twice the zoom covers about four times the pixels, the default is world == pixels
+        let (one, two) = (lit(1.0), lit(2.0));
+        assert!((45..=60).contains(&one), "{one}");
+        assert!(
+            (3.5..4.5).contains(&(two as f32 / one as f32)),
+            "{one} {two}"
+        );
+        // Far out particles stay a dot straddling the pixels around their centre, far in they fill
+        // the frame without walking the millions of rows of the whole disc
+        assert_eq!(lit(0.001), 4);
+        assert_eq!(lit(1e6), 64 * 64);
+    }
+
+    #[test]
+    fn particle_shapes_cover_what_they_promise() {
+        let lit = |shape: ParticleShape, position: [f32; 2]| {
+            let config = RenderConfig::builder()
+                .width(64)
+                .height(64)
+                .particle_shape(shape)
+                .build();
+            let world = RenderWorld {
+                positions: vec![position],
+                velocities: vec![[0.0, 0.0]],
+                radii: vec![4.0],
+                colors: vec![pack_rgba([255; 4])],
+                food: Vec::new(),
+                obstacles: Vec::new(),
+                color_mode: ColorMode::Uniform,
+                color_range: (0.0, 0.0),
+                bounds: Rect {
+                    min: [0.0, 0.0],
+                    max: [64.0, 64.0],
+                },
+                camera: Camera::new([32.0, 32.0]),
+                tick: 0,
+                selected: None,
+                generation: (0, None),
+                grid: None,
+                region: None,
+                nutrients: None,
+            };
+            let mut frame = vec![0; config.frame_len()];
+            rasterize(&world, &mut frame, &config);
+            frame.chunks_exact(4).filter(|pixel| pixel[0] > 0).count()
+        };
+
+        // A radius of 4 makes an 8x8 square around a pixel corner, points ignore the radius
+        let circle = lit(ParticleShape::Circle, [32.0, 32.0]);
+        assert_eq!(lit(ParticleShape::Square, [32.0, 32.0]), 64);
+        assert!(circle > 45 && circle < 64, "{circle}");
+        assert_eq!(lit(ParticleShape::Point, [32.0, 32.0]), 1);
+        // Clipped at the edges of the frame
+        assert_eq!(lit(ParticleShape::Square, [0.0, 0.0]), 16);
+        assert_eq!(lit(ParticleShape::Point, [64.0, 10.0]), 0);
+        assert_eq!(lit(ParticleShape::Point, [-0.5, 10.0]), 0);
+        assert_eq!(ParticleShape::Point.next(), ParticleShape::Circle);
+    }
+
+    #[test]
+    fn density_is_normalized_to_the_busiest_cell() {
+        let config = RenderConfig::builder()
+            .width(16)
+            .height(10)
+            .render_mode(RenderMode::Density)
+            .build();
+        // Four particles in the top left cell, one in the cell right of it and one off screen
+        let positions = vec![
+            [0.5, 0.5],
+            [1.5, 2.5],
+            [3.5, 3.5],
+            [2.0, 1.0],
+            [5.0, 1.0],
+            [40.0, 1.0],
+        ];
+        let world = RenderWorld {
+            velocities: vec![[0.0, 0.0]; positions.len()],
+            radii: vec![1.0; positions.len()],
+            colors: vec![pack_rgba(PARTICLE_COLOR); positions.len()],
+            positions,
+            food: vec![[12.0, 8.0]],
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [16.0, 10.0],
+            },
+            camera: Camera::new([8.0, 5.0]),
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        let mut frame = vec![0; config.frame_len()];
+        let mut density = Vec::new();
+        rasterize_density(&world, &mut frame, &config, &mut density);
+
+        let pixel = |x: usize, y: usize| &frame[(y * 16 + x) * 4..(y * 16 + x) * 4 + 4];
+        assert_eq!(pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(3, 3), [255, 255, 255, 255]);
+        assert_eq!(pixel(4, 0), heat(0.25));
+        // Nothing else, not even food, the ragged bottom row of cells is clipped to the frame
+        assert_eq!(pixel(8, 0), [0; 4]);
+        assert_eq!(pixel(12, 8), [0; 4]);
+        assert_eq!(density.len(), 4 * 3);
+        assert_eq!(density.iter().sum::<f32>(), 5.0);
+
+        // Counts start over every frame
+        let capacity = density.capacity();
+        rasterize_density(&world, &mut frame, &config, &mut density);
+        assert_eq!(density.iter().sum::<f32>(), 5.0);
+        assert_eq!(density.capacity(), capacity);
+    }
+
+    #[test]
+    fn individuals_keep_their_color_and_children_take_after_parents() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 1,
+            initial_energy: 150.0,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        });
+        let extract = |core_state: &CoreState, color_mode| {
+            RenderWorld::extract(
+                core_state.world(),
+                0,
+                color_mode,
+                &[],
+                Camera::new([0.0, 0.0]),
+            )
+            .colors
+        };
+        let before = extract(&core_state, ColorMode::Individual);
+        core_state.step(1.0 / 60.0);
+        let particles = core_state.world().particles();
+        assert_eq!(particles.len(), 2);
+
+        // The parent kept its color through moving and splitting its energy, the child has its own
+        let individual = extract(&core_state, ColorMode::Individual);
+        assert_eq!(individual[0], before[0]);
+        assert_ne!(individual[1], individual[0]);
+        assert_eq!(
+            individual[0].to_le_bytes(),
+            hsv_to_rgba(id_hue(0), 0.8, 1.0)
+        );
+
+        // A founder's lineage color is its individual one, its child's is only a few degrees off
+        let lineage = extract(&core_state, ColorMode::Lineage);
+        assert_eq!(lineage[0], individual[0]);
+        let offset =
+            (particles[1].lineage_hue - particles[0].lineage_hue + 180.0).rem_euclid(360.0);
+        assert!((offset - 180.0).abs() <= 8.0, "{offset}");
+        assert_eq!(ColorMode::Lineage.legend_title(), None);
+    }
+
+    #[test]
+    fn particles_fade_into_the_background_towards_the_end_of_their_life() {
+        let core_state = CoreState::new(SimulationConfig {
+            particle_count: 4,
+            ..SimulationConfig::default()
+        });
+        let mut world = RenderWorld::extract(
+            core_state.world(),
+            0,
+            ColorMode::Uniform,
+            &[],
+            Camera::new([0.0, 0.0]),
+        );
+        let mut particles = core_state.world().particles().to_vec();
+        for (particle, age) in particles.iter_mut().zip([1.0, 4.0, 7.0, 12.0]) {
+            particle.age = age;
+        }
+        let background = [0, 0, 100, 255];
+        world.fade_by_age(&particles, 10.0, background);
+
+        let colors: Vec<_> = world
+            .colors
+            .iter()
+            .map(|color| color.to_le_bytes())
+            .collect();
+        let [r, g, b, a] = PARTICLE_COLOR;
+        let blend = |opacity: f32, back: u8, channel: u8| {
+            (back as f32 + (channel as f32 - back as f32) * opacity).round() as u8
+        };
+        // Untouched for the first half of their life, then linearly into the background
+        assert_eq!(colors[0], PARTICLE_COLOR);
+        assert_eq!(colors[1], PARTICLE_COLOR);
+        assert_eq!(
+            colors[2],
+            [blend(0.6, 0, r), blend(0.6, 0, g), blend(0.6, 100, b), a]
+        );
+        assert_eq!(colors[3], [0, 0, 100, a]);
+    }
+
+    #[test]
+    fn species_colors_come_from_the_palette_then_stay_apart() {
+        let palette = [[200, 10, 10], [10, 200, 10]];
+        let core_state = CoreState::new(SimulationConfig {
+            particle_count: 60,
+            interaction_matrix: vec![vec![0.0; 3]; 3],
+            ..SimulationConfig::default()
+        });
+        let world = RenderWorld::extract(
+            core_state.world(),
+            0,
+            ColorMode::Species,
+            &palette,
+            Camera::new([0.0, 0.0]),
+        );
+        for (particle, &color) in core_state.world().particles().iter().zip(&world.colors) {
+            let expected = match particle.species {
+                0 => [200, 10, 10, 255],
+                1 => [10, 200, 10, 255],
+                _ => species_color(&palette, 2),
+            };
+            assert_eq!(color.to_le_bytes(), expected);
+        }
+        let particles = core_state.world().particles();
+        assert!(particles.iter().any(|particle| particle.species == 2));
+        assert_eq!(ColorMode::Species.legend_title(), None);
+
+        // Without a palette every species still gets its own color, the same one every time
+        let generated: Vec<_> = (0..=255)
+            .map(|species| species_color(&[], species))
+            .collect();
+        assert_eq!(generated[2], species_color(&palette, 4));
+        for (index, color) in generated.iter().enumerate().take(16) {
+            assert!(!generated[..index].contains(color), "{index}");
+        }
+    }
+
+    #[test]
+    fn velocity_vectors_point_along_the_motion_and_are_clipped() {
+        let config = RenderConfig::builder()
+            .width(40)
+            .height(20)
+            .velocity_scale(0.5)
+            .build();
+        let color = [0, 255, 0, 255];
+        let world = RenderWorld {
+            positions: vec![[10.5, 10.5], [35.5, 2.5]],
+            velocities: vec![[20.0, -10.0], [1e9, 0.0]],
+            radii: vec![0.0; 2],
+            colors: vec![pack_rgba(color); 2],
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [40.0, 20.0],
+            },
+            camera: Camera::new([20.0, 10.0]),
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        let mut frame = vec![0; config.frame_len()];
+        draw_velocity_vectors(&mut frame, &config, &world);
+        let lit = |x: usize, y: usize| frame[(y * 40 + x) * 4..(y * 40 + x) * 4 + 4] == color;
+
+        // Half a second at (20, -10) ends 10 to the right and 5 up, one pixel per column
+        assert!(lit(10, 10) && lit(12, 9) && lit(20, 5));
+        assert!(!lit(21, 5) && !lit(10, 9));
+        assert_eq!(
+            (0..40)
+                .filter(|&x| (0..20).any(|y| lit(x, y) && y > 3))
+                .count(),
+            11
+        );
+        // Far too long vectors stop at the edge of the frame
+        assert!((35..40).all(|x| lit(x, 2)));
+
+        let mut frame = vec![0; config.frame_len()];
+        draw_line(&mut frame, 40, 20, [-5.0, -5.0], [-1.0, 30.0], color);
+        draw_line(&mut frame, 40, 20, [f32::NAN, 0.0], [3.0, 3.0], color);
+        assert!(frame.iter().all(|&channel| channel == 0));
+    }
+
+    #[test]
+    fn bounds_and_grid_lines_follow_the_camera() {
+        let mut config = RenderConfig::builder().width(60).height(40).build();
+        let mut world = RenderWorld {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            radii: Vec::new(),
+            colors: Vec::new(),
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [20.0, 10.0],
+            },
+            // Zoom 2 centred on the world puts it at (10, 10) to (50, 30) on screen
+            camera: Camera {
+                center: [10.0, 5.0],
+                zoom: 2.0,
+            },
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: Some(GridLayout {
+                origin: [0.0, 0.0],
+                cell_size: [5.0, 5.0],
+                columns: 4,
+                rows: 2,
+            }),
+            region: None,
+            nutrients: None,
+        };
+        let mut frame = vec![0; config.frame_len()];
+        draw_debug_lines(&mut frame, &config, &world);
+        let pixel = |frame: &[u8], x: usize, y: usize| {
+            <[u8; 4]>::try_from(&frame[(y * 60 + x) * 4..(y * 60 + x) * 4 + 4]).unwrap()
+        };
+        // Grid lines every 10 pixels, the bounds stay off
+        assert_eq!(pixel(&frame, 20, 15), GRID_COLOR);
+        assert_eq!(pixel(&frame, 35, 20), GRID_COLOR);
+        assert_eq!(pixel(&frame, 15, 15), [0; 4]);
+        assert_eq!(pixel(&frame, 5, 5), [0; 4]);
+
+        config.show_bounds = true;
+        world.grid = None;
+        let mut frame = vec![0; config.frame_len()];
+        draw_debug_lines(&mut frame, &config, &world);
+        assert_eq!(pixel(&frame, 10, 10), BOUNDS_COLOR);
+        assert_eq!(pixel(&frame, 50, 20), BOUNDS_COLOR);
+        assert_eq!(pixel(&frame, 30, 30), BOUNDS_COLOR);
+        assert_eq!(pixel(&frame, 20, 15), [0; 4]);
+
+        // Cells too small to tell apart on screen are left out
+        world.camera.zoom = 0.5;
+        world.grid = Some(GridLayout {
+            origin: [0.0, 0.0],
+            cell_size: [1.0, 1.0],
+            columns: 20,
+            rows: 10,
+        });
+        let mut frame = vec![0; config.frame_len()];
+        draw_debug_lines(&mut frame, &config, &world);
+        assert!(!frame.chunks_exact(4).any(|pixel| pixel == GRID_COLOR));
+        assert!(frame.chunks_exact(4).any(|pixel| pixel == BOUNDS_COLOR));
+
+        // A selected region is outlined whatever the config says
+        config.show_bounds = false;
+        world.grid = None;
+        world.camera.zoom = 2.0;
+        world.region = Some(Rect {
+            min: [5.0, 0.0],
+            max: [10.0, 5.0],
+        });
+        let mut frame = vec![0; config.frame_len()];
+        draw_debug_lines(&mut frame, &config, &world);
+        assert_eq!(pixel(&frame, 20, 15), SELECTION_COLOR);
+        assert_eq!(pixel(&frame, 30, 20), SELECTION_COLOR);
+        assert_eq!(pixel(&frame, 25, 15), [0; 4]);
+    }
+
+    #[test]
+    fn nutrients_are_drawn_from_the_background_up_to_full() {
+        let config = RenderConfig::builder()
+            .width(40)
+            .height(20)
+            .background([0, 0, 0, 255])
+            .build();
+        // Two cells of 10 world units, zoom 2 centred on them fills the frame
+        let field = NutrientField {
+            origin: [0.0, 0.0],
+            cell_size: 10.0,
+            columns: 2,
+            rows: 1,
+            concentrations: vec![1.0, 0.5],
+        };
+        let camera = Camera {
+            center: [10.0, 5.0],
+            zoom: 2.0,
+        };
+        let mut frame = vec![7; config.frame_len()];
+        draw_nutrients(&field, &mut frame, &config, camera);
+        let pixel = |x: usize, y: usize| &frame[(y * 40 + x) * 4..(y * 40 + x) * 4 + 4];
+        assert_eq!(pixel(0, 0), NUTRIENT_COLOR);
+        assert_eq!(pixel(19, 19), NUTRIENT_COLOR);
+        let half = NUTRIENT_COLOR.map(|channel| (channel as f32 / 2.0).round() as u8);
+        assert_eq!(pixel(20, 0), [half[0], half[1], half[2], 255]);
+        assert_eq!(pixel(39, 19), [half[0], half[1], half[2], 255]);
+    }
+
+    #[test]
+    fn console_shows_the_input_and_errors_at_the_bottom() {
+        let config = RenderConfig::builder().width(200).height(60).build();
+        let mut frame = vec![0; config.frame_len()];
+        let error = Err("unknown command x".to_string());
+        draw_console(&mut frame, &config, "spawn 10", Some(&error));
+        let rows_with = |color: [u8; 4]| -> Vec<usize> {
+            (0..60)
+                .filter(|&y| {
+                    (0..200).any(|x| frame[(y * 200 + x) * 4..(y * 200 + x) * 4 + 4] == color)
+                })
+                .collect()
+        };
+        let (errors, text) = (
+            rows_with(CONSOLE_ERROR_COLOR),
+            rows_with(OVERLAY_TEXT_COLOR),
+        );
+        // Error line above the input, both in the bottom two rows of text
+        let input_top = 60 - (OVERLAY_MARGIN + OVERLAY_PADDING + OVERLAY_LINE_HEIGHT) as usize;
+        assert!(errors
+            .iter()
+            .all(|&y| y >= input_top - OVERLAY_LINE_HEIGHT as usize));
+        assert!(errors.iter().all(|&y| y < input_top));
+        assert!(!text.is_empty() && text.iter().all(|&y| y >= input_top));
+
+        let tiny = RenderConfig::builder().width(8).height(4).build();
+        let mut frame = vec![0; tiny.frame_len()];
+        draw_console(&mut frame, &tiny, "help", None);
+    }
+
+    #[test]
+    fn selection_ring_surrounds_the_particle() {
+        let config = RenderConfig::builder().width(40).height(40).build();
+        let mut camera = Camera::new([20.0, 20.0]);
+        camera.zoom = 2.0;
+        let mut world = RenderWorld {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            radii: Vec::new(),
+            colors: Vec::new(),
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [40.0, 40.0],
+            },
+            camera,
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        let mut frame = vec![0; config.frame_len()];
+        draw_selection(&mut frame, &config, &world);
+        assert!(frame.iter().all(|&channel| channel == 0));
+
+        // Radius 3 at zoom 2 plus the gap puts the ring 9 pixels out from the centre
+        world.selected = Some(([20.0, 20.0], 3.0));
+        draw_selection(&mut frame, &config, &world);
+        let pixel = |x: usize, y: usize| &frame[(y * 40 + x) * 4..(y * 40 + x) * 4 + 4];
+        assert_eq!(pixel(29, 20), SELECTION_COLOR);
+        assert_eq!(pixel(20, 10), SELECTION_COLOR);
+        assert_eq!(pixel(20, 20), [0; 4]);
+        assert_eq!(pixel(0, 0), [0; 4]);
+
+        // Partly off-screen rings are clipped
+        world.selected = Some(([0.0, 0.0], 3.0));
+        draw_selection(&mut frame, &config, &world);
+    }
+
+    #[test]
+    fn trails_fade_out_without_ghost_pixels() {
+        let mut frame = vec![255, 128, 1, 255];
+        fade(&mut frame, 0.5, [0; 4]);
+        assert_eq!(frame, [127, 64, 0, 127]);
+
+        // Even the longest trail reaches the background, one step closer per frame at worst
+        for _ in 0..255 {
+            fade(&mut frame, 0.999, [0; 4]);
+        }
+        assert_eq!(frame, [0; 4]);
+
+        let mut frame = vec![200; 8];
+        fade(&mut frame, 0.0, [0; 4]);
+        assert_eq!(frame, [0; 8]);
+
+        // Channels on both sides of a colored background converge onto it
+        let background = [40, 40, 80, 255];
+        let mut frame = vec![255, 0, 80, 0];
+        for _ in 0..255 {
+            fade(&mut frame, 0.999, background);
+        }
+        assert_eq!(frame, background);
+    }
+
+    #[test]
+    fn steady_state_frames_do_not_allocate() {
+        let config = RenderConfig::builder().width(1920).height(1080).build();
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 2000,
+            bounds: [1920.0, 1080.0],
+            // No births or deaths, so the population is the same size every frame
+            reproduction_threshold: f32::INFINITY,
+            basal_metabolism: 0.0,
+            movement_cost: 0.0,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        });
+        let camera = Camera::new([960.0, 540.0]);
+        let mut frame = vec![0; config.frame_len()];
+        let mut world =
+            RenderWorld::extract(core_state.world(), 0, ColorMode::Velocity, &[], camera);
+
+        let frames = 60;
+        let mut allocations = 0;
+        let mut render_time = Duration::ZERO;
+        for tick in 0..frames {
+            core_state.step(1.0 / 60.0);
+            let before = ALLOCATIONS.with(Cell::get);
+            let start = Instant::now();
+            world.refill(core_state.world(), tick, ColorMode::Velocity, &[], camera);
+            rasterize(&world, &mut frame, &config);
+            render_time += start.elapsed();
+            allocations += ALLOCATIONS.with(Cell::get) - before;
+        }
+        println!(
+            "1080p, 2000 particles: {:?} per frame, {} allocations in {} frames",
+            render_time / frames as u32,
+            allocations,
+            frames
+        );
+
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    fn extracting_100k_particles_stays_cheap() {
+        let core_state = CoreState::new(SimulationConfig {
+            particle_count: 100_000,
+            bounds: [4000.0, 4000.0],
+            ..SimulationConfig::default()
+        });
+        let particles = core_state.world().particles();
+
+        let extract_start = Instant::now();
+        let world = RenderWorld::extract(
+            core_state.world(),
+            0,
+            ColorMode::Velocity,
+            &[],
+            Camera::new([0.0; 2]),
+        );
+        let extract_time = extract_start.elapsed();
+
+        // What handing the snapshot to another thread costs on top of building it
+        let copy_start = Instant::now();
+        let copy = world.clone();
+        let copy_time = copy_start.elapsed();
+        let particle_copy_start = Instant::now();
+        let particle_copy = particles.to_vec();
+        let particle_copy_time = particle_copy_start.elapsed();
+
+        let snapshot_bytes = std::mem::size_of::<[f32; 2]>() * copy.positions.len()
+            + std::mem::size_of::<[f32; 2]>() * copy.velocities.len()
+            + std::mem::size_of::<f32>() * copy.radii.len()
+            + std::mem::size_of::<u32>() * copy.colors.len();
+        let particle_bytes = std::mem::size_of::<Particle>() * particle_copy.len();
+        println!(
+            "100k particles: extract {:?}, copy snapshot {:?} ({} KiB), copy particles {:?} ({} KiB)",
+            extract_time,
+            copy_time,
+            snapshot_bytes / 1024,
+            particle_copy_time,
+            particle_bytes / 1024
+        );
+        assert!(snapshot_bytes * 2 < particle_bytes);
+    }
+
+    #[test]
+    fn points_rasterize_1m_particles_faster_than_circles() {
+        let mut rng = SimRng::new(5);
+        let count = 1_000_000;
+        let world = RenderWorld {
+            positions: (0..count)
+                .map(|_| [rng.range_f32(0.0, 640.0), rng.range_f32(0.0, 480.0)])
+                .collect(),
+            velocities: vec![[0.0, 0.0]; count],
+            radii: vec![2.0; count],
+            colors: vec![pack_rgba([255; 4]); count],
+            food: Vec::new(),
+            obstacles: Vec::new(),
+            color_mode: ColorMode::Uniform,
+            color_range: (0.0, 0.0),
+            bounds: Rect {
+                min: [0.0, 0.0],
+                max: [640.0, 480.0],
+            },
+            camera: Camera::new([320.0, 240.0]),
+            tick: 0,
+            selected: None,
+            generation: (0, None),
+            grid: None,
+            region: None,
+            nutrients: None,
+        };
+        let time = |shape: ParticleShape| {
+            let config = RenderConfig::builder().particle_shape(shape).build();
+            let mut frame = vec![0; config.frame_len()];
+            let start = Instant::now();
+            rasterize(&world, &mut frame, &config);
+            start.elapsed()
+        };
+        let circle_time = time(ParticleShape::Circle);
+        let point_time = time(ParticleShape::Point);
+
+        // Only meaningful with optimizations, `cargo test --release points -- --nocapture`
+        println!(
+            "1M particles: circles {:?}, points {:?} ({:.1}x)",
+            circle_time,
+            point_time,
+            circle_time.as_secs_f64() / point_time.as_secs_f64()
+        );
+    }
+}
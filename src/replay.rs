@@ -0,0 +1,278 @@
+use crate::simulation::{Brush, CoreState, Rect};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// First line of every input log, bumped whenever its layout changes
+const LOG_MAGIC: &str = "particle_evolution replay";
+const LOG_VERSION: u32 = 1;
+
+// Everything from outside that changes where a run goes. Pausing and the speed multiplier only
+// change how fast ticks come, stepping is the same either way, so they aren't logged.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    SpawnAt([f32; 2]),
+    GravityWell(Option<[f32; 2]>),
+    Brush(Option<Brush>),
+    // Unit vector gravity turns towards
+    Gravity([f32; 2]),
+    Reset,
+    // Typed into the console, see `console::Command`
+    SpawnRandom(usize),
+    Set { name: String, values: Vec<f32> },
+    // Particles inside the rect dragged out with shift, removed with delete
+    DeleteRegion(Rect),
+    // Last entry of a session that ended cleanly, replaying stops here
+    End,
+}
+
+impl Input {
+    pub fn apply(self, core_state: &mut CoreState) {
+        match self {
+            Input::SpawnAt(position) => core_state.spawn_at(position),
+            Input::GravityWell(position) => core_state.set_gravity_well(position),
+            Input::Brush(brush) => core_state.set_brush(brush),
+            Input::Gravity(direction) => core_state.turn_gravity(direction),
+            Input::Reset => core_state.reset(),
+            Input::SpawnRandom(count) => core_state.spawn_random(count),
+            // Checked before it was logged, a replay sets exactly what the session did
+            Input::Set { name, values } => {
+                let _ = core_state.set_parameter(&name, &values);
+            }
+            Input::DeleteRegion(region) => {
+                core_state.delete_region(&region);
+            }
+            Input::End => {}
+        }
+    }
+}
+
+// Writes the starting state and then every input with the tick it was applied at, one line
+// each. Lines are flushed right away so the log survives the crash it is meant to reproduce.
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    // Truncates `path` and writes the header and `core_state` as it is before the first input
+    pub fn create(path: &Path, core_state: &CoreState) -> Result<Self, String> {
+        let describe = |error: &dyn std::fmt::Display| format!("{}: {error}", path.display());
+        let state = core_state.encode()?;
+        let mut writer = BufWriter::new(File::create(path).map_err(|error| describe(&error))?);
+        writeln!(writer, "{LOG_MAGIC} v{LOG_VERSION}\n{state}")
+            .and_then(|_| writer.flush())
+            .map_err(|error| describe(&error))?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, tick: u64, input: &Input) -> Result<(), String> {
+        let input = ron::to_string(input).map_err(|error| error.to_string())?;
+        writeln!(self.writer, "{tick} {input}")
+            .and_then(|_| self.writer.flush())
+            .map_err(|error| error.to_string())
+    }
+}
+
+// Inputs of a recorded session waiting for their tick
+#[derive(Debug)]
+pub struct Replay {
+    inputs: VecDeque<(u64, Input)>,
+    // Whether the log came from a session that ended cleanly
+    complete: bool,
+}
+
+impl Replay {
+    // Reads a log written by `InputRecorder`, returns the starting state and what happened to it
+    pub fn load(path: &Path) -> Result<(CoreState, Self), String> {
+        let contents =
+            fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    // Same as `load` for the contents of a file
+    pub fn parse(contents: &str) -> Result<(CoreState, Self), String> {
+        let mut lines = contents.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(LOG_MAGIC))
+            .and_then(|version| version.trim().strip_prefix('v'))
+            .ok_or("not a particle_evolution input log")?;
+        if version != LOG_VERSION.to_string() {
+            return Err(format!(
+                "input log format {version}, only v{LOG_VERSION} is supported"
+            ));
+        }
+        let state = [lines.next(), lines.next()]
+            .map(Option::unwrap_or_default)
+            .join("\n");
+        let core_state = CoreState::decode(&state)?;
+
+        let mut inputs = VecDeque::new();
+        for (index, line) in lines.enumerate() {
+            // Three lines of header and state come before the first input
+            let number = index + 4;
+            let (tick, input) = line
+                .split_once(' ')
+                .ok_or_else(|| format!("{number}: expected `<tick> <input>`"))?;
+            let tick = tick
+                .parse()
+                .map_err(|_| format!("{number}: `{tick}` is not a tick"))?;
+            let input = ron::from_str(input).map_err(|error| format!("{number}: {error}"))?;
+            inputs.push_back((tick, input));
+        }
+        let complete = matches!(inputs.back(), Some((_, Input::End)));
+        Ok((core_state, Self { inputs, complete }))
+    }
+
+    // Applies every input recorded at the current tick, call it before each step. Resets take
+    // the tick back, so inputs are only ever compared in the order they were recorded.
+    pub fn apply_due(&mut self, core_state: &mut CoreState) {
+        while let Some((tick, _)) = self.inputs.front() {
+            if *tick > core_state.tick() {
+                break;
+            }
+            if let Some((_, input)) = self.inputs.pop_front() {
+                input.apply(core_state);
+            }
+        }
+    }
+
+    // Whether every input was applied, sessions that crashed leave no end to wait for
+    pub fn finished(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+}
+
+// Where input changing the world comes from in a session
+pub enum InputLog {
+    // Straight from the user
+    Off,
+    // From the user, writing it down on the way
+    Record(InputRecorder),
+    // From a log, the user only watches until it runs out
+    Replay(Replay),
+}
+
+impl InputLog {
+    pub fn replaying(&self) -> bool {
+        matches!(self, InputLog::Replay(_))
+    }
+
+    // Applies input from the user unless a replay is in charge
+    pub fn submit(&mut self, core_state: &mut CoreState, input: Input) {
+        match self {
+            InputLog::Off => input.apply(core_state),
+            InputLog::Record(recorder) => {
+                // A failed write stops recording instead of failing every input after it
+                if let Err(error) = recorder.record(core_state.tick(), &input) {
+                    eprintln!("Failed to write input log, stopping: {error}");
+                    *self = InputLog::Off;
+                }
+                input.apply(core_state);
+            }
+            InputLog::Replay(_) => {}
+        }
+    }
+
+    // Injects replayed input due at the current tick, call it before every step. The user takes
+    // over once the log runs out.
+    pub fn before_step(&mut self, core_state: &mut CoreState) {
+        let InputLog::Replay(replay) = self else {
+            return;
+        };
+        replay.apply_due(core_state);
+        if replay.finished() {
+            if replay.complete() {
+                println!("Replay finished at tick {}", core_state.tick());
+            } else {
+                println!(
+                    "Replay ran out of input at tick {}, the recorded session didn't end cleanly",
+                    core_state.tick()
+                );
+            }
+            *self = InputLog::Off;
+        }
+    }
+
+    // Marks a recording as complete so its replay stops where this session does
+    pub fn finish(self, tick: u64) {
+        if let InputLog::Record(mut recorder) = self {
+            if let Err(error) = recorder.record(tick, &Input::End) {
+                eprintln!("Failed to finish input log: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SimulationConfig;
+
+    #[test]
+    fn replay_reproduces_the_recorded_session() {
+        let path = std::env::temp_dir().join("particle_evolution_replay_test.log");
+        let config = SimulationConfig {
+            particle_count: 30,
+            seed: 99,
+            ..SimulationConfig::default()
+        };
+        let inputs = [
+            (5, Input::SpawnAt([100.0, 100.0])),
+            (5, Input::Gravity([1.0, 0.0])),
+            (
+                12,
+                Input::Brush(Some(Brush {
+                    center: [300.0, 200.0],
+                    radius: 30.0,
+                    density: 0.05,
+                })),
+            ),
+            (20, Input::Brush(None)),
+            (40, Input::Reset),
+            (3, Input::GravityWell(Some([320.0, 240.0]))),
+        ];
+
+        // The live session applies inputs between steps and logs them with the tick
+        let mut live = CoreState::new(config);
+        let mut recorder = InputRecorder::create(&path, &live).unwrap();
+        let mut pending = inputs.iter().cloned().peekable();
+        for _ in 0..100 {
+            while let Some((_, input)) = pending.next_if(|(tick, _)| *tick == live.tick()) {
+                recorder.record(live.tick(), &input).unwrap();
+                input.apply(&mut live);
+            }
+            live.step(1.0 / 120.0);
+        }
+        recorder.record(live.tick(), &Input::End).unwrap();
+        drop(recorder);
+
+        let (mut replayed, mut replay) = Replay::load(&path).unwrap();
+        assert!(replay.complete());
+        while !replay.finished() {
+            replay.apply_due(&mut replayed);
+            if !replay.finished() {
+                replayed.step(1.0 / 120.0);
+            }
+        }
+        assert_eq!(replayed.tick(), live.tick());
+        assert_eq!(replayed.world().particles(), live.world().particles());
+        assert_eq!(replayed.world().food(), live.world().food());
+
+        assert!(Replay::parse("particle_evolution replay v2\n").is_err());
+        let state = live.encode().unwrap();
+        let Err(error) = Replay::parse(&format!(
+            "particle_evolution replay v1\n{state}\n7 Teleport"
+        )) else {
+            panic!("unknown inputs have to be rejected");
+        };
+        assert!(error.starts_with("4: "), "{error}");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// `screenshot-<unix millis>.png` in the working directory, unique enough for interactive use
+pub fn timestamped_path() -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    PathBuf::from(format!("screenshot-{millis}.png"))
+}
+
+// Encodes a tightly packed RGBA frame of `width` x `height` pixels as a PNG at `path`
+pub fn save_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), String> {
+    if rgba.len() != width as usize * height as usize * 4 {
+        return Err(format!(
+            "frame of {} bytes doesn't match {width}x{height}",
+            rgba.len()
+        ));
+    }
+
+    let file = File::create(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|error| format!("{}: {error}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_png_decodes_to_the_same_pixels() {
+        let path = std::env::temp_dir().join("particle_evolution_screenshot_test.png");
+        let rgba = [255, 0, 0, 255, 0, 0, 255, 128];
+        save_png(&path, 2, 1, &rgba).unwrap();
+        assert!(save_png(&path, 3, 1, &rgba).is_err());
+
+        let mut reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(decoded, rgba);
+        let _ = std::fs::remove_file(&path);
+    }
+}
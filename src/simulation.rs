@@ -0,0 +1,2965 @@
+use crate::collision::{self, AllPairs, Broadphase};
+use crate::emitter::Emitter;
+use crate::energy::EnergyMonitor;
+use crate::generation::GenerationTracker;
+use crate::genome::Genome;
+use crate::layout::LayoutParticle;
+use crate::nutrients::{NutrientConfig, NutrientField};
+use crate::obstacle::{self, Obstacle};
+use crate::profiler::Profiler;
+use crate::quadtree::QuadTree;
+use crate::rng::{self, SimRng};
+use crate::spatial::{GridLayout, SpatialGrid};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Range the simulation speed multiplier can be scaled within
+const MIN_SPEED_MULTIPLIER: f32 = 0.25;
+const MAX_SPEED_MULTIPLIER: f32 = 8.0;
+
+// First line of every save file, bumped whenever the layout of `Snapshot` changes
+const SAVE_MAGIC: &str = "particle_evolution save";
+const SAVE_VERSION: u32 = 11;
+
+const DEFAULT_PARTICLE_MASS: f32 = 1.0;
+const DEFAULT_PARTICLE_RADIUS: f32 = 3.0;
+// Interactions between more species than fit in `Particle::species` can't be looked up
+const MAX_SPECIES: usize = u8::MAX as usize + 1;
+// Okabe-Ito colors without the black, told apart with any kind of color blindness
+const DEFAULT_SPECIES_COLORS: [[u8; 3]; 7] = [
+    [230, 159, 0],
+    [86, 180, 233],
+    [0, 158, 115],
+    [240, 228, 66],
+    [0, 114, 178],
+    [213, 94, 0],
+    [204, 121, 167],
+];
+// Clicks this far outside of a particle still pick it, small ones are hard to hit exactly
+const PICK_MARGIN: f32 = 4.0;
+// Simulated seconds gravity takes to swing over to a new direction
+const GRAVITY_TURN_TIME: f32 = 0.25;
+pub const FOOD_RADIUS: f32 = 2.0;
+// Degrees a child's lineage hue can differ from its parent's, families stay recognisable for a
+// few generations and slowly fan out after that
+const LINEAGE_HUE_DRIFT: f32 = 8.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Particle {
+    // Unique within a run and handed out in birth order, see `CoreWorld::particles`
+    pub id: u64,
+    // Row and column into `SimulationConfig::interaction_matrix`, inherited by children
+    pub species: u8,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    // Acceleration at the end of the last step, only kept by velocity Verlet. Zero for particles
+    // that haven't been through a step yet.
+    pub acceleration: [f32; 2],
+    // Hue in degrees shared loosely by a family, founders start at `id_hue` and every child
+    // strays a little from its parent's
+    pub lineage_hue: f32,
+    pub mass: f32,
+    pub radius: f32,
+    pub genome: Genome,
+    // Dies once this runs out, reproduces once it passes the configured threshold
+    pub energy: f32,
+    // Simulated seconds since it was spawned or born
+    pub age: f32,
+}
+
+impl Particle {
+    // Resting particle whose size and mass follow from its genome
+    pub fn new(id: u64, species: u8, position: [f32; 2], genome: Genome, energy: f32) -> Self {
+        Self {
+            id,
+            species,
+            position,
+            velocity: [0.0, 0.0],
+            acceleration: [0.0, 0.0],
+            lineage_hue: id_hue(id),
+            // Mass grows with the area of the particle
+            mass: DEFAULT_PARTICLE_MASS * genome.size_factor * genome.size_factor,
+            radius: DEFAULT_PARTICLE_RADIUS * genome.size_factor,
+            genome,
+            energy,
+            age: 0.0,
+        }
+    }
+}
+
+// Stable pseudo-random hue in [0, 360) for the particle `id`, the same every frame and every run
+pub fn id_hue(id: u64) -> f32 {
+    (rng::splitmix64(id) >> 40) as f32 / (1u64 << 24) as f32 * 360.0
+}
+
+// Static pellet that hands its energy to the first particle touching it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Food {
+    pub position: [f32; 2],
+    pub energy: f32,
+}
+
+// What happens to particles reaching the edge of the world
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoundaryMode {
+    // Bounce off the walls, losing energy as set by `restitution`
+    #[default]
+    Reflect,
+    // Leave through one edge and come back in through the opposite one, the world is a torus
+    Wrap,
+    // No walls, particles are free to drift away forever
+    Open,
+}
+
+// How positions and velocities advance over a step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Integrator {
+    // Velocity first, then position with the new velocity. First order, one force evaluation per
+    // step.
+    #[default]
+    SemiImplicitEuler,
+    // Position from the last step's acceleration, then velocity from the average of that and the
+    // one at the new position. Second order and time reversible, so orbits keep their energy
+    // much better at the same single force evaluation per step.
+    VelocityVerlet,
+}
+
+// How the pairs of touching particles are found, both ways find exactly the same ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollisionDetection {
+    // Uniform spatial grid, close to linear in the number of particles
+    #[default]
+    Grid,
+    // Checks every pair, quadratic. For validating that the grid doesn't miss any.
+    AllPairs,
+}
+
+// Velocity particles are spawned with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum VelocityDistribution {
+    // At rest
+    #[default]
+    Zero,
+    // Heading in a uniformly random direction at a speed uniform in `min_speed..max_speed`
+    Uniform {
+        min_speed: f32,
+        max_speed: f32,
+    },
+    // Like a 2D ideal gas, each axis is a normal sample of variance `temperature / mass` so the
+    // mean kinetic energy is `temperature`
+    MaxwellBoltzmann {
+        temperature: f32,
+    },
+}
+
+// How parents get to reproduce
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelectionStrategy {
+    // Every particle reaching `reproduction_threshold` splits its energy with a child
+    #[default]
+    Threshold,
+    // Every `tournament_interval` ticks `tournament_size` particles are drawn at random, the
+    // most energetic of them has a child and the least energetic dies
+    Tournament,
+}
+
+// Which particles die when the population grows past `SimulationConfig::max_particles`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+pub enum CullPolicy {
+    // Lowest ids first, the population keeps renewing itself
+    #[default]
+    OldestDies,
+    // Least energy first, older particles lose ties
+    LowestEnergyDies,
+    // Uniformly at random from the seeded RNG
+    RandomDies,
+}
+
+// Spring between the particles with ids `a` and `b`, removed once either of them dies
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bond {
+    pub a: u64,
+    pub b: u64,
+    // Distance between the centres at which the spring is relaxed
+    pub rest_length: f32,
+    // Force per unit of stretch or compression
+    pub stiffness: f32,
+}
+
+// Disc particles are painted into while the spawn brush is held down
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Brush {
+    pub center: [f32; 2],
+    pub radius: f32,
+    // Particles spawned per second per unit of area
+    pub density: f32,
+}
+
+// Parameters of a simulation run, everything stochastic is derived from `seed`.
+// Fields missing from a config file keep their default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub particle_count: usize,
+    // Velocity of particles spawned at the start, by clicking or by the brush. Children start
+    // out at rest next to their parent either way.
+    pub initial_velocity: VelocityDistribution,
+    // Width and height of the area particles are spawned in, in world units. Everything in the
+    // simulation is measured in those, the camera maps them to pixels (1:1 at the default zoom).
+    pub bounds: [f32; 2],
+    pub boundary: BoundaryMode,
+    // Global acceleration applied to every particle, zero for force free space
+    pub gravity: [f32; 2],
+    // Fraction of the normal velocity kept when bouncing off a wall or obstacle, 1.0 is
+    // perfectly elastic
+    pub restitution: f32,
+    // Fraction of the relative normal velocity two colliding particles keep, 1.0 bounces them
+    // apart elastically and 0.0 has them stick together
+    pub collision_restitution: f32,
+    // Collision restitution between a particle of the row species and one of the column
+    // species, in place of `collision_restitution`. Symmetric with a row and column per species
+    // of `interaction_matrix`, empty for the same value between all of them.
+    pub restitution_matrix: Vec<Vec<f32>>,
+    // Gap left between a particle and the wall it bounced off. Within it a particle counts as
+    // touching, so one resting on the floor doesn't fall in and out of contact every step.
+    pub wall_padding: f32,
+    // Bounces off a wall slower than this end with the particle at rest against it, instead of
+    // hopping on the spot forever
+    pub wall_rest_speed: f32,
+    // Static circles and boxes particles bounce off, for building mazes and funnels. They don't
+    // wrap around the edges of a wrapping world.
+    pub obstacles: Vec<Obstacle>,
+    // Segments spawning particles at a steady rate, as long as the population is below
+    // `max_particles`
+    pub emitters: Vec<Emitter>,
+    pub collision_detection: CollisionDetection,
+    // Every step is split into this many equal substeps of motion and collision resolution,
+    // more keep dense packings from sinking into each other and jittering. Feeding, births and
+    // deaths still happen once per step.
+    pub collision_substeps: u32,
+    // Drag slowing every particle down, the velocity decays by a factor of e every
+    // 1 / `linear_damping` seconds. 0.0 for frictionless space.
+    pub linear_damping: f32,
+    // Strength of random Brownian kicks, every step changes each velocity axis by a normal
+    // sample of variance `temperature * dt / mass`. Together with damping particles diffuse,
+    // 0.0 turns the kicks off.
+    pub temperature: f32,
+    // Strength of the mutual attraction between particles, 0.0 turns N-body gravity off
+    pub gravitational_constant: f32,
+    // Barnes-Hut opening angle, lower is more accurate and slower
+    pub theta: f32,
+    // Plummer softening length of N-body gravity, pairs closer than this stop pulling harder.
+    // 0.0 for the exact inverse square law.
+    pub softening: f32,
+    // Upper bound on the magnitude of the net acceleration of every particle, `None` leaves it
+    // unbounded. Either this or `softening` keeps near collisions from blowing up the world.
+    pub max_acceleration: Option<f32>,
+    pub integrator: Integrator,
+    // Speed limit of a particle with a neutral genome, scaled by its `speed_factor`
+    pub max_speed: f32,
+    // Chance of each genome trait mutating when a particle reproduces
+    pub mutation_rate: f32,
+    // Energy every particle is spawned with
+    pub initial_energy: f32,
+    // Energy spent per second just staying alive, scaled by `size_factor`
+    pub basal_metabolism: f32,
+    // Energy spent per second per unit of speed, scaled by `size_factor`
+    pub movement_cost: f32,
+    // Energy every particle gains per second regardless of what it does
+    pub ambient_energy_gain: f32,
+    pub selection: SelectionStrategy,
+    // A particle with at least this much energy splits it evenly with a newborn child, only
+    // used by threshold selection
+    pub reproduction_threshold: f32,
+    // Particles competing in each tournament and ticks between two of them, only used by
+    // tournament selection
+    pub tournament_size: usize,
+    pub tournament_interval: u64,
+    // Population cap enforced at the end of every step by culling as `cull_policy` says, `None`
+    // lets it grow without bound
+    pub max_particles: Option<usize>,
+    pub cull_policy: CullPolicy,
+    // Seconds a particle lives at most however much energy it has, `None` for no limit. It fades
+    // out on screen towards the end.
+    pub max_lifetime: Option<f32>,
+    // Food pellets spawned per second at random positions
+    pub food_spawn_rate: f32,
+    // Energy a particle gains from eating one pellet
+    pub food_energy: f32,
+    // No more pellets are spawned while this many are lying around
+    pub max_food: usize,
+    // Field of nutrients regrowing in every cell of the world that particles feed on wherever
+    // they go, `None` for none
+    pub nutrients: Option<NutrientConfig>,
+    // Strength with which a particle of the row species is pulled towards one of the column
+    // species, negative values repel. One row and column per species, empty for a single
+    // species without interactions.
+    pub interaction_matrix: Vec<Vec<f32>>,
+    // Species interactions fade out linearly up to this distance
+    pub interaction_radius: f32,
+    // RGB color of each species in the species color mode, indexed by species. Species past
+    // the end get distinct generated colors.
+    pub species_colors: Vec<[u8; 3]>,
+    // Pull of the cursor gravity well, acceleration at unit distance
+    pub well_strength: f32,
+    // Particles further away than this from the well are unaffected
+    pub well_radius: f32,
+    // The pull stops growing closer than this, so particles can't be flung to infinity
+    pub well_min_distance: f32,
+    // Wall clock time after which the simulation stops and closes the window, `None` runs forever
+    pub max_runtime: Option<Duration>,
+    // Wall clock seconds the windowed simulation may fall behind before it warns that it can't
+    // keep up and drops the backlog, instead of trying to catch up forever
+    pub max_lag: f32,
+}
+
+impl SimulationConfig {
+    pub fn species_count(&self) -> usize {
+        self.interaction_matrix.len().max(1)
+    }
+
+    // Coefficient for a particle of species `own` reacting to one of species `other`
+    pub fn interaction(&self, own: u8, other: u8) -> f32 {
+        self.interaction_matrix
+            .get(own as usize)
+            .and_then(|row| row.get(other as usize))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // Restitution of a collision between particles of species `a` and `b`
+    pub fn pair_restitution(&self, a: u8, b: u8) -> f32 {
+        self.restitution_matrix
+            .get(a as usize)
+            .and_then(|row| row.get(b as usize))
+            .copied()
+            .unwrap_or(self.collision_restitution)
+    }
+
+    // Changes `name` to `values`, one number for most parameters and two for vectors. Only the
+    // ones a running world copes with can be set, the others shape the world it generates.
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), String> {
+        let mut changed = self.clone();
+        let field = match name {
+            "gravity" => {
+                let [x, y] = values else {
+                    return Err(format!("gravity takes 2 values, got {}", values.len()));
+                };
+                changed.gravity = [*x, *y];
+                None
+            }
+            "restitution" => Some(&mut changed.restitution),
+            "collision_restitution" => Some(&mut changed.collision_restitution),
+            "wall_padding" => Some(&mut changed.wall_padding),
+            "wall_rest_speed" => Some(&mut changed.wall_rest_speed),
+            "linear_damping" => Some(&mut changed.linear_damping),
+            "temperature" => Some(&mut changed.temperature),
+            "gravitational_constant" => Some(&mut changed.gravitational_constant),
+            "max_speed" => Some(&mut changed.max_speed),
+            "mutation_rate" => Some(&mut changed.mutation_rate),
+            "basal_metabolism" => Some(&mut changed.basal_metabolism),
+            "movement_cost" => Some(&mut changed.movement_cost),
+            "ambient_energy_gain" => Some(&mut changed.ambient_energy_gain),
+            "reproduction_threshold" => Some(&mut changed.reproduction_threshold),
+            "food_spawn_rate" => Some(&mut changed.food_spawn_rate),
+            "food_energy" => Some(&mut changed.food_energy),
+            "interaction_radius" => Some(&mut changed.interaction_radius),
+            "well_strength" => Some(&mut changed.well_strength),
+            "well_radius" => Some(&mut changed.well_radius),
+            _ => {
+                return Err(format!(
+                    "{name} is not a parameter that can be set while running"
+                ))
+            }
+        };
+        if let Some(field) = field {
+            let [value] = values else {
+                return Err(format!("{name} takes 1 value, got {}", values.len()));
+            };
+            *field = *value;
+        }
+        if !values.iter().all(|value| value.is_finite()) {
+            return Err(format!("{name} must be finite"));
+        }
+        changed.validate()?;
+        *self = changed;
+        Ok(())
+    }
+
+    // Catches mistakes a hand written config can make that deserializing doesn't
+    pub fn validate(&self) -> Result<(), String> {
+        let species = self.interaction_matrix.len();
+        if species > MAX_SPECIES {
+            return Err(format!(
+                "interaction_matrix has {species} species, at most {MAX_SPECIES} are supported"
+            ));
+        }
+        if let Some(row) = self
+            .interaction_matrix
+            .iter()
+            .position(|row| row.len() != species)
+        {
+            return Err(format!(
+                "interaction_matrix must be {species}x{species}, row {row} has {} entries",
+                self.interaction_matrix[row].len()
+            ));
+        }
+        let species = self.restitution_matrix.len();
+        if species > 0 && species != self.species_count() {
+            return Err(format!(
+                "restitution_matrix has {species} species, interaction_matrix has {}",
+                self.species_count()
+            ));
+        }
+        for (row, values) in self.restitution_matrix.iter().enumerate() {
+            if values.len() != species {
+                return Err(format!(
+                    "restitution_matrix must be {species}x{species}, row {row} has {} entries",
+                    values.len()
+                ));
+            }
+            for (column, &value) in values.iter().enumerate() {
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(format!(
+                        "restitution_matrix entries must be between 0 and 1, got {value}"
+                    ));
+                }
+                if self
+                    .restitution_matrix
+                    .get(column)
+                    .and_then(|other| other.get(row))
+                    != Some(&value)
+                {
+                    return Err(format!(
+                        "restitution_matrix must be symmetric, entry {row},{column} differs"
+                    ));
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&self.collision_restitution) {
+            return Err(format!(
+                "collision_restitution must be between 0 and 1, got {}",
+                self.collision_restitution
+            ));
+        }
+        if self.softening.is_nan() || self.softening < 0.0 {
+            return Err(format!(
+                "softening must not be negative, got {}",
+                self.softening
+            ));
+        }
+        for (name, value) in [
+            ("wall_padding", self.wall_padding),
+            ("wall_rest_speed", self.wall_rest_speed),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("{name} must not be negative, got {value}"));
+            }
+        }
+        if self.selection == SelectionStrategy::Tournament {
+            if self.tournament_size < 2 {
+                return Err(format!(
+                    "tournament_size must be at least 2, got {}",
+                    self.tournament_size
+                ));
+            }
+            if self.tournament_interval == 0 {
+                return Err("tournament_interval must be at least 1 tick".to_string());
+            }
+        }
+        match self.initial_velocity {
+            VelocityDistribution::Zero => {}
+            VelocityDistribution::Uniform {
+                min_speed,
+                max_speed,
+            } => {
+                if !(min_speed >= 0.0 && min_speed <= max_speed && max_speed.is_finite()) {
+                    return Err(format!(
+                        "initial_velocity needs 0 <= min_speed <= max_speed, got {min_speed} and \
+                         {max_speed}"
+                    ));
+                }
+            }
+            VelocityDistribution::MaxwellBoltzmann { temperature } => {
+                if !(temperature >= 0.0 && temperature.is_finite()) {
+                    return Err(format!(
+                        "initial_velocity temperature must not be negative, got {temperature}"
+                    ));
+                }
+            }
+        }
+        for obstacle in &self.obstacles {
+            obstacle.validate()?;
+        }
+        for emitter in &self.emitters {
+            emitter.validate()?;
+        }
+        if let Some(nutrients) = &self.nutrients {
+            nutrients.validate()?;
+        }
+        if self.collision_substeps == 0 {
+            return Err("collision_substeps must be at least 1".to_string());
+        }
+        if self.max_lag.is_nan() || self.max_lag <= 0.0 {
+            return Err(format!("max_lag must be positive, got {}", self.max_lag));
+        }
+        if let Some(max_lifetime) = self.max_lifetime.filter(|max| max.is_nan() || *max <= 0.0) {
+            return Err(format!("max_lifetime must be positive, got {max_lifetime}"));
+        }
+        if let Some(max_acceleration) = self
+            .max_acceleration
+            .filter(|max| max.is_nan() || *max <= 0.0)
+        {
+            return Err(format!(
+                "max_acceleration must be positive, got {max_acceleration}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            particle_count: 500,
+            initial_velocity: VelocityDistribution::Zero,
+            bounds: [640.0, 480.0],
+            boundary: BoundaryMode::Reflect,
+            gravity: [0.0, 9.81],
+            restitution: 0.9,
+            collision_restitution: 1.0,
+            restitution_matrix: Vec::new(),
+            wall_padding: 0.01,
+            wall_rest_speed: 1.0,
+            obstacles: Vec::new(),
+            emitters: Vec::new(),
+            collision_detection: CollisionDetection::Grid,
+            collision_substeps: 1,
+            linear_damping: 0.0,
+            temperature: 0.0,
+            gravitational_constant: 0.0,
+            theta: 0.5,
+            softening: 0.0,
+            max_acceleration: None,
+            integrator: Integrator::SemiImplicitEuler,
+            max_speed: 200.0,
+            mutation_rate: 0.1,
+            initial_energy: 50.0,
+            basal_metabolism: 0.5,
+            movement_cost: 0.02,
+            ambient_energy_gain: 1.0,
+            selection: SelectionStrategy::Threshold,
+            reproduction_threshold: 100.0,
+            tournament_size: 4,
+            tournament_interval: 60,
+            max_particles: None,
+            max_lifetime: None,
+            cull_policy: CullPolicy::OldestDies,
+            food_spawn_rate: 20.0,
+            food_energy: 10.0,
+            max_food: 500,
+            nutrients: None,
+            interaction_matrix: Vec::new(),
+            interaction_radius: 40.0,
+            species_colors: DEFAULT_SPECIES_COLORS.to_vec(),
+            well_strength: 2_000_000.0,
+            well_radius: 200.0,
+            well_min_distance: 10.0,
+            max_runtime: None,
+            max_lag: 0.25,
+        }
+    }
+}
+
+pub struct CoreState {
+    config: SimulationConfig,
+    rng: SimRng,
+    world: CoreWorld,
+    broadphase: Box<dyn Broadphase + Send>,
+    // Scratch buffer reused by the broadphase every step
+    collision_pairs: Vec<(usize, usize)>,
+    quadtree: QuadTree,
+    food_grid: SpatialGrid,
+    interaction_grid: SpatialGrid,
+    // Static entries for the obstacles, built once as they never move
+    obstacle_grid: SpatialGrid,
+    // Largest bounding radius of an obstacle, how far a grid query has to reach beyond a particle
+    obstacle_reach: f32,
+    // Fractional particles every emitter carries over between steps
+    emitter_debts: Vec<f32>,
+    // Fractional pellets carried over between steps
+    food_spawn_debt: f32,
+    // World position of the cursor gravity well while it is held down
+    gravity_well: Option<[f32; 2]>,
+    // Spawn brush while it is held down, painting carries fractional particles over like food
+    brush: Option<Brush>,
+    paint_debt: f32,
+    // While set `step` leaves the world untouched
+    paused: bool,
+    // Simulated seconds per wall clock second, 0.0 behaves like pause
+    speed_multiplier: f32,
+    // Steps taken since the world was generated
+    tick: u64,
+    // Id of the next particle born
+    next_id: u64,
+    // Time spent in each phase of `step`, disabled unless asked for
+    profiler: Profiler,
+    // Gravity swinging over to a new direction, see `turn_gravity`
+    gravity_turn: Option<GravityTurn>,
+    // Logs the total energy every tick while validating the integrator, off by default
+    energy_monitor: Option<EnergyMonitor>,
+    // Arrangement the world started from instead of random particles, kept for `reset`
+    layout: Option<Vec<LayoutParticle>>,
+    generation: GenerationTracker,
+}
+
+// Blend from one gravity vector to another over `GRAVITY_TURN_TIME`
+#[derive(Clone, Copy, Debug)]
+struct GravityTurn {
+    from: [f32; 2],
+    to: [f32; 2],
+    elapsed: f32,
+}
+
+// Everything a save file needs to continue a run exactly where it stopped. Acceleration
+// structures are rebuilt every step and interactive settings belong to the session.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    config: SimulationConfig,
+    rng: SimRng,
+    tick: u64,
+    next_id: u64,
+    particles: Vec<Particle>,
+    food: Vec<Food>,
+    bonds: Vec<Bond>,
+    nutrients: Option<NutrientField>,
+    food_spawn_debt: f32,
+    emitter_debts: Vec<f32>,
+    generation: GenerationTracker,
+    // Resets of a resumed run regenerate the same world as before saving
+    layout: Option<Vec<LayoutParticle>>,
+}
+
+impl Snapshot {
+    // Same as `CoreState::encode` of the state it was taken from
+    pub fn encode(&self) -> Result<String, String> {
+        let body = ron::to_string(self).map_err(|error| error.to_string())?;
+        Ok(format!("{SAVE_MAGIC} v{SAVE_VERSION}\n{body}"))
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+impl CoreState {
+    // Seeds `config.particle_count` particles at random positions within the bounds
+    pub fn new(config: SimulationConfig) -> Self {
+        let bounds = Rect {
+            min: [0.0, 0.0],
+            max: config.bounds,
+        };
+        // Neighbours across opposite edges only exist in a wrapping world
+        let grid = || match config.boundary {
+            BoundaryMode::Wrap => SpatialGrid::periodic(bounds.clone()),
+            BoundaryMode::Reflect | BoundaryMode::Open => SpatialGrid::new(),
+        };
+        let mut state = Self {
+            rng: SimRng::new(config.seed),
+            world: CoreWorld {
+                particles: Vec::with_capacity(config.particle_count),
+                food: Vec::new(),
+                bonds: Vec::new(),
+                obstacles: config.obstacles.clone(),
+                nutrients: config
+                    .nutrients
+                    .as_ref()
+                    .map(|nutrients| NutrientField::new(&bounds, nutrients)),
+                bounds: bounds.clone(),
+            },
+            broadphase: match (config.collision_detection, config.boundary) {
+                (CollisionDetection::Grid, _) => Box::new(grid()),
+                (CollisionDetection::AllPairs, BoundaryMode::Wrap) => {
+                    Box::new(AllPairs::periodic(bounds.clone()))
+                }
+                (CollisionDetection::AllPairs, _) => Box::new(AllPairs::new()),
+            },
+            collision_pairs: Vec::new(),
+            quadtree: QuadTree::new(),
+            food_grid: grid(),
+            interaction_grid: grid(),
+            obstacle_grid: SpatialGrid::new(),
+            obstacle_reach: 0.0,
+            emitter_debts: vec![0.0; config.emitters.len()],
+            food_spawn_debt: 0.0,
+            gravity_well: None,
+            brush: None,
+            paint_debt: 0.0,
+            paused: false,
+            speed_multiplier: 1.0,
+            tick: 0,
+            next_id: 0,
+            profiler: Profiler::default(),
+            gravity_turn: None,
+            energy_monitor: None,
+            layout: None,
+            generation: GenerationTracker::new(0),
+            config,
+        };
+
+        let obstacles = &state.world.obstacles;
+        state
+            .obstacle_grid
+            .rebuild_with(obstacles.len(), |index| obstacles[index].bounding_circle());
+        state.obstacle_reach = obstacles
+            .iter()
+            .map(|obstacle| obstacle.bounding_circle().1)
+            .fold(0.0, f32::max);
+
+        for _ in 0..state.config.particle_count {
+            state.spawn_random_particle();
+        }
+        state.generation = GenerationTracker::new(state.next_id);
+
+        state
+    }
+
+    // Starts from the particles of `layout` with neutral genomes instead of random ones
+    pub fn from_layout(mut config: SimulationConfig, layout: Vec<LayoutParticle>) -> Self {
+        config.particle_count = 0;
+        let mut state = Self::new(config);
+        for row in &layout {
+            let id = state.take_id();
+            let mut particle = Particle::new(
+                id,
+                row.species,
+                row.position,
+                Genome::default(),
+                state.config.initial_energy,
+            );
+            particle.velocity = row.velocity;
+            state.world.particles.push(particle);
+        }
+        state.config.particle_count = layout.len();
+        state.generation = GenerationTracker::new(state.next_id);
+        state.layout = Some(layout);
+        state
+    }
+
+    // Regenerates the world from the seed (or the layout) as if freshly started. Interactive settings
+    // (pause, speed, a held gravity well or brush), the profiler and the energy check carry over.
+    pub fn reset(&mut self) {
+        let paused = self.paused;
+        let speed_multiplier = self.speed_multiplier;
+        let gravity_well = self.gravity_well;
+        let brush = self.brush.take();
+        let profiler = std::mem::take(&mut self.profiler);
+        let energy_check = self
+            .energy_monitor
+            .as_ref()
+            .map(EnergyMonitor::threshold_percent);
+        // The regenerated world starts with gravity where it was headed
+        if let Some(turn) = self.gravity_turn.take() {
+            self.config.gravity = turn.to;
+        }
+
+        *self = match self.layout.take() {
+            Some(layout) => Self::from_layout(self.config.clone(), layout),
+            None => Self::new(self.config.clone()),
+        };
+        self.paused = paused;
+        self.speed_multiplier = speed_multiplier;
+        self.gravity_well = gravity_well;
+        self.brush = brush;
+        self.profiler = profiler;
+        self.set_energy_check(energy_check);
+    }
+
+    // Writes the world, RNG state and config to `path`, see `load`
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = self.encode()?;
+        fs::write(path, contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    // Restores a state written by `save`, stepping it continues the run as if it never stopped
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        Self::decode(&contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    // Contents of a save file, two lines of text
+    pub fn encode(&self) -> Result<String, String> {
+        self.snapshot().encode()
+    }
+
+    // Copy of everything `encode` writes, cheap next to encoding it, which can then happen on
+    // another thread
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            config: self.config.clone(),
+            rng: self.rng.clone(),
+            tick: self.tick,
+            next_id: self.next_id,
+            particles: self.world.particles.clone(),
+            food: self.world.food.clone(),
+            bonds: self.world.bonds.clone(),
+            nutrients: self.world.nutrients.clone(),
+            food_spawn_debt: self.food_spawn_debt,
+            emitter_debts: self.emitter_debts.clone(),
+            generation: self.generation.clone(),
+            layout: self.layout.clone(),
+        }
+    }
+
+    // Inverse of `encode`
+    pub fn decode(contents: &str) -> Result<Self, String> {
+        let (header, body) = contents.split_once('\n').unwrap_or((contents, ""));
+        let version = header
+            .strip_prefix(SAVE_MAGIC)
+            .and_then(|version| version.trim().strip_prefix('v'))
+            .ok_or("not a particle_evolution save")?;
+        if version != SAVE_VERSION.to_string() {
+            return Err(format!(
+                "save format {version}, only v{SAVE_VERSION} is supported"
+            ));
+        }
+
+        let snapshot: Snapshot =
+            ron::from_str(body).map_err(|error| format!("corrupt save: {error}"))?;
+        snapshot
+            .config
+            .validate()
+            .map_err(|error| format!("corrupt save: {error}"))?;
+        if snapshot.emitter_debts.len() != snapshot.config.emitters.len() {
+            return Err(format!(
+                "corrupt save: {} emitters but {} debts",
+                snapshot.config.emitters.len(),
+                snapshot.emitter_debts.len()
+            ));
+        }
+        if snapshot.nutrients.is_some() != snapshot.config.nutrients.is_some() {
+            return Err("corrupt save: nutrient field doesn't match the config".to_string());
+        }
+        let mut state = Self::new(SimulationConfig {
+            particle_count: 0,
+            ..snapshot.config.clone()
+        });
+        state.config = snapshot.config;
+        state.rng = snapshot.rng;
+        state.tick = snapshot.tick;
+        state.next_id = snapshot.next_id;
+        state.world.particles = snapshot.particles;
+        state.world.food = snapshot.food;
+        state.world.bonds = snapshot.bonds;
+        state.world.nutrients = snapshot.nutrients;
+        state.food_spawn_debt = snapshot.food_spawn_debt;
+        state.emitter_debts = snapshot.emitter_debts;
+        state.generation = snapshot.generation;
+        state.layout = snapshot.layout;
+        Ok(state)
+    }
+
+    // The config with everything changed while running, gravity where a turn is headed. Starting
+    // from it sets a new run up the way this one is now.
+    pub fn live_config(&self) -> SimulationConfig {
+        let mut config = self.config.clone();
+        if let Some(turn) = &self.gravity_turn {
+            config.gravity = turn.to;
+        }
+        config
+    }
+
+    pub fn config(&self) -> &SimulationConfig {
+        &self.config
+    }
+
+    pub fn world(&self) -> &CoreWorld {
+        &self.world
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn generation(&self) -> &GenerationTracker {
+        &self.generation
+    }
+
+    // Cells of the collision grid as of the last step, `None` when collisions don't use one
+    pub fn collision_grid(&self) -> Option<GridLayout> {
+        self.broadphase.layout()
+    }
+
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.set_enabled(enabled);
+    }
+
+    // Prints the total energy now and then and warns once it drifts more than
+    // `threshold_percent` from where it started, `None` turns the check off
+    pub fn set_energy_check(&mut self, threshold_percent: Option<f32>) {
+        self.energy_monitor = threshold_percent.map(EnergyMonitor::new);
+    }
+
+    // Kinetic energy plus the potential energy of uniform gravity, N-body gravity and bonds.
+    // Species interactions and the gravity well aren't conservative and left out. Quadratic in
+    // the number of particles, meant for diagnostics only.
+    pub fn total_energy(&self) -> f64 {
+        let particles = &self.world.particles;
+        let config = &self.config;
+        let mut energy = 0.0;
+        for particle in particles {
+            let mass = particle.mass.max(0.0) as f64;
+            let [vx, vy] = particle.velocity.map(f64::from);
+            let [x, y] = particle.position.map(f64::from);
+            let [gx, gy] = config.gravity.map(f64::from);
+            energy += 0.5 * mass * (vx * vx + vy * vy) - mass * (gx * x + gy * y);
+        }
+
+        if config.gravitational_constant != 0.0 {
+            let softening = config.softening as f64;
+            for (index, a) in particles.iter().enumerate() {
+                for b in &particles[index + 1..] {
+                    let [dx, dy] = displacement(a.position, b.position, config).map(f64::from);
+                    let distance = (dx * dx + dy * dy + softening * softening).sqrt();
+                    if distance > 0.0 {
+                        energy -=
+                            config.gravitational_constant as f64 * a.mass as f64 * b.mass as f64
+                                / distance;
+                    }
+                }
+            }
+        }
+
+        for bond in &self.world.bonds {
+            let (Some(a), Some(b)) = (self.world.particle(bond.a), self.world.particle(bond.b))
+            else {
+                continue;
+            };
+            let [dx, dy] = displacement(a.position, b.position, config).map(f64::from);
+            let stretch = (dx * dx + dy * dy).sqrt() - bond.rest_length as f64;
+            energy += 0.5 * bond.stiffness as f64 * stretch * stretch;
+        }
+        energy
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    // Multiplies the simulation speed by `factor`, staying within 0.25x to 8x
+    pub fn scale_speed(&mut self, factor: f32) {
+        self.speed_multiplier =
+            (self.speed_multiplier * factor).clamp(MIN_SPEED_MULTIPLIER, MAX_SPEED_MULTIPLIER);
+    }
+
+    // Advances exactly one step while paused and stays paused, does nothing while running
+    pub fn single_step(&mut self, dt: f32) {
+        if self.paused {
+            self.paused = false;
+            self.step(dt);
+            self.paused = true;
+        }
+    }
+
+    // Advances the world by `dt` seconds with the integrator `config.integrator` picks, no-op while
+    // paused
+    pub fn step(&mut self, dt: f32) {
+        if self.paused {
+            return;
+        }
+
+        if let Some(turn) = &mut self.gravity_turn {
+            turn.elapsed += dt;
+            let t = turn.elapsed / GRAVITY_TURN_TIME;
+            if t < 1.0 {
+                self.config.gravity = [
+                    turn.from[0] + (turn.to[0] - turn.from[0]) * t,
+                    turn.from[1] + (turn.to[1] - turn.from[1]) * t,
+                ];
+            } else {
+                self.config.gravity = turn.to;
+                self.gravity_turn = None;
+            }
+        }
+
+        // Motion and contacts in substeps, everything else happens once per step
+        let substeps = self.config.collision_substeps.max(1);
+        let substep_dt = dt / substeps as f32;
+        for _ in 0..substeps {
+            self.advance(substep_dt);
+            let start = self.profiler.start();
+            self.metabolize(substep_dt);
+            self.profiler.record("metabolism", start);
+            self.resolve_collisions();
+            self.collide_with_obstacles();
+        }
+        let start = self.profiler.start();
+        self.eat_food();
+        if let (Some(field), Some(nutrients)) = (&mut self.world.nutrients, &self.config.nutrients)
+        {
+            field.step(&mut self.world.particles, nutrients, dt);
+        }
+        self.spawn_food(dt);
+        self.profiler.record("food", start);
+        self.paint(dt);
+        self.emit(dt);
+        // Children born below start out at 0 and age from the next step on
+        for particle in &mut self.world.particles {
+            particle.age += dt;
+        }
+        let start = self.profiler.start();
+        self.reproduce_and_cull();
+        self.profiler.record("reproduction", start);
+        self.tick += 1;
+        self.generation.observe(&self.world.particles, self.next_id);
+
+        if self.energy_monitor.is_some() {
+            let energy = self.total_energy();
+            let particle_count = self.world.particles.len();
+            let tick = self.tick;
+            if let Some(line) = self
+                .energy_monitor
+                .as_mut()
+                .and_then(|monitor| monitor.observe(tick, particle_count, energy))
+            {
+                println!("{line}");
+            }
+        }
+    }
+
+    // Applies the forces and moves every particle over `dt`
+    fn advance(&mut self, dt: f32) {
+        match self.config.integrator {
+            Integrator::SemiImplicitEuler => {
+                let start = self.profiler.start();
+                let accelerations = self.accelerations();
+                self.thermal_kicks(dt);
+                self.profiler.record("forces", start);
+
+                let start = self.profiler.start();
+                let config = &self.config;
+                let bounds = &self.world.bounds;
+                for_each_indexed(&mut self.world.particles, |index, particle| {
+                    integrate(particle, accelerations[index], dt, config, bounds)
+                });
+                self.profiler.record("integration", start);
+            }
+            // Forces are evaluated in between the two halves, at the positions after the step
+            Integrator::VelocityVerlet => {
+                let start = self.profiler.start();
+                self.thermal_kicks(dt);
+                let config = &self.config;
+                let bounds = &self.world.bounds;
+                for_each_indexed(&mut self.world.particles, |_, particle| {
+                    drift(particle, dt, config, bounds)
+                });
+                self.profiler.record("integration", start);
+
+                let start = self.profiler.start();
+                let accelerations = self.accelerations();
+                self.profiler.record("forces", start);
+
+                let start = self.profiler.start();
+                let config = &self.config;
+                for_each_indexed(&mut self.world.particles, |index, particle| {
+                    kick(particle, accelerations[index], dt, config)
+                });
+                self.profiler.record("integration", start);
+            }
+        }
+    }
+
+    // Serial even with rayon, the draws happen in id order so runs stay reproducible
+    fn thermal_kicks(&mut self, dt: f32) {
+        if self.config.temperature <= 0.0 {
+            return;
+        }
+        for particle in &mut self.world.particles {
+            let scale = (self.config.temperature * dt * inverse_mass(particle.mass)).sqrt();
+            particle.velocity[0] += scale * self.rng.next_gaussian();
+            particle.velocity[1] += scale * self.rng.next_gaussian();
+        }
+    }
+
+    // Every pellet goes to the lowest indexed particle touching it
+    fn eat_food(&mut self) {
+        let food = &self.world.food;
+        if food.is_empty() {
+            return;
+        }
+        self.food_grid
+            .rebuild_with(food.len(), |index| (food[index].position, FOOD_RADIUS));
+
+        let mut eaten = vec![false; food.len()];
+        for particle in &mut self.world.particles {
+            let reach = particle.radius + FOOD_RADIUS;
+            for food_index in self.food_grid.query(particle.position, reach) {
+                let pellet = &food[food_index];
+                let [dx, dy] = displacement(particle.position, pellet.position, &self.config);
+                if !eaten[food_index] && dx * dx + dy * dy < reach * reach {
+                    eaten[food_index] = true;
+                    particle.energy += pellet.energy;
+                }
+            }
+        }
+
+        let mut eaten = eaten.into_iter();
+        self.world.food.retain(|_| !eaten.next().unwrap_or(false));
+    }
+
+    fn spawn_food(&mut self, dt: f32) {
+        self.food_spawn_debt += self.config.food_spawn_rate * dt;
+        while self.food_spawn_debt >= 1.0 {
+            self.food_spawn_debt -= 1.0;
+            if self.world.food.len() >= self.config.max_food {
+                continue;
+            }
+
+            let bounds = &self.world.bounds;
+            let position = [
+                self.rng.range_f32(bounds.min[0], bounds.max[0]),
+                self.rng.range_f32(bounds.min[1], bounds.max[1]),
+            ];
+            self.world.food.push(Food {
+                position,
+                energy: self.config.food_energy,
+            });
+        }
+    }
+
+    // Spawns particles along every emitter at its rate. At the population cap they are skipped
+    // rather than owed, so emission doesn't burst once there is room again.
+    fn emit(&mut self, dt: f32) {
+        for index in 0..self.config.emitters.len() {
+            let emitter = self.config.emitters[index];
+            self.emitter_debts[index] += emitter.rate * dt;
+            while self.emitter_debts[index] >= 1.0 {
+                self.emitter_debts[index] -= 1.0;
+                let particles = self.world.particles.len();
+                if self
+                    .config
+                    .max_particles
+                    .is_some_and(|max| particles >= max)
+                {
+                    continue;
+                }
+                let (position, velocity) = emitter.sample(&mut self.rng);
+                self.spawn_moving(position, Some(velocity));
+            }
+        }
+    }
+
+    // Spawns particles with random genomes at uniformly random points of the brush disc. Points
+    // past the edge of a walled world are skipped, a wrapping one takes them across.
+    fn paint(&mut self, dt: f32) {
+        let Some(brush) = self.brush.clone() else {
+            return;
+        };
+        let area = std::f32::consts::PI * brush.radius * brush.radius;
+        self.paint_debt += brush.density * area * dt;
+        while self.paint_debt >= 1.0 {
+            self.paint_debt -= 1.0;
+            let angle = self.rng.range_f32(0.0, std::f32::consts::TAU);
+            let distance = brush.radius * self.rng.next_f32().sqrt();
+            let position = [
+                brush.center[0] + angle.cos() * distance,
+                brush.center[1] + angle.sin() * distance,
+            ];
+            let bounds = &self.world.bounds;
+            let inside =
+                (0..2).all(|axis| (bounds.min[axis]..=bounds.max[axis]).contains(&position[axis]));
+            if inside || self.config.boundary == BoundaryMode::Wrap {
+                self.spawn_at(position);
+            }
+        }
+    }
+
+    fn metabolize(&mut self, dt: f32) {
+        let config = &self.config;
+        for particle in &mut self.world.particles {
+            let speed = (particle.velocity[0].powi(2) + particle.velocity[1].powi(2)).sqrt();
+            let drain = (config.basal_metabolism + config.movement_cost * speed)
+                * particle.genome.size_factor;
+            particle.energy += (config.ambient_energy_gain - drain) * dt;
+        }
+    }
+
+    // Runs at the very end of a tick, so nothing else holds particle indices while the vec
+    // grows and shrinks. Children are appended with fresh ids and the dead are compacted away in
+    // order, which keeps the particles sorted by id.
+    fn reproduce_and_cull(&mut self) {
+        match self.config.selection {
+            SelectionStrategy::Threshold => {
+                let parents: Vec<usize> = (0..self.world.particles.len())
+                    .filter(|&index| {
+                        self.world.particles[index].energy >= self.config.reproduction_threshold
+                    })
+                    .collect();
+                for parent_index in parents {
+                    self.spawn_offspring(parent_index);
+                }
+            }
+            SelectionStrategy::Tournament => self.run_tournament(),
+        }
+
+        // Old age goes the same way as starving
+        let max_lifetime = self.config.max_lifetime;
+        self.world.particles.retain(|particle| {
+            particle.energy > 0.0 && max_lifetime.is_none_or(|max| particle.age < max)
+        });
+        self.cull_to_cap();
+        debug_assert!(self
+            .world
+            .particles
+            .windows(2)
+            .all(|pair| pair[0].id < pair[1].id));
+
+        self.drop_dangling_bonds();
+    }
+
+    // Bonds go with either of their particles
+    fn drop_dangling_bonds(&mut self) {
+        let world = &mut self.world;
+        if !world.bonds.is_empty() {
+            let particles = &world.particles;
+            world.bonds.retain(|bond| {
+                particle_index(particles, bond.a).is_some()
+                    && particle_index(particles, bond.b).is_some()
+            });
+        }
+    }
+
+    // The winner's child takes the place of the loser, which is starved for `retain` to remove.
+    // Ties go to the younger particle for winning and the older one for losing.
+    fn run_tournament(&mut self) {
+        if !self
+            .tick
+            .is_multiple_of(self.config.tournament_interval.max(1))
+        {
+            return;
+        }
+        let count = self.world.particles.len();
+        let size = self.config.tournament_size.min(count);
+        if size < 2 {
+            return;
+        }
+
+        let mut entrants = sample_indices(&mut self.rng, count, size);
+        let particles = &self.world.particles;
+        entrants.sort_by(|&a, &b| {
+            particles[a]
+                .energy
+                .total_cmp(&particles[b].energy)
+                .then(a.cmp(&b))
+        });
+        let (loser, winner) = (entrants[0], entrants[size - 1]);
+        self.world.particles[loser].energy = 0.0;
+        self.spawn_offspring(winner);
+    }
+
+    // Removes particles picked by the cull policy until the population fits the cap, keeping
+    // the survivors in id order
+    fn cull_to_cap(&mut self) {
+        let Some(max_particles) = self.config.max_particles else {
+            return;
+        };
+        let particles = &mut self.world.particles;
+        let excess = particles.len().saturating_sub(max_particles);
+        if excess == 0 {
+            return;
+        }
+
+        let mut victims = vec![false; particles.len()];
+        match self.config.cull_policy {
+            CullPolicy::OldestDies => victims[..excess].fill(true),
+            CullPolicy::LowestEnergyDies => {
+                // Sorted by id already, so the stable sort lets older particles lose ties
+                let mut order: Vec<usize> = (0..particles.len()).collect();
+                order.sort_by(|&a, &b| particles[a].energy.total_cmp(&particles[b].energy));
+                for &index in &order[..excess] {
+                    victims[index] = true;
+                }
+            }
+            CullPolicy::RandomDies => {
+                for index in sample_indices(&mut self.rng, particles.len(), excess) {
+                    victims[index] = true;
+                }
+            }
+        }
+        let mut victims = victims.into_iter();
+        particles.retain(|_| !victims.next().unwrap_or(false));
+    }
+
+    fn resolve_collisions(&mut self) {
+        let start = self.profiler.start();
+        self.broadphase
+            .candidate_pairs(&self.world.particles, &mut self.collision_pairs);
+        self.profiler.record("broadphase", start);
+        let start = self.profiler.start();
+
+        let wrap = self.config.boundary == BoundaryMode::Wrap;
+        for &(i, j) in &self.collision_pairs {
+            let (a, b) = collision::pair_mut(&mut self.world.particles, i, j);
+            let restitution = self.config.pair_restitution(a.species, b.species);
+            if !wrap {
+                collision::resolve_collision(a, b, restitution);
+                continue;
+            }
+
+            // Resolve against the image of `b` closest to `a`, then bring both back inside
+            let delta = displacement(a.position, b.position, &self.config);
+            let shift = [
+                a.position[0] + delta[0] - b.position[0],
+                a.position[1] + delta[1] - b.position[1],
+            ];
+            b.position = [b.position[0] + shift[0], b.position[1] + shift[1]];
+            collision::resolve_collision(a, b, restitution);
+            b.position = [b.position[0] - shift[0], b.position[1] - shift[1]];
+            a.position = wrap_position(a.position, &self.world.bounds);
+            b.position = wrap_position(b.position, &self.world.bounds);
+        }
+        self.profiler.record("collisions", start);
+    }
+
+    fn collide_with_obstacles(&mut self) {
+        if self.world.obstacles.is_empty() {
+            return;
+        }
+        let start = self.profiler.start();
+        let obstacles = &self.world.obstacles;
+        let grid = &self.obstacle_grid;
+        let (reach, restitution) = (self.obstacle_reach, self.config.restitution);
+        for_each_indexed(&mut self.world.particles, |_, particle| {
+            for index in grid.query(particle.position, particle.radius + reach) {
+                obstacle::collide_with_obstacle(particle, &obstacles[index], restitution);
+            }
+        });
+        self.profiler.record("obstacles", start);
+    }
+
+    // Connects the living particles with ids `a` and `b` with a spring, returns whether both exist
+    pub fn add_bond(&mut self, a: u64, b: u64, rest_length: f32, stiffness: f32) -> bool {
+        let particles = &self.world.particles;
+        if a == b
+            || particle_index(particles, a).is_none()
+            || particle_index(particles, b).is_none()
+        {
+            return false;
+        }
+        self.world.bonds.push(Bond {
+            a,
+            b,
+            rest_length,
+            stiffness,
+        });
+        true
+    }
+
+    // Swings gravity over to `direction`, a unit vector, within `GRAVITY_TURN_TIME` simulated
+    // seconds. The strength stays, without any gravity yet the default strength is used.
+    pub fn turn_gravity(&mut self, direction: [f32; 2]) {
+        let current = self
+            .gravity_turn
+            .map_or(self.config.gravity, |turn| turn.to);
+        let mut strength = current[0].hypot(current[1]);
+        if strength == 0.0 {
+            let [x, y] = SimulationConfig::default().gravity;
+            strength = x.hypot(y);
+        }
+        self.gravity_turn = Some(GravityTurn {
+            from: self.config.gravity,
+            to: [direction[0] * strength, direction[1] * strength],
+            elapsed: 0.0,
+        });
+    }
+
+    // Id of the particle closest to `position` among those within `PICK_MARGIN` of it, `None`
+    // for empty space
+    pub fn particle_at(&mut self, position: [f32; 2]) -> Option<u64> {
+        let particles = &self.world.particles;
+        // Scratch use, `step` rebuilds the grid before every read
+        self.interaction_grid.rebuild(particles);
+        let reach = particles
+            .iter()
+            .map(|particle| particle.radius)
+            .fold(0.0, f32::max)
+            + PICK_MARGIN;
+
+        let mut nearest = None;
+        let mut nearest_distance = f32::INFINITY;
+        for index in self.interaction_grid.query(position, reach) {
+            let particle = &particles[index];
+            let [dx, dy] = displacement(position, particle.position, &self.config);
+            let distance = (dx * dx + dy * dy).sqrt();
+            // Ties go to the older particle, candidates come in no particular order
+            let closer = distance < nearest_distance
+                || (distance == nearest_distance && nearest.is_some_and(|id| particle.id < id));
+            if distance <= particle.radius + PICK_MARGIN && closer {
+                nearest = Some(particle.id);
+                nearest_distance = distance;
+            }
+        }
+        nearest
+    }
+
+    pub fn set_gravity_well(&mut self, gravity_well: Option<[f32; 2]>) {
+        self.gravity_well = gravity_well;
+    }
+
+    // Paints particles into the disc of `brush` every step, `None` once it is released
+    pub fn set_brush(&mut self, brush: Option<Brush>) {
+        if brush.is_none() {
+            self.paint_debt = 0.0;
+        }
+        self.brush = brush;
+    }
+
+    // Adds a particle with a random genome and species at `position`, moving as
+    // `initial_velocity` says
+    pub fn spawn_at(&mut self, position: [f32; 2]) {
+        self.spawn_moving(position, None);
+    }
+
+    // Spawns `count` particles at uniformly random positions within the bounds
+    pub fn spawn_random(&mut self, count: usize) {
+        let [width, height] = self.config.bounds;
+        for _ in 0..count {
+            let position = [
+                self.rng.range_f32(0.0, width),
+                self.rng.range_f32(0.0, height),
+            ];
+            self.spawn_moving(position, None);
+        }
+    }
+
+    // Removes every particle centred inside `region` along with their bonds, returns how many.
+    // Ids aren't reused, selections of the other particles stay valid.
+    pub fn delete_region(&mut self, region: &Rect) -> usize {
+        let before = self.world.particles.len();
+        self.world
+            .particles
+            .retain(|particle| !region.contains(particle.position));
+        self.drop_dangling_bonds();
+        // Pairs from the last step index into the old list, the next step finds them again
+        self.collision_pairs.clear();
+        before - self.world.particles.len()
+    }
+
+    // Same as `SimulationConfig::set_parameter` on the running world. Setting gravity ends a
+    // turn towards another direction.
+    pub fn set_parameter(&mut self, name: &str, values: &[f32]) -> Result<(), String> {
+        let mut config = self.live_config();
+        config.set_parameter(name, values)?;
+        if name == "gravity" {
+            self.gravity_turn = None;
+        } else {
+            // Gravity stays mid-turn
+            config.gravity = self.config.gravity;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    // Same as `spawn_at` with an explicit velocity, `None` samples `initial_velocity`
+    fn spawn_moving(&mut self, position: [f32; 2], velocity: Option<[f32; 2]>) {
+        let position = self.confine(position);
+        let genome = Genome::random(&mut self.rng);
+        let species_count = self.config.species_count();
+        // Single species worlds leave the random sequence as it was before species existed
+        let species = if species_count > 1 {
+            ((self.rng.next_f32() * species_count as f32) as usize).min(species_count - 1) as u8
+        } else {
+            0
+        };
+        let id = self.take_id();
+
+        let mut particle = Particle::new(id, species, position, genome, self.config.initial_energy);
+        particle.velocity = match velocity {
+            Some(velocity) => velocity,
+            None => self.sample_initial_velocity(particle.mass),
+        };
+        self.world.particles.push(particle);
+    }
+
+    // Resting particles don't draw from the RNG, runs from before the distribution existed stay
+    // as they were
+    fn sample_initial_velocity(&mut self, mass: f32) -> [f32; 2] {
+        match self.config.initial_velocity {
+            VelocityDistribution::Zero => [0.0, 0.0],
+            VelocityDistribution::Uniform {
+                min_speed,
+                max_speed,
+            } => {
+                let speed = self.rng.range_f32(min_speed, max_speed);
+                let angle = self.rng.range_f32(0.0, std::f32::consts::TAU);
+                [angle.cos() * speed, angle.sin() * speed]
+            }
+            VelocityDistribution::MaxwellBoltzmann { temperature } => {
+                let scale = (temperature * inverse_mass(mass)).sqrt();
+                [
+                    self.rng.next_gaussian() * scale,
+                    self.rng.next_gaussian() * scale,
+                ]
+            }
+        }
+    }
+
+    fn spawn_random_particle(&mut self) {
+        let bounds = &self.world.bounds;
+        let position = [
+            self.rng.range_f32(bounds.min[0], bounds.max[0]),
+            self.rng.range_f32(bounds.min[1], bounds.max[1]),
+        ];
+        self.spawn_at(position);
+    }
+
+    // Places a child right next to the parent, carrying a mutated copy of the parent's genome.
+    // The parent hands over half of its energy.
+    fn spawn_offspring(&mut self, parent_index: usize) {
+        let parent = &mut self.world.particles[parent_index];
+        parent.energy /= 2.0;
+        let energy = parent.energy;
+        let parent_position = parent.position;
+        let parent_radius = parent.radius;
+        let species = parent.species;
+        let parent_hue = parent.lineage_hue;
+        let mut genome = parent.genome.clone();
+        genome.mutate(&mut self.rng, self.config.mutation_rate);
+
+        let angle = self.rng.range_f32(0.0, std::f32::consts::TAU);
+        let offset = parent_radius + DEFAULT_PARTICLE_RADIUS * genome.size_factor;
+        let position = self.confine([
+            parent_position[0] + angle.cos() * offset,
+            parent_position[1] + angle.sin() * offset,
+        ]);
+
+        let id = self.take_id();
+        let mut child = Particle::new(id, species, position, genome, energy);
+        // Drift taken from the child's own id hash, so it doesn't use up random numbers
+        let drift = (child.lineage_hue / 180.0 - 1.0) * LINEAGE_HUE_DRIFT;
+        child.lineage_hue = (parent_hue + drift).rem_euclid(360.0);
+        self.world.particles.push(child);
+    }
+
+    // Wraps positions outside a wrapping world back in, they are left alone otherwise
+    fn confine(&self, position: [f32; 2]) -> [f32; 2] {
+        match self.config.boundary {
+            BoundaryMode::Wrap => wrap_position(position, &self.world.bounds),
+            BoundaryMode::Reflect | BoundaryMode::Open => position,
+        }
+    }
+
+    fn take_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // Net acceleration acting on each particle, indexed like `world.particles`
+    fn accelerations(&mut self) -> Vec<[f32; 2]> {
+        let mut accelerations = vec![self.config.gravity; self.world.particles.len()];
+
+        let particles = &self.world.particles;
+        let config = &self.config;
+
+        if config.gravitational_constant != 0.0 {
+            // Built once up front, every particle then walks it read-only
+            self.quadtree.rebuild(particles);
+            let quadtree = &self.quadtree;
+            for_each_indexed(&mut accelerations, |index, acceleration| {
+                let attraction = quadtree.acceleration(
+                    particles,
+                    index,
+                    config.gravitational_constant,
+                    config.theta,
+                    config.softening,
+                );
+                acceleration[0] += attraction[0];
+                acceleration[1] += attraction[1];
+            });
+        }
+
+        if !config.interaction_matrix.is_empty() && config.interaction_radius > 0.0 {
+            let reach = config.interaction_radius;
+            // Cells as wide as the reach, so a query only touches the neighbouring ones
+            self.interaction_grid
+                .rebuild_with(particles.len(), |index| {
+                    (particles[index].position, reach / 2.0)
+                });
+            let grid = &self.interaction_grid;
+            for_each_indexed(&mut accelerations, |index, acceleration| {
+                let interaction = species_acceleration(particles, index, grid, config);
+                acceleration[0] += interaction[0];
+                acceleration[1] += interaction[1];
+            });
+        }
+
+        if let Some(well) = self.gravity_well {
+            for_each_indexed(&mut accelerations, |index, acceleration| {
+                let attraction = well_acceleration(particles[index].position, well, config);
+                acceleration[0] += attraction[0];
+                acceleration[1] += attraction[1];
+            });
+        }
+
+        // Serial, every bond pushes on two particles. Bonds are ordered, so the sums are too.
+        for bond in &self.world.bonds {
+            let (Some(a), Some(b)) = (
+                particle_index(particles, bond.a),
+                particle_index(particles, bond.b),
+            ) else {
+                continue;
+            };
+            let force = spring_force(&particles[a], &particles[b], bond, config);
+            for axis in 0..2 {
+                accelerations[a][axis] += force[axis] * inverse_mass(particles[a].mass);
+                accelerations[b][axis] -= force[axis] * inverse_mass(particles[b].mass);
+            }
+        }
+
+        if let Some(max_acceleration) = config.max_acceleration {
+            for_each_indexed(&mut accelerations, |_, acceleration| {
+                *acceleration = clamp_magnitude(*acceleration, max_acceleration);
+            });
+        }
+
+        accelerations
+    }
+}
+
+// Scales `vector` down to at most `max` long. Overflowed components still point the way, a
+// vector without any direction left becomes zero.
+fn clamp_magnitude(vector: [f32; 2], max: f32) -> [f32; 2] {
+    let magnitude = vector[0].hypot(vector[1]);
+    if magnitude <= max {
+        return vector;
+    }
+    let vector = if magnitude.is_infinite() {
+        vector.map(|axis| {
+            if axis.is_infinite() {
+                axis.signum()
+            } else {
+                0.0
+            }
+        })
+    } else {
+        vector
+    };
+    let magnitude = vector[0].hypot(vector[1]);
+    if magnitude.is_nan() || magnitude == 0.0 {
+        return [0.0, 0.0];
+    }
+    [vector[0] * max / magnitude, vector[1] * max / magnitude]
+}
+
+// Runs `f` on every item along with its index, spread over all cores with the `rayon` feature.
+// Items must not depend on each other, which keeps the outcome independent of the thread count.
+fn for_each_indexed<T: Send>(items: &mut [T], f: impl Fn(usize, &mut T) + Send + Sync) {
+    #[cfg(feature = "rayon")]
+    items
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(index, item)| f(index, item));
+
+    #[cfg(not(feature = "rayon"))]
+    for (index, item) in items.iter_mut().enumerate() {
+        f(index, item);
+    }
+}
+
+// `size` distinct indices below `count` drawn uniformly, by a partial Fisher-Yates shuffle
+fn sample_indices(rng: &mut SimRng, count: usize, size: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..count).collect();
+    for slot in 0..size.min(count) {
+        let remaining = count - slot;
+        let pick = slot + ((rng.next_f32() * remaining as f32) as usize).min(remaining - 1);
+        order.swap(slot, pick);
+    }
+    order.truncate(size);
+    order
+}
+
+// Index of the particle with `id`, particles are sorted by id
+fn particle_index(particles: &[Particle], id: u64) -> Option<usize> {
+    particles
+        .binary_search_by_key(&id, |particle| particle.id)
+        .ok()
+}
+
+// Hooke's law force on `a`, pulling it towards `b` while stretched and pushing it away while
+// compressed. `b` feels the opposite force.
+fn spring_force(a: &Particle, b: &Particle, bond: &Bond, config: &SimulationConfig) -> [f32; 2] {
+    let [dx, dy] = displacement(a.position, b.position, config);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance == 0.0 {
+        return [0.0, 0.0];
+    }
+    let strength = bond.stiffness * (distance - bond.rest_length);
+    [strength * dx / distance, strength * dy / distance]
+}
+
+// Non-positive masses are immovable, same as in collisions
+fn inverse_mass(mass: f32) -> f32 {
+    if mass > 0.0 {
+        1.0 / mass
+    } else {
+        0.0
+    }
+}
+
+// Sum of the species interactions on `particles[index]`, each pulling along the line to the other
+// particle and fading linearly from full strength at contact to nothing at the interaction radius
+fn species_acceleration(
+    particles: &[Particle],
+    index: usize,
+    grid: &SpatialGrid,
+    config: &SimulationConfig,
+) -> [f32; 2] {
+    let particle = &particles[index];
+    let reach = config.interaction_radius;
+    let mut acceleration = [0.0, 0.0];
+    for other_index in grid.query(particle.position, reach) {
+        let other = &particles[other_index];
+        let [dx, dy] = displacement(particle.position, other.position, config);
+        let distance_squared = dx * dx + dy * dy;
+        // Also skips the particle itself, collisions keep coincident ones apart
+        if other_index == index || distance_squared >= reach * reach || distance_squared == 0.0 {
+            continue;
+        }
+
+        let distance = distance_squared.sqrt();
+        let strength =
+            config.interaction(particle.species, other.species) * (1.0 - distance / reach);
+        acceleration[0] += strength * dx / distance;
+        acceleration[1] += strength * dy / distance;
+    }
+    acceleration
+}
+
+// Inverse square pull towards `well`, clamped below `well_min_distance`
+fn well_acceleration(position: [f32; 2], well: [f32; 2], config: &SimulationConfig) -> [f32; 2] {
+    let delta = [well[0] - position[0], well[1] - position[1]];
+    let distance = (delta[0] * delta[0] + delta[1] * delta[1]).sqrt();
+    if distance > config.well_radius || distance == 0.0 {
+        return [0.0, 0.0];
+    }
+
+    let clamped = distance.max(config.well_min_distance);
+    let magnitude = config.well_strength / (clamped * clamped);
+    [
+        delta[0] / distance * magnitude,
+        delta[1] / distance * magnitude,
+    ]
+}
+
+// Semi-implicit Euler step of a single particle, including the speed limit and wall bounces
+pub fn integrate(
+    particle: &mut Particle,
+    acceleration: [f32; 2],
+    dt: f32,
+    config: &SimulationConfig,
+    bounds: &Rect,
+) {
+    particle.velocity[0] += acceleration[0] * dt;
+    particle.velocity[1] += acceleration[1] * dt;
+    if config.linear_damping != 0.0 {
+        // Exponential rather than `1 - damping * dt`, which would reverse particles at large dt
+        let retained = (-config.linear_damping * dt).exp();
+        particle.velocity[0] *= retained;
+        particle.velocity[1] *= retained;
+    }
+    limit_speed(particle, config.max_speed * particle.genome.speed_factor);
+    particle.position[0] += particle.velocity[0] * dt;
+    particle.position[1] += particle.velocity[1] * dt;
+    keep_within_boundary(particle, config, bounds);
+}
+
+// First half of a velocity Verlet step, moves the particle along its velocity and the
+// acceleration stored by the last step
+fn drift(particle: &mut Particle, dt: f32, config: &SimulationConfig, bounds: &Rect) {
+    for axis in 0..2 {
+        particle.position[axis] +=
+            (particle.velocity[axis] + 0.5 * particle.acceleration[axis] * dt) * dt;
+    }
+    keep_within_boundary(particle, config, bounds);
+}
+
+// Second half of a velocity Verlet step, `acceleration` is the one at the position `drift` moved
+// the particle to
+fn kick(particle: &mut Particle, acceleration: [f32; 2], dt: f32, config: &SimulationConfig) {
+    for (axis, new) in acceleration.into_iter().enumerate() {
+        particle.velocity[axis] += 0.5 * (particle.acceleration[axis] + new) * dt;
+    }
+    particle.acceleration = acceleration;
+    if config.linear_damping != 0.0 {
+        let retained = (-config.linear_damping * dt).exp();
+        particle.velocity[0] *= retained;
+        particle.velocity[1] *= retained;
+    }
+    limit_speed(particle, config.max_speed * particle.genome.speed_factor);
+}
+
+fn keep_within_boundary(particle: &mut Particle, config: &SimulationConfig, bounds: &Rect) {
+    match config.boundary {
+        BoundaryMode::Reflect => collide_with_walls(particle, bounds, config),
+        BoundaryMode::Wrap => particle.position = wrap_position(particle.position, bounds),
+        BoundaryMode::Open => {}
+    }
+}
+
+// Vector from `from` to `to`, through the nearest edge in a wrapping world
+fn displacement(from: [f32; 2], to: [f32; 2], config: &SimulationConfig) -> [f32; 2] {
+    let mut delta = [to[0] - from[0], to[1] - from[1]];
+    if config.boundary == BoundaryMode::Wrap {
+        for (axis, delta) in delta.iter_mut().enumerate() {
+            let size = config.bounds[axis];
+            if *delta > size / 2.0 {
+                *delta -= size;
+            } else if *delta < -size / 2.0 {
+                *delta += size;
+            }
+        }
+    }
+    delta
+}
+
+// `position` moved back into the bounds by whole world sizes
+fn wrap_position(position: [f32; 2], bounds: &Rect) -> [f32; 2] {
+    [
+        wrap_coordinate(position[0], bounds.min[0], bounds.max[0]),
+        wrap_coordinate(position[1], bounds.min[1], bounds.max[1]),
+    ]
+}
+
+// One axis of `wrap_position`
+pub fn wrap_coordinate(value: f32, min: f32, max: f32) -> f32 {
+    let size = max - min;
+    if size <= 0.0 {
+        return min;
+    }
+    let wrapped = min + (value - min).rem_euclid(size);
+    // Tiny negative offsets round up to exactly `size`, which belongs to the other edge
+    if wrapped >= max {
+        min
+    } else {
+        wrapped
+    }
+}
+
+fn limit_speed(particle: &mut Particle, max_speed: f32) {
+    let speed_squared = particle.velocity[0].powi(2) + particle.velocity[1].powi(2);
+    if speed_squared > max_speed * max_speed {
+        let scale = max_speed / speed_squared.sqrt();
+        particle.velocity[0] *= scale;
+        particle.velocity[1] *= scale;
+    }
+}
+
+// Reflects the particle off every wall it touches and puts it back inside the bounds, the
+// padding away from them. Axes are handled independently, so crossing two walls at a corner in
+// one step works too.
+fn collide_with_walls(particle: &mut Particle, bounds: &Rect, config: &SimulationConfig) {
+    for axis in 0..2 {
+        let (position, velocity) = wall_contact(
+            particle.position[axis],
+            particle.velocity[axis],
+            particle.radius,
+            [bounds.min[axis], bounds.max[axis]],
+            config,
+        );
+        particle.position[axis] = position;
+        particle.velocity[axis] = velocity;
+    }
+}
+
+// Position and velocity along one axis after touching the walls at `min` and `max`, shared with
+// the column layout so both stay bit for bit the same
+pub fn wall_contact(
+    position: f32,
+    velocity: f32,
+    radius: f32,
+    [min, max]: [f32; 2],
+    config: &SimulationConfig,
+) -> (f32, f32) {
+    let padding = config.wall_padding;
+    let low = min + radius;
+    let high = max - radius;
+    // Bounds narrower than the particle, pin it to the middle
+    if low > high {
+        return ((min + max) / 2.0, 0.0);
+    }
+    // Padding wider than the room left for the particle, the middle is as far from both as it gets
+    let padding = padding.min((high - low) / 2.0);
+    let (position, into_wall) = if position < low + padding {
+        (low + padding, velocity < 0.0)
+    } else if position > high - padding {
+        (high - padding, velocity > 0.0)
+    } else {
+        return (position, velocity);
+    };
+    // Already moving away, e.g. after a collision or a slow lift-off, it only has to stay inside
+    if !into_wall {
+        return (position, velocity);
+    }
+    let velocity = -velocity * config.restitution;
+    if velocity.abs() < config.wall_rest_speed {
+        (position, 0.0)
+    } else {
+        (position, velocity)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Rect {
+    // Smallest rect with both `a` and `b` as corners, in any order
+    pub fn spanning(a: [f32; 2], b: [f32; 2]) -> Self {
+        Self {
+            min: [a[0].min(b[0]), a[1].min(b[1])],
+            max: [a[0].max(b[0]), a[1].max(b[1])],
+        }
+    }
+
+    // Edges included
+    pub fn contains(&self, position: [f32; 2]) -> bool {
+        (self.min[0]..=self.max[0]).contains(&position[0])
+            && (self.min[1]..=self.max[1]).contains(&position[1])
+    }
+}
+
+pub struct CoreWorld {
+    particles: Vec<Particle>,
+    food: Vec<Food>,
+    // Kept in the order they were added, each tied to particle ids rather than indices
+    bonds: Vec<Bond>,
+    obstacles: Vec<Obstacle>,
+    nutrients: Option<NutrientField>,
+    bounds: Rect,
+}
+
+impl CoreWorld {
+    // Always sorted by ascending id, so every per particle pass visits them in the same order
+    // regardless of how births and deaths were interleaved
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn particle(&self, id: u64) -> Option<&Particle> {
+        particle_index(&self.particles, id).map(|index| &self.particles[index])
+    }
+
+    pub fn nutrients(&self) -> Option<&NutrientField> {
+        self.nutrients.as_ref()
+    }
+
+    pub fn food(&self) -> &[Food] {
+        &self.food
+    }
+
+    pub fn bonds(&self) -> &[Bond] {
+        &self.bonds
+    }
+
+    pub fn obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+
+    pub fn bounds(&self) -> &Rect {
+        &self.bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_produces_identical_runs() {
+        let config = SimulationConfig {
+            seed: 1234,
+            ..SimulationConfig::default()
+        };
+        let mut first = CoreState::new(config.clone());
+        let mut second = CoreState::new(config);
+
+        for _ in 0..100 {
+            first.step(1.0 / 60.0);
+            second.step(1.0 / 60.0);
+        }
+
+        assert_eq!(first.world().particles(), second.world().particles());
+    }
+
+    #[test]
+    fn particle_ids_stay_unique_sorted_and_reproducible_over_1000_ticks() {
+        let config = SimulationConfig {
+            seed: 7,
+            particle_count: 200,
+            reproduction_threshold: 60.0,
+            ..SimulationConfig::default()
+        };
+        let run = || {
+            let mut core_state = CoreState::new(config.clone());
+            for _ in 0..1000 {
+                core_state.step(1.0 / 60.0);
+            }
+            core_state
+        };
+        let (first, second) = (run(), run());
+
+        let ids = |core_state: &CoreState| -> Vec<u64> {
+            core_state
+                .world()
+                .particles()
+                .iter()
+                .map(|particle| particle.id)
+                .collect()
+        };
+        // Births happened, otherwise the ids would just be the initial 0..200
+        assert!(first.next_id > 200);
+        assert!(ids(&first).windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(ids(&first), ids(&second));
+        assert_eq!(first.world().particles(), second.world().particles());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_step_matches_single_threaded_step() {
+        let config = SimulationConfig {
+            seed: 42,
+            particle_count: 2000,
+            gravitational_constant: 50.0,
+            ..SimulationConfig::default()
+        };
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut core_state = CoreState::new(config.clone());
+                core_state.set_gravity_well(Some([320.0, 240.0]));
+                for _ in 0..100 {
+                    core_state.step(1.0 / 60.0);
+                }
+                core_state.world().particles().to_vec()
+            })
+        };
+
+        assert_eq!(run(1), run(8));
+    }
+
+    #[test]
+    fn grid_and_all_pairs_collide_identically_in_a_crowd() {
+        for boundary in [BoundaryMode::Reflect, BoundaryMode::Wrap] {
+            let run = |collision_detection| {
+                let mut core_state = CoreState::new(SimulationConfig {
+                    seed: 5,
+                    particle_count: 400,
+                    bounds: [160.0, 160.0],
+                    boundary,
+                    collision_detection,
+                    ..SimulationConfig::default()
+                });
+                for _ in 0..300 {
+                    core_state.step(1.0 / 120.0);
+                }
+                core_state
+            };
+
+            let grid = run(CollisionDetection::Grid);
+            let all_pairs = run(CollisionDetection::AllPairs);
+            assert_eq!(grid.tick(), 300);
+            assert_eq!(
+                grid.world().particles(),
+                all_pairs.world().particles(),
+                "{boundary:?}"
+            );
+            assert_eq!(grid.world().food(), all_pairs.world().food());
+        }
+    }
+
+    #[test]
+    fn species_interact_according_to_the_matrix() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            // Species 0 chases species 1, which flees from it
+            interaction_matrix: vec![vec![0.0, 10.0], vec![-10.0, 0.0]],
+            interaction_radius: 40.0,
+            ..SimulationConfig::default()
+        });
+        core_state.spawn_at([100.0, 100.0]);
+        core_state.spawn_at([120.0, 100.0]);
+        core_state.spawn_at([300.0, 100.0]);
+        core_state.world.particles[0].species = 0;
+        core_state.world.particles[1].species = 1;
+        core_state.world.particles[2].species = 1;
+
+        let accelerations = core_state.accelerations();
+        // Half way to the interaction radius, so half the strength
+        assert_eq!(accelerations[0], [5.0, 0.0]);
+        assert_eq!(accelerations[1], [5.0, 0.0]);
+        // Out of reach of everyone
+        assert_eq!(accelerations[2], [0.0, 0.0]);
+
+        let lopsided = SimulationConfig {
+            interaction_matrix: vec![vec![1.0, 2.0], vec![3.0]],
+            ..SimulationConfig::default()
+        };
+        assert!(lopsided.validate().is_err());
+        assert!(SimulationConfig {
+            selection: SelectionStrategy::Tournament,
+            tournament_size: 1,
+            ..SimulationConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(SimulationConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn species_pairs_bounce_with_their_own_restitution() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            food_spawn_rate: 0.0,
+            reproduction_threshold: f32::INFINITY,
+            interaction_matrix: vec![vec![0.0; 2]; 2],
+            // Species 0 bounce off each other, species 1 stick together, and mixed pairs settle
+            // somewhere in between
+            restitution_matrix: vec![vec![1.0, 0.5], vec![0.5, 0.0]],
+            ..SimulationConfig::default()
+        });
+        let pairs = [(0, 0), (1, 1), (0, 1)];
+        for (index, species) in pairs.iter().enumerate() {
+            let y = 100.0 + index as f32 * 100.0;
+            core_state.spawn_at([100.0, y]);
+            core_state.spawn_at([109.0, y]);
+            let particles = &mut core_state.world.particles[index * 2..];
+            for (particle, (species, velocity)) in particles
+                .iter_mut()
+                .zip([(species.0, 10.0), (species.1, -10.0)])
+            {
+                particle.species = species;
+                particle.velocity = [velocity, 0.0];
+                (particle.radius, particle.mass) = (5.0, 1.0);
+            }
+        }
+
+        core_state.step(1.0 / 120.0);
+
+        // Closing at 20, equal masses part at 20 times the restitution
+        let particles = core_state.world().particles();
+        for (index, restitution) in [1.0, 0.0, 0.5].into_iter().enumerate() {
+            let (a, b) = (&particles[index * 2], &particles[index * 2 + 1]);
+            assert!((a.velocity[0] + 10.0 * restitution).abs() < 1e-4, "{a:?}");
+            assert!((b.velocity[0] - 10.0 * restitution).abs() < 1e-4, "{b:?}");
+        }
+
+        // Without a matrix every pair shares the one value
+        let config = SimulationConfig {
+            collision_restitution: 0.3,
+            ..SimulationConfig::default()
+        };
+        assert_eq!(config.pair_restitution(0, 1), 0.3);
+        let lopsided = SimulationConfig {
+            interaction_matrix: vec![vec![0.0; 2]; 2],
+            restitution_matrix: vec![vec![1.0, 0.5], vec![0.2, 1.0]],
+            ..SimulationConfig::default()
+        };
+        assert!(lopsided.validate().is_err());
+        let mismatched = SimulationConfig {
+            restitution_matrix: vec![vec![1.0, 0.5], vec![0.5, 1.0]],
+            ..SimulationConfig::default()
+        };
+        assert!(mismatched.validate().is_err());
+    }
+
+    #[test]
+    fn collisions_across_a_wrapping_edge_conserve_momentum() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            boundary: BoundaryMode::Wrap,
+            gravity: [0.0, 0.0],
+            ..SimulationConfig::default()
+        });
+        let width = core_state.config().bounds[0];
+        core_state.spawn_at([1.0, 100.0]);
+        core_state.spawn_at([width - 2.0, 100.0]);
+        core_state.spawn_at([width - 0.5, 300.0]);
+        for (particle, (mass, velocity)) in core_state.world.particles.iter_mut().zip([
+            (1.0, [-30.0, 5.0]),
+            (2.0, [20.0, 0.0]),
+            (1.0, [60.0, 0.0]),
+        ]) {
+            particle.radius = 3.0;
+            particle.mass = mass;
+            particle.velocity = velocity;
+            particle.genome = Genome::default();
+        }
+        let momentum = |core_state: &CoreState| {
+            core_state
+                .world()
+                .particles()
+                .iter()
+                .fold([0.0, 0.0], |sum, particle| {
+                    [
+                        sum[0] + particle.mass * particle.velocity[0],
+                        sum[1] + particle.mass * particle.velocity[1],
+                    ]
+                })
+        };
+        let before = momentum(&core_state);
+
+        core_state.step(1.0 / 60.0);
+
+        let after = momentum(&core_state);
+        assert!((after[0] - before[0]).abs() < 1e-3 && (after[1] - before[1]).abs() < 1e-3);
+        let particles = core_state.world().particles();
+        // The pair touching through the edge bounced, they would keep their velocity otherwise
+        assert!(particles[0].velocity[0] > 0.0 && particles[1].velocity[0] < 0.0);
+        // The lone particle left through the right edge and came back in on the left
+        assert!(particles[2].position[0] < 1.0);
+        assert_eq!(particles[2].velocity, [60.0, 0.0]);
+        assert!(particles
+            .iter()
+            .all(|particle| { (0.0..width).contains(&particle.position[0]) }));
+    }
+
+    #[test]
+    fn loaded_save_continues_identically() {
+        let path = std::env::temp_dir().join("particle_evolution_save_test.ron");
+        let mut original = CoreState::new(SimulationConfig {
+            seed: 99,
+            ..SimulationConfig::default()
+        });
+        for _ in 0..100 {
+            original.step(1.0 / 60.0);
+        }
+
+        original.save(&path).unwrap();
+        let mut loaded = CoreState::load(&path).unwrap();
+        assert_eq!(loaded.tick(), 100);
+        for _ in 0..100 {
+            original.step(1.0 / 60.0);
+            loaded.step(1.0 / 60.0);
+        }
+
+        assert_eq!(loaded.world().particles(), original.world().particles());
+        assert_eq!(loaded.world().food(), original.world().food());
+
+        fs::write(&path, "particle_evolution save v0\n()").unwrap();
+        let error = CoreState::load(&path).err().unwrap();
+        assert!(error.contains("format 0"), "{error}");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn damping_slows_particles_towards_rest_without_overshooting() {
+        let config = SimulationConfig {
+            gravity: [0.0, 0.0],
+            linear_damping: 2.0,
+            boundary: BoundaryMode::Open,
+            ..SimulationConfig::default()
+        };
+        let bounds = Rect {
+            min: [0.0, 0.0],
+            max: config.bounds,
+        };
+        let mut particle = Particle::new(0, 0, [0.0, 0.0], Genome::default(), 0.0);
+        particle.velocity = [100.0, -50.0];
+
+        // A whole second per step, the linear form would flip the velocity every step
+        let mut previous = particle.velocity;
+        for _ in 0..20 {
+            integrate(&mut particle, [0.0, 0.0], 1.0, &config, &bounds);
+            assert!(particle.velocity[0] >= 0.0 && particle.velocity[0] < previous[0]);
+            assert!(particle.velocity[1] <= 0.0 && particle.velocity[1] > previous[1]);
+            previous = particle.velocity;
+        }
+        assert!(particle.velocity[0] < 1e-10 && particle.velocity[1] > -1e-10);
+
+        let undamped = SimulationConfig {
+            linear_damping: 0.0,
+            ..config
+        };
+        integrate(&mut particle, [0.0, 0.0], 1.0, &undamped, &bounds);
+        assert_eq!(particle.velocity, previous);
+    }
+
+    #[test]
+    fn nearly_coincident_particles_stay_finite_with_either_limit() {
+        let unlimited = SimulationConfig {
+            particle_count: 0,
+            boundary: BoundaryMode::Open,
+            gravity: [0.0, 0.0],
+            gravitational_constant: 50.0,
+            ..SimulationConfig::default()
+        };
+        let finite = |core_state: &CoreState| {
+            core_state.world().particles().iter().all(|particle| {
+                particle.position.iter().all(|axis| axis.is_finite())
+                    && particle.velocity.iter().all(|axis| axis.is_finite())
+            })
+        };
+        // Close enough for the cube of the distance to underflow to zero
+        let pair = |config: SimulationConfig| {
+            let mut core_state = CoreState::new(config);
+            core_state.spawn_at([0.0, 0.0]);
+            core_state.spawn_at([1e-20, 0.0]);
+            core_state
+        };
+
+        // Unlimited the pull overflows, stepping would fling the pair to infinity
+        let mut core_state = pair(unlimited.clone());
+        assert!(core_state.accelerations()[0][0].is_infinite());
+
+        let limits = [
+            SimulationConfig {
+                softening: 1.0,
+                ..unlimited.clone()
+            },
+            SimulationConfig {
+                max_acceleration: Some(1000.0),
+                ..unlimited
+            },
+        ];
+        for config in limits {
+            let mut core_state = pair(config);
+            for _ in 0..120 {
+                core_state.step(1.0 / 120.0);
+                assert!(finite(&core_state));
+            }
+        }
+    }
+
+    #[test]
+    fn clicks_pick_the_nearest_particle_or_nothing() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            ..SimulationConfig::default()
+        });
+        for position in [[100.0, 100.0], [110.0, 100.0], [400.0, 300.0]] {
+            core_state.spawn_at(position);
+        }
+
+        assert_eq!(core_state.particle_at([103.0, 101.0]), Some(0));
+        assert_eq!(core_state.particle_at([108.0, 99.0]), Some(1));
+        assert_eq!(core_state.particle_at([400.0, 300.0]), Some(2));
+        assert_eq!(core_state.particle_at([250.0, 200.0]), None);
+        assert_eq!(
+            core_state.world().particle(1).unwrap().position,
+            [110.0, 100.0]
+        );
+        assert!(core_state.world().particle(3).is_none());
+
+        core_state.world.particles.clear();
+        assert_eq!(core_state.particle_at([100.0, 100.0]), None);
+    }
+
+    #[test]
+    fn deleting_a_region_removes_only_what_is_inside() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            ..SimulationConfig::default()
+        });
+        for position in [
+            [100.0, 100.0],
+            [150.0, 120.0],
+            [300.0, 300.0],
+            [120.0, 140.0],
+        ] {
+            core_state.spawn_at(position);
+        }
+        assert!(core_state.add_bond(1, 2, 30.0, 50.0));
+        assert!(core_state.add_bond(2, 3, 30.0, 50.0));
+        core_state.step(1.0 / 120.0);
+
+        // Dragged from the bottom right to the top left, the corners come in either order
+        let region = Rect::spanning([160.0, 130.0], [90.0, 90.0]);
+        assert_eq!(core_state.delete_region(&region), 2);
+        let ids: Vec<_> = core_state
+            .world()
+            .particles()
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(core_state.world().bonds().len(), 1);
+        assert_eq!(core_state.particle_at([100.0, 100.0]), None);
+        assert_eq!(core_state.particle_at([300.0, 300.0]), Some(2));
+
+        // Survivors keep their ids and the next step sees the smaller world
+        core_state.step(1.0 / 120.0);
+        assert!(core_state.world().particle(3).is_some());
+        assert_eq!(core_state.delete_region(&region), 0);
+    }
+
+    #[test]
+    fn brush_paints_within_the_world_only() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            reproduction_threshold: f32::INFINITY,
+            ..SimulationConfig::default()
+        });
+        // Centred on the corner, a quarter of the disc lies within the world
+        core_state.set_brush(Some(Brush {
+            center: [0.0, 0.0],
+            radius: 20.0,
+            density: 0.1,
+        }));
+        for _ in 0..120 {
+            core_state.step(1.0 / 120.0);
+        }
+        core_state.set_brush(None);
+        core_state.step(1.0 / 120.0);
+
+        // About 125 painted over a second, a quarter of them kept
+        let painted = core_state.world().particles().len();
+        assert!((20..=45).contains(&painted), "{painted}");
+        // Collisions spread them out a little and can nudge them into a wall, but none started
+        // past one
+        for particle in core_state.world().particles() {
+            let [x, y] = particle.position;
+            let near_corner = -particle.radius..60.0;
+            assert!(
+                near_corner.contains(&x) && near_corner.contains(&y),
+                "{x} {y}"
+            );
+        }
+    }
+
+    #[test]
+    fn tournaments_replace_the_weakest_entrant_with_the_child_of_the_fittest() {
+        let config = SimulationConfig {
+            particle_count: 0,
+            selection: SelectionStrategy::Tournament,
+            tournament_size: 6,
+            tournament_interval: 1,
+            ..SimulationConfig::default()
+        };
+        let mut core_state = CoreState::new(config.clone());
+        for (index, energy) in [50.0, 300.0, 20.0, 80.0, 20.0, 150.0]
+            .into_iter()
+            .enumerate()
+        {
+            core_state.spawn_at([40.0 + 60.0 * index as f32, 100.0]);
+            core_state.world.particles[index].energy = energy;
+        }
+        // Everyone enters, far past the threshold only the winner has a child
+        core_state.reproduce_and_cull();
+        let particles = core_state.world().particles();
+        let ids: Vec<u64> = particles.iter().map(|particle| particle.id).collect();
+        assert_eq!(ids, [0, 1, 3, 4, 5, 6]);
+        assert_eq!((particles[1].energy, particles[5].energy), (150.0, 150.0));
+
+        // Random entrants, yet the same seed holds the same tournaments
+        let run = || {
+            let mut core_state = CoreState::new(SimulationConfig {
+                particle_count: 40,
+                tournament_size: 3,
+                tournament_interval: 10,
+                ..config.clone()
+            });
+            for _ in 0..100 {
+                core_state.step(1.0 / 120.0);
+            }
+            core_state
+                .world()
+                .particles()
+                .iter()
+                .map(|particle| (particle.id, particle.energy.to_bits()))
+                .collect::<Vec<_>>()
+        };
+        let first = run();
+        assert_eq!(first, run());
+        // One birth every ten ticks, starting on tick 0
+        assert_eq!(first.len(), 40);
+        assert_eq!(first.last().unwrap().0, 49);
+    }
+
+    #[test]
+    fn particles_die_of_old_age_whatever_their_energy() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 20,
+            max_lifetime: Some(0.5),
+            initial_energy: 1000.0,
+            reproduction_threshold: f32::INFINITY,
+            ..SimulationConfig::default()
+        });
+        for _ in 0..25 {
+            core_state.step(1.0 / 60.0);
+        }
+        let particles = core_state.world().particles();
+        assert_eq!(particles.len(), 20);
+        assert!(particles.iter().all(|p| (p.age - 25.0 / 60.0).abs() < 1e-4));
+
+        // A newcomer keeps its own clock while everyone else reaches the limit
+        core_state.spawn_at([100.0, 100.0]);
+        for _ in 0..10 {
+            core_state.step(1.0 / 60.0);
+        }
+        let ids: Vec<_> = core_state
+            .world()
+            .particles()
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(ids, [20]);
+
+        let invalid = SimulationConfig {
+            max_lifetime: Some(0.0),
+            ..SimulationConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn population_cap_culls_the_victims_of_the_policy() {
+        let config = SimulationConfig {
+            particle_count: 0,
+            max_particles: Some(8),
+            ..SimulationConfig::default()
+        };
+        // Ten particles, the odd ids are about to reproduce
+        let populated = |cull_policy| {
+            let mut core_state = CoreState::new(SimulationConfig {
+                cull_policy,
+                ..config.clone()
+            });
+            for index in 0..10 {
+                core_state.spawn_at([20.0 + 30.0 * index as f32, 100.0]);
+            }
+            for particle in &mut core_state.world.particles {
+                particle.energy = if particle.id % 2 == 1 {
+                    200.0
+                } else {
+                    10.0 + particle.id as f32
+                };
+            }
+            core_state.reproduce_and_cull();
+            core_state
+        };
+        let ids = |core_state: &CoreState| -> Vec<u64> {
+            core_state
+                .world()
+                .particles()
+                .iter()
+                .map(|particle| particle.id)
+                .collect()
+        };
+
+        // 5 births bring it to 15, so 7 have to go
+        let oldest = populated(CullPolicy::OldestDies);
+        assert_eq!(ids(&oldest), (7..15).collect::<Vec<_>>());
+
+        // The hungry even ids go first, then the oldest of the parents and children holding 100
+        let lowest_energy = populated(CullPolicy::LowestEnergyDies);
+        assert_eq!(ids(&lowest_energy), [5, 7, 9, 10, 11, 12, 13, 14]);
+
+        let random = populated(CullPolicy::RandomDies);
+        assert_eq!(ids(&random).len(), 8);
+        assert_eq!(ids(&random), ids(&populated(CullPolicy::RandomDies)));
+
+        // Unchecked reproduction never gets past the cap
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 8,
+            initial_energy: 1000.0,
+            ..config
+        });
+        for _ in 0..60 {
+            core_state.step(1.0 / 120.0);
+            assert!(core_state.world().particles().len() <= 8);
+        }
+    }
+
+    #[test]
+    fn gravity_turns_to_a_new_direction_keeping_its_strength() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            ..SimulationConfig::default()
+        });
+        core_state.turn_gravity([-1.0, 0.0]);
+        // Flipping again mid turn aims from wherever gravity is right now
+        core_state.step(GRAVITY_TURN_TIME / 2.0);
+        assert_eq!(core_state.config().gravity, [-4.905, 4.905]);
+        assert_eq!(core_state.live_config().gravity, [-9.81, 0.0]);
+        core_state.turn_gravity([0.0, -1.0]);
+        for _ in 0..3 {
+            core_state.step(GRAVITY_TURN_TIME / 2.0);
+        }
+        assert_eq!(core_state.config().gravity, [0.0, -9.81]);
+        assert!(core_state.gravity_turn.is_none());
+
+        let mut weightless = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            ..SimulationConfig::default()
+        });
+        weightless.turn_gravity([1.0, 0.0]);
+        weightless.reset();
+        assert_eq!(weightless.config().gravity, [9.81, 0.0]);
+    }
+
+    #[test]
+    fn spawned_speeds_follow_the_configured_distribution() {
+        let spawn = |initial_velocity| {
+            let config = SimulationConfig {
+                seed: 3,
+                particle_count: 5000,
+                initial_velocity,
+                ..SimulationConfig::default()
+            };
+            assert!(config.validate().is_ok());
+            CoreState::new(config).world().particles().to_vec()
+        };
+        let mean = |particles: &[Particle], value: fn(&Particle) -> f32| {
+            particles.iter().map(value).sum::<f32>() / particles.len() as f32
+        };
+        let speed = |particle: &Particle| particle.velocity[0].hypot(particle.velocity[1]);
+
+        let resting = spawn(VelocityDistribution::Zero);
+        assert!(resting
+            .iter()
+            .all(|particle| particle.velocity == [0.0, 0.0]));
+
+        let uniform = spawn(VelocityDistribution::Uniform {
+            min_speed: 20.0,
+            max_speed: 60.0,
+        });
+        assert!(uniform
+            .iter()
+            .all(|particle| (19.99..60.01).contains(&speed(particle))));
+        let mean_speed = mean(&uniform, speed);
+        assert!((mean_speed - 40.0).abs() < 1.0, "{mean_speed}");
+
+        // Equipartition, heavier particles move slower at the same kinetic energy
+        let gas = spawn(VelocityDistribution::MaxwellBoltzmann { temperature: 500.0 });
+        let mean_kinetic_energy = mean(&gas, |particle| {
+            0.5 * particle.mass * (particle.velocity[0].powi(2) + particle.velocity[1].powi(2))
+        });
+        assert!(
+            (mean_kinetic_energy - 500.0).abs() < 20.0,
+            "{mean_kinetic_energy}"
+        );
+
+        let inverted = SimulationConfig {
+            initial_velocity: VelocityDistribution::Uniform {
+                min_speed: 10.0,
+                max_speed: 5.0,
+            },
+            ..SimulationConfig::default()
+        };
+        assert!(inverted.validate().is_err());
+    }
+
+    #[test]
+    fn brownian_displacement_grows_with_the_square_root_of_time() {
+        // A lone immortal particle in open space that neither eats nor breeds
+        let config = SimulationConfig {
+            particle_count: 0,
+            boundary: BoundaryMode::Open,
+            gravity: [0.0, 0.0],
+            linear_damping: 5.0,
+            temperature: 1000.0,
+            basal_metabolism: 0.0,
+            movement_cost: 0.0,
+            ambient_energy_gain: 0.0,
+            reproduction_threshold: f32::INFINITY,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        };
+        let runs = 200;
+        let mut mean_displacement = [0.0; 2];
+        for seed in 0..runs {
+            let mut core_state = CoreState::new(SimulationConfig {
+                seed,
+                ..config.clone()
+            });
+            core_state.spawn_at([0.0, 0.0]);
+            for (checkpoint, steps) in [240, 720].into_iter().enumerate() {
+                for _ in 0..steps {
+                    core_state.step(1.0 / 120.0);
+                }
+                let [x, y] = core_state.world().particles()[0].position;
+                mean_displacement[checkpoint] += x.hypot(y) / runs as f32;
+            }
+        }
+
+        // Four times as long goes twice as far, a drifting particle would go four times as far
+        let ratio = mean_displacement[1] / mean_displacement[0];
+        assert!((1.8..2.25).contains(&ratio), "{mean_displacement:?}");
+    }
+
+    #[test]
+    fn closed_system_keeps_its_energy() {
+        // Elastic walls and collisions, no damping, no births or deaths
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 60,
+            restitution: 1.0,
+            gravitational_constant: 20.0,
+            softening: 2.0,
+            basal_metabolism: 0.0,
+            movement_cost: 0.0,
+            ambient_energy_gain: 0.0,
+            reproduction_threshold: f32::INFINITY,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        });
+        core_state.set_energy_check(Some(1.0));
+        let initial = core_state.total_energy();
+        for _ in 0..600 {
+            core_state.step(1.0 / 120.0);
+        }
+        let drift = (core_state.total_energy() - initial) / initial.abs();
+        assert!(drift.abs() < 0.01, "{drift}");
+    }
+
+    #[test]
+    fn velocity_verlet_drifts_less_on_a_two_body_orbit() {
+        // Two unit masses 100 apart, a bit slower than a circular orbit so it is eccentric
+        let gravitational_constant = 500_000.0;
+        let speed = 0.8 * (gravitational_constant / 200.0f32).sqrt();
+        let worst_drift = |integrator| {
+            let config = SimulationConfig {
+                boundary: BoundaryMode::Open,
+                gravity: [0.0, 0.0],
+                gravitational_constant,
+                theta: 0.0,
+                max_speed: f32::INFINITY,
+                integrator,
+                basal_metabolism: 0.0,
+                movement_cost: 0.0,
+                ambient_energy_gain: 0.0,
+                reproduction_threshold: f32::INFINITY,
+                food_spawn_rate: 0.0,
+                ..SimulationConfig::default()
+            };
+            let layout = vec![
+                LayoutParticle {
+                    position: [270.0, 240.0],
+                    velocity: [0.0, speed],
+                    species: 0,
+                },
+                LayoutParticle {
+                    position: [370.0, 240.0],
+                    velocity: [0.0, -speed],
+                    species: 0,
+                },
+            ];
+            let mut core_state = CoreState::from_layout(config, layout);
+            let initial = core_state.total_energy();
+            let mut worst: f64 = 0.0;
+            // A few orbits at the usual timestep
+            for _ in 0..3000 {
+                core_state.step(1.0 / 120.0);
+                worst = worst.max(((core_state.total_energy() - initial) / initial).abs());
+            }
+            worst
+        };
+
+        let euler = worst_drift(Integrator::SemiImplicitEuler);
+        let verlet = worst_drift(Integrator::VelocityVerlet);
+        assert!(verlet < euler / 10.0, "verlet {verlet}, euler {euler}");
+        assert!(verlet < 1e-3, "{verlet}");
+    }
+
+    #[test]
+    fn substeps_keep_a_dense_pile_from_sinking_into_itself() {
+        let settle = |collision_substeps| {
+            let mut core_state = CoreState::new(SimulationConfig {
+                seed: 8,
+                particle_count: 250,
+                bounds: [100.0, 100.0],
+                gravity: [0.0, 400.0],
+                collision_substeps,
+                basal_metabolism: 0.0,
+                movement_cost: 0.0,
+                ambient_energy_gain: 0.0,
+                reproduction_threshold: f32::INFINITY,
+                food_spawn_rate: 0.0,
+                ..SimulationConfig::default()
+            });
+            for _ in 0..240 {
+                core_state.step(1.0 / 120.0);
+            }
+            // Depth summed over all overlapping pairs and the energy left once the pile settled
+            let (mut overlap, mut kinetic) = (0.0, 0.0);
+            for _ in 0..60 {
+                core_state.step(1.0 / 120.0);
+                let particles = core_state.world().particles();
+                for (index, a) in particles.iter().enumerate() {
+                    kinetic += 0.5 * a.mass * (a.velocity[0].powi(2) + a.velocity[1].powi(2));
+                    for b in &particles[index + 1..] {
+                        let distance =
+                            (a.position[0] - b.position[0]).hypot(a.position[1] - b.position[1]);
+                        overlap += (a.radius + b.radius - distance).max(0.0);
+                    }
+                }
+            }
+            (overlap / 60.0, kinetic / 60.0)
+        };
+
+        let one = settle(1);
+        let eight = settle(8);
+        // Less sinking in and much less jitter heating the pile up
+        assert!(eight.0 < one.0 / 4.0, "{one:?} {eight:?}");
+        assert!(eight.1 < one.1 / 4.0, "{one:?} {eight:?}");
+
+        let none = SimulationConfig {
+            collision_substeps: 0,
+            ..SimulationConfig::default()
+        };
+        assert!(none.validate().is_err());
+    }
+
+    #[test]
+    fn bonded_pair_oscillates_around_its_rest_length() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            gravity: [0.0, 0.0],
+            ..SimulationConfig::default()
+        });
+        core_state.spawn_at([300.0, 240.0]);
+        core_state.spawn_at([350.0, 240.0]);
+        for particle in &mut core_state.world.particles {
+            particle.genome = Genome::default();
+            particle.mass = 1.0;
+        }
+        assert!(core_state.add_bond(0, 1, 30.0, 50.0));
+        assert!(!core_state.add_bond(0, 7, 30.0, 50.0));
+
+        let distance = |core_state: &CoreState| {
+            let particles = core_state.world().particles();
+            particles[1].position[0] - particles[0].position[0]
+        };
+        let mut crossings = 0;
+        let mut stretched = distance(&core_state) > 30.0;
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for _ in 0..600 {
+            core_state.step(1.0 / 120.0);
+            let distance = distance(&core_state);
+            (min, max) = (min.min(distance), max.max(distance));
+            if (distance > 30.0) != stretched {
+                stretched = !stretched;
+                crossings += 1;
+            }
+        }
+
+        // Five seconds at a period of 2 pi / sqrt(2 * 50) is about 8 full oscillations
+        assert!(crossings >= 10, "{crossings}");
+        // Swings between 50 and 10 undamped, never close enough to collide
+        assert!(
+            (max - 50.0).abs() < 1.0 && (min - 10.0).abs() < 1.0,
+            "{min} {max}"
+        );
+    }
+
+    #[test]
+    fn particle_crossing_a_corner_bounces_off_both_walls() {
+        let bounds = Rect {
+            min: [0.0, 0.0],
+            max: [100.0, 100.0],
+        };
+        let mut particle = Particle {
+            id: 0,
+            species: 0,
+            position: [105.0, -3.0],
+            velocity: [50.0, -40.0],
+            acceleration: [0.0, 0.0],
+            lineage_hue: 0.0,
+            mass: 1.0,
+            radius: 2.0,
+            genome: Genome::default(),
+            energy: 0.0,
+            age: 0.0,
+        };
+
+        let config = SimulationConfig {
+            restitution: 0.5,
+            wall_padding: 0.0,
+            ..SimulationConfig::default()
+        };
+        collide_with_walls(&mut particle, &bounds, &config);
+
+        assert_eq!(particle.position, [98.0, 2.0]);
+        assert_eq!(particle.velocity, [-25.0, 20.0]);
+    }
+
+    #[test]
+    fn particle_dropped_onto_the_floor_comes_to_rest() {
+        let drop = |wall_padding, wall_rest_speed| {
+            let mut core_state = CoreState::new(SimulationConfig {
+                particle_count: 0,
+                gravity: [0.0, 200.0],
+                restitution: 0.5,
+                wall_padding,
+                wall_rest_speed,
+                food_spawn_rate: 0.0,
+                reproduction_threshold: f32::INFINITY,
+                ..SimulationConfig::default()
+            });
+            core_state.spawn_at([320.0, 100.0]);
+            for _ in 0..1200 {
+                core_state.step(1.0 / 120.0);
+            }
+            core_state
+        };
+
+        let mut core_state = drop(0.01, 1.0);
+        let particle = core_state.world().particles()[0].clone();
+        let floor = 480.0 - particle.radius - 0.01;
+        assert_eq!(particle.position[1], floor);
+        assert_eq!(particle.velocity, [0.0, 0.0]);
+        // And stays there, touching the floor without bouncing off it again
+        for _ in 0..10 {
+            core_state.step(1.0 / 120.0);
+            let particle = &core_state.world().particles()[0];
+            assert_eq!((particle.position[1], particle.velocity[1]), (floor, 0.0));
+        }
+
+        // Without either it keeps hopping by whatever gravity added in the last step
+        let core_state = drop(0.0, 0.0);
+        assert_ne!(core_state.world().particles()[0].velocity[1], 0.0);
+
+        // Within the padding only moving into the wall bounces, moving away keeps the speed
+        let config = SimulationConfig {
+            restitution: 0.5,
+            wall_padding: 1.0,
+            wall_rest_speed: 1.0,
+            ..SimulationConfig::default()
+        };
+        let contact =
+            |position, velocity| wall_contact(position, velocity, 5.0, [0.0, 100.0], &config);
+        assert_eq!(contact(94.5, 4.0), (94.0, -2.0));
+        assert_eq!(contact(94.5, -0.5), (94.0, -0.5));
+        assert_eq!(contact(5.5, 0.5), (6.0, 0.5));
+        assert_eq!(contact(5.5, -1.0), (6.0, 0.0));
+    }
+}
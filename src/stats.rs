@@ -0,0 +1,179 @@
+use crate::simulation::CoreState;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const CSV_HEADER: &str =
+    "tick,particle_count,mean_energy,mean_speed,mean_speed_factor,mean_size_factor,mean_color_hue,generation,generation_mean_energy";
+
+// Population averages at a single tick, `None` for an empty world where there is nothing to
+// average
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub tick: u64,
+    pub particle_count: usize,
+    pub mean_energy: Option<f32>,
+    pub mean_speed: Option<f32>,
+    pub mean_speed_factor: Option<f32>,
+    pub mean_size_factor: Option<f32>,
+    // Circular mean in degrees, so 350 and 10 average to 0 rather than 180
+    pub mean_color_hue: Option<f32>,
+    // Generation by population turnover and its mean energy so far, see `GenerationTracker`
+    pub generation: u64,
+    pub generation_mean_energy: Option<f32>,
+}
+
+impl Stats {
+    pub fn of(core_state: &CoreState) -> Self {
+        let particles = core_state.world().particles();
+        let mut stats = Self {
+            tick: core_state.tick(),
+            particle_count: particles.len(),
+            generation: core_state.generation().number(),
+            generation_mean_energy: core_state.generation().mean_energy(),
+            ..Self::default()
+        };
+        if particles.is_empty() {
+            return stats;
+        }
+
+        let mut sums = [0.0_f32; 4];
+        let mut hue_vector = [0.0_f32; 2];
+        for particle in particles {
+            sums[0] += particle.energy;
+            sums[1] += (particle.velocity[0].powi(2) + particle.velocity[1].powi(2)).sqrt();
+            sums[2] += particle.genome.speed_factor;
+            sums[3] += particle.genome.size_factor;
+            let (sin, cos) = particle.genome.color_hue.to_radians().sin_cos();
+            hue_vector[0] += cos;
+            hue_vector[1] += sin;
+        }
+
+        let count = particles.len() as f32;
+        stats.mean_energy = Some(sums[0] / count);
+        stats.mean_speed = Some(sums[1] / count);
+        stats.mean_speed_factor = Some(sums[2] / count);
+        stats.mean_size_factor = Some(sums[3] / count);
+        stats.mean_color_hue = Some(
+            hue_vector[1]
+                .atan2(hue_vector[0])
+                .to_degrees()
+                .rem_euclid(360.0),
+        );
+        stats
+    }
+
+    // Fields in the order of `CSV_HEADER`, missing averages are left empty
+    pub fn csv_row(&self) -> String {
+        let mean = |mean: Option<f32>| mean.map_or_else(String::new, |mean| mean.to_string());
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.tick,
+            self.particle_count,
+            mean(self.mean_energy),
+            mean(self.mean_speed),
+            mean(self.mean_speed_factor),
+            mean(self.mean_size_factor),
+            mean(self.mean_color_hue),
+            self.generation,
+            mean(self.generation_mean_energy)
+        )
+    }
+}
+
+// Appends one row of `Stats` per tick to a CSV file. Rows are buffered, `finish` flushes them.
+pub struct StatsWriter {
+    writer: BufWriter<File>,
+    // Newest tick already written, pauses and frames without steps don't repeat rows
+    last_tick: Option<u64>,
+}
+
+impl StatsWriter {
+    // Truncates `path` and writes the header row
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{CSV_HEADER}")?;
+        Ok(Self {
+            writer,
+            last_tick: None,
+        })
+    }
+
+    // Writes a row for the current tick unless it already has one
+    pub fn record(&mut self, core_state: &CoreState) -> io::Result<()> {
+        if self.last_tick == Some(core_state.tick()) {
+            return Ok(());
+        }
+        self.last_tick = Some(core_state.tick());
+
+        writeln!(self.writer, "{}", Stats::of(core_state).csv_row())
+    }
+
+    // Flushes the buffered rows and closes the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SimulationConfig;
+
+    #[test]
+    fn writes_one_row_per_tick() {
+        let path = std::env::temp_dir().join("particle_evolution_stats_test.csv");
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 20,
+            ..SimulationConfig::default()
+        });
+        let mut writer = StatsWriter::create(&path).unwrap();
+        writer.record(&core_state).unwrap();
+        for _ in 0..3 {
+            core_state.step(1.0 / 60.0);
+            writer.record(&core_state).unwrap();
+            writer.record(&core_state).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[4].starts_with("3,"));
+        assert_eq!(lines[4].split(',').count(), CSV_HEADER.split(',').count());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn empty_worlds_have_no_averages_and_single_particles_are_their_own() {
+        let mut core_state = CoreState::new(SimulationConfig {
+            particle_count: 0,
+            food_spawn_rate: 0.0,
+            ..SimulationConfig::default()
+        });
+        core_state.step(1.0 / 60.0);
+        let stats = Stats::of(&core_state);
+        assert_eq!(
+            stats,
+            Stats {
+                tick: 1,
+                ..Stats::default()
+            }
+        );
+        assert_eq!(stats.csv_row(), "1,0,,,,,,0,");
+
+        core_state.spawn_at([100.0, 100.0]);
+        core_state.step(1.0 / 60.0);
+        let particle = &core_state.world().particles()[0];
+        let stats = Stats::of(&core_state);
+        assert_eq!(stats.mean_energy, Some(particle.energy));
+        assert_eq!(stats.mean_size_factor, Some(particle.genome.size_factor));
+        let hue = stats.mean_color_hue.unwrap();
+        assert!((hue - particle.genome.color_hue).abs() < 1e-3, "{hue}");
+        assert!(stats.generation_mean_energy.is_some());
+        for field in stats.csv_row().split(',') {
+            assert!(field.parse::<f32>().is_ok_and(f32::is_finite), "{field}");
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::config;
+use crate::simulation::CoreState;
+use crate::stats::{self, Stats};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// Summary file written when `--summary-csv` isn't given
+pub const DEFAULT_SUMMARY_PATH: &str = "sweep.csv";
+
+// Every `.ron` file directly in `dir`, sorted by name so sweeps run in a predictable order
+pub fn config_paths(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|error| format!("{}: {error}", dir.display()))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|error| format!("{}: {error}", dir.display()))?
+            .path();
+        if path.is_file() && path.extension().is_some_and(|extension| extension == "ron") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// Runs every config in `dir` for up to `ticks` steps of `dt` one after the other and writes a
+// row of final statistics for each to `summary_path`. Every run starts from a fresh `CoreState`
+// seeded by its own config, so the order of the runs doesn't change any of them. A config that
+// fails to load is reported and skipped, returns how many did.
+pub fn run(dir: &Path, ticks: u64, dt: f32, summary_path: &Path) -> Result<usize, String> {
+    let paths = config_paths(dir)?;
+    if paths.is_empty() {
+        return Err(format!("no .ron config files in {}", dir.display()));
+    }
+    let describe = |error: &dyn std::fmt::Display| format!("{}: {error}", summary_path.display());
+    let mut writer = BufWriter::new(File::create(summary_path).map_err(|error| describe(&error))?);
+    writeln!(writer, "config,seconds,{}", stats::CSV_HEADER).map_err(|error| describe(&error))?;
+
+    let mut failed = 0;
+    for (index, path) in paths.iter().enumerate() {
+        let name = path
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let progress = format!("[{}/{}] {name}", index + 1, paths.len());
+        let config = match config::load(path) {
+            Ok(config) => config,
+            Err(error) => {
+                eprintln!("{progress}: skipped, {error}");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        let mut core_state = CoreState::new(config);
+        while core_state.tick() < ticks
+            && (!core_state.world().particles().is_empty()
+                || !core_state.config().emitters.is_empty())
+        {
+            core_state.step(dt);
+        }
+        let elapsed = start.elapsed();
+        let stats = Stats::of(&core_state);
+        println!(
+            "{progress}: {} ticks in {:.1} seconds, {} particles left",
+            stats.tick,
+            elapsed.as_secs_f32(),
+            stats.particle_count
+        );
+        // Flushed per run, an interrupted sweep keeps the rows it finished
+        writeln!(
+            writer,
+            "{},{:.3},{}",
+            csv_field(&name),
+            elapsed.as_secs_f64(),
+            stats.csv_row()
+        )
+        .and_then(|_| writer.flush())
+        .map_err(|error| describe(&error))?;
+    }
+    Ok(failed)
+}
+
+// Quotes `field` if it would otherwise break the row apart
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::SimulationConfig;
+
+    #[test]
+    fn sweeps_write_one_independent_row_per_config() {
+        let dir = std::env::temp_dir().join("particle_evolution_sweep_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = |seed: u64| SimulationConfig {
+            particle_count: 40,
+            seed,
+            ..SimulationConfig::default()
+        };
+        // The same config twice with another one in between, both have to end up identical
+        for (name, seed) in [("a.ron", 1), ("b, big.ron", 2), ("c.ron", 1)] {
+            config::save(&dir.join(name), &config(seed)).unwrap();
+        }
+        fs::write(dir.join("broken.ron"), "(seed: \"many\")").unwrap();
+        fs::write(dir.join("notes.txt"), "not a config").unwrap();
+
+        let summary = std::env::temp_dir().join("particle_evolution_sweep_test.csv");
+        assert_eq!(run(&dir, 50, 1.0 / 120.0, &summary), Ok(1));
+        let contents = fs::read_to_string(&summary).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], format!("config,seconds,{}", stats::CSV_HEADER));
+        assert!(lines[1].starts_with("a.ron,"));
+        assert!(lines[2].starts_with("\"b, big.ron\","));
+
+        // Everything after the time taken
+        let results = |line: &str| line.splitn(3, ',').nth(2).unwrap().to_string();
+        assert_eq!(results(lines[1]), results(lines[3]));
+        assert!(results(lines[1]).starts_with("50,"));
+        let mut core_state = CoreState::new(config(1));
+        for _ in 0..50 {
+            core_state.step(1.0 / 120.0);
+        }
+        assert_eq!(results(lines[1]), Stats::of(&core_state).csv_row());
+
+        assert!(run(&dir.join("missing"), 50, 1.0 / 120.0, &summary).is_err());
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_file(&summary);
+    }
+}